@@ -1,3 +1,6 @@
 mod nodes;
 pub use nodes::*;
+mod span;
+pub use span::*;
+pub mod printer;
 pub mod visitor;
@@ -1,25 +1,74 @@
+use crate::ast::Span;
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
+use std::path::PathBuf;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Program {
+    /// Files the program was read from, indexed by `Span::file`.
+    /// Empty for programs parsed from text.
+    pub files: Vec<PathBuf>,
+    pub procedures: Vec<Procedure>,
     pub declarations: Option<Declarations>,
     pub commands: Commands,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Procedure {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    pub declarations: Option<Declarations>,
+    pub commands: Commands,
+    pub span: Span,
+}
+
+/// A parameter of a procedure, passed by reference.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub array: bool,
+    pub span: Span,
+}
+
+/// A variable passed to a procedure.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Argument {
+    pub name: String,
+    pub span: Span,
 }
 
 pub type Declarations = Vec<Declaration>;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Declaration {
-    Var { name: String },
-    Array { name: String, start: i64, end: i64 },
+    Var {
+        name: String,
+        span: Span,
+    },
+    Array {
+        name: String,
+        /// `(start, end)` ranges of the indices, one for every dimension.
+        dimensions: Vec<(i64, i64)>,
+        span: Span,
+    },
 }
 
 impl Declaration {
     pub fn name(&self) -> &str {
         use Declaration::*;
         match self {
-            Var { name } => name,
+            Var { name, .. } => name,
             Array { name, .. } => name,
         }
     }
+
+    pub fn span(&self) -> Span {
+        use Declaration::*;
+        match self {
+            Var { span, .. } | Array { span, .. } => *span,
+        }
+    }
 }
 
 pub type Commands = Vec<Command>;
@@ -30,38 +79,88 @@ pub enum Command {
         condition: Condition,
         positive: Commands,
         negative: Commands,
+        span: Span,
     },
     If {
         condition: Condition,
         positive: Commands,
+        span: Span,
     },
     While {
         condition: Condition,
         commands: Commands,
+        span: Span,
     },
     Do {
         commands: Commands,
         condition: Condition,
+        span: Span,
     },
     For {
         counter: String,
         ascending: bool,
         from: Value,
         to: Value,
+        /// How much the counter moves by after each iteration, 1 if not given.
+        /// Only its absolute value counts, the direction is given by `ascending`.
+        /// A loop whose step turns out to be 0 when the program runs is skipped.
+        step: Option<Value>,
         commands: Commands,
+        span: Span,
     },
     Read {
-        target: Identifier,
+        targets: Vec<Identifier>,
+        span: Span,
     },
     Write {
-        value: Value,
+        values: Vec<Value>,
+        span: Span,
     },
     Assign {
         target: Identifier,
         expr: Expression,
+        span: Span,
+    },
+    Call {
+        name: String,
+        arguments: Vec<Argument>,
+        span: Span,
+    },
+    /// Resolved by `parse_file`, which merges the included program into the including one.
+    Include {
+        path: String,
+        span: Span,
+    },
+    /// Leaves the innermost loop.
+    Break {
+        span: Span,
+    },
+    /// Skips to the next iteration of the innermost loop.
+    Continue {
+        span: Span,
     },
 }
 
+impl Command {
+    pub fn span(&self) -> Span {
+        use Command::*;
+        match self {
+            IfElse { span, .. }
+            | If { span, .. }
+            | While { span, .. }
+            | Do { span, .. }
+            | For { span, .. }
+            | Read { span, .. }
+            | Write { span, .. }
+            | Assign { span, .. }
+            | Call { span, .. }
+            | Include { span, .. }
+            | Break { span }
+            | Continue { span } => *span,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ExprOp {
     Plus,
@@ -71,18 +170,72 @@ pub enum ExprOp {
     Mod,
 }
 
+impl ExprOp {
+    /// How tightly the operator binds, higher binds tighter.
+    pub fn precedence(self) -> u8 {
+        match self {
+            ExprOp::Plus | ExprOp::Minus => 1,
+            ExprOp::Times | ExprOp::Div | ExprOp::Mod => 2,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     Simple {
         value: Value,
+        span: Span,
     },
     Compound {
-        left: Value,
+        left: Box<Expression>,
         op: ExprOp,
-        right: Value,
+        right: Box<Expression>,
+        span: Span,
     },
 }
 
+impl Expression {
+    /// Builds `left op right`, spanning from the start of `left` to the end of `right`.
+    pub fn compound(left: Expression, op: ExprOp, right: Expression) -> Self {
+        let span = left.span().to(right.span());
+        Expression::Compound {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+            span,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Simple { span, .. } | Expression::Compound { span, .. } => *span,
+        }
+    }
+
+    /// Returns the identifiers the expression reads, without the ones nested in array indices.
+    pub fn identifiers(&self) -> Vec<&Identifier> {
+        match self {
+            Expression::Simple {
+                value: Value::Identifier(identifier),
+                ..
+            } => vec![identifier],
+            Expression::Simple { .. } => vec![],
+            Expression::Compound { left, right, .. } => {
+                let mut identifiers = left.identifiers();
+                identifiers.extend(right.identifiers());
+                identifiers
+            }
+        }
+    }
+}
+
+impl From<Value> for Expression {
+    fn from(value: Value) -> Self {
+        let span = value.span();
+        Expression::Simple { value, span }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum RelOp {
     EQ,
@@ -93,40 +246,171 @@ pub enum RelOp {
     GT,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LogicOp {
+    And,
+    Or,
+}
+
+impl LogicOp {
+    /// How tightly the operator binds, higher binds tighter.
+    pub fn precedence(self) -> u8 {
+        match self {
+            LogicOp::Or => 1,
+            LogicOp::And => 2,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
-pub struct Condition {
-    pub left: Value,
-    pub op: RelOp,
-    pub right: Value,
+pub enum Condition {
+    Relation {
+        left: Value,
+        op: RelOp,
+        right: Value,
+        span: Span,
+    },
+    Not {
+        condition: Box<Condition>,
+        span: Span,
+    },
+    Compound {
+        left: Box<Condition>,
+        op: LogicOp,
+        right: Box<Condition>,
+        span: Span,
+    },
+}
+
+impl Condition {
+    /// Builds `left op right`, spanning from the start of `left` to the end of `right`.
+    pub fn compound(left: Condition, op: LogicOp, right: Condition) -> Self {
+        let span = left.span().to(right.span());
+        Condition::Compound {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+            span,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Condition::Relation { span, .. }
+            | Condition::Not { span, .. }
+            | Condition::Compound { span, .. } => *span,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value {
-    Num(i64),
+    Num {
+        value: i64,
+        span: Span,
+    },
+    #[cfg(feature = "bignum")]
+    BigNum {
+        value: BigInt,
+        span: Span,
+    },
     Identifier(Identifier),
 }
 
+impl Value {
+    pub fn span(&self) -> Span {
+        match self {
+            Value::Num { span, .. } => *span,
+            #[cfg(feature = "bignum")]
+            Value::BigNum { span, .. } => *span,
+            Value::Identifier(identifier) => identifier.span(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Identifier {
-    VarAccess { name: String },
-    ArrAccess { name: String, index: String },
-    ArrConstAccess { name: String, index: i64 },
+    VarAccess {
+        name: String,
+        span: Span,
+    },
+    ArrAccess {
+        name: String,
+        index: String,
+        span: Span,
+    },
+    ArrConstAccess {
+        name: String,
+        index: i64,
+        span: Span,
+    },
+    /// Access to an element of a one-dimensional array at an index
+    /// that is neither a variable nor a constant, e.g. `t(i PLUS 1)` or `t(u(i))`.
+    ArrExprAccess {
+        name: String,
+        index: Box<Expression>,
+        span: Span,
+    },
+    /// Access to an element of a multi-dimensional array.
+    ArrMultiAccess {
+        name: String,
+        indices: Vec<Expression>,
+        span: Span,
+    },
 }
 
 impl Identifier {
     pub fn name(&self) -> &str {
         match self {
-            Identifier::VarAccess { name } => name,
+            Identifier::VarAccess { name, .. } => name,
             Identifier::ArrAccess { name, .. } => name,
             Identifier::ArrConstAccess { name, .. } => name,
+            Identifier::ArrExprAccess { name, .. } => name,
+            Identifier::ArrMultiAccess { name, .. } => name,
+        }
+    }
+
+    /// Returns the expressions an array is indexed with, except for
+    /// variable and constant indices of one-dimensional arrays.
+    pub fn index_expressions(&self) -> &[Expression] {
+        match self {
+            Identifier::ArrExprAccess { index, .. } => std::slice::from_ref(&**index),
+            Identifier::ArrMultiAccess { indices, .. } => indices,
+            _ => &[],
         }
     }
 
     pub fn all_names(&self) -> Vec<&str> {
         match self {
-            Identifier::VarAccess { name } => vec![name],
-            Identifier::ArrAccess { name, index } => vec![name, index],
+            Identifier::VarAccess { name, .. } => vec![name],
+            Identifier::ArrAccess { name, index, .. } => vec![name, index],
             Identifier::ArrConstAccess { name, .. } => vec![name],
+            Identifier::ArrExprAccess { name, .. } | Identifier::ArrMultiAccess { name, .. } => {
+                let mut names = vec![name.as_str()];
+                for index in self.index_expressions() {
+                    for identifier in index.identifiers() {
+                        names.extend(identifier.all_names());
+                    }
+                }
+                names
+            }
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Identifier::VarAccess { span, .. }
+            | Identifier::ArrAccess { span, .. }
+            | Identifier::ArrConstAccess { span, .. }
+            | Identifier::ArrExprAccess { span, .. }
+            | Identifier::ArrMultiAccess { span, .. } => *span,
         }
     }
 }
+
+/// A `[ comment ]` from the source text, without the brackets.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
+}
@@ -0,0 +1,829 @@
+use crate::ast::visitor::{walk_command, Visitable, Visitor};
+use crate::ast::*;
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
+use std::fmt::{self, Display, Formatter};
+
+const INDENT: &str = "    ";
+const MAX_WIDTH: usize = 80;
+
+/// Renders a program back into source text with canonical layout.
+///
+/// Comments are placed before the first command that follows them in the
+/// original source, or at the end of the line they were on if that line
+/// holds the start of the previously printed command. Whitespace inside
+/// comments is collapsed to single spaces.
+pub struct Printer<'a> {
+    output: String,
+    indent: usize,
+    comments: &'a [Comment],
+    last_line: usize,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(comments: &'a [Comment]) -> Self {
+        Printer {
+            output: String::new(),
+            indent: 0,
+            comments,
+            last_line: 0,
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.output
+    }
+
+    fn start_line(&mut self) {
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.output.push('\n');
+    }
+
+    fn write(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn write_line(&mut self, text: &str) {
+        self.start_line();
+        self.write(text);
+        self.end_line();
+    }
+
+    /// Prints all remaining comments that start before `limit`.
+    fn flush_comments(&mut self, limit: usize) {
+        while let Some((comment, rest)) = self.comments.split_first() {
+            if comment.span.start >= limit {
+                break;
+            }
+            self.comments = rest;
+
+            let words: Vec<_> = comment.text.split_whitespace().collect();
+            let text = if words.is_empty() {
+                "[]".to_owned()
+            } else {
+                format!("[ {} ]", words.join(" "))
+            };
+            if comment.span.line == self.last_line && self.output.ends_with('\n') {
+                self.output.pop();
+                self.write(" ");
+                self.write(&text);
+                self.end_line();
+            } else {
+                self.write_line(&text);
+            }
+        }
+    }
+
+    fn visit_block(&mut self, commands: &Commands, end: usize) {
+        self.indent += 1;
+        self.visit_commands(commands);
+        self.flush_comments(end);
+        self.indent -= 1;
+    }
+
+    fn visit_operand<F: FnOnce(u8) -> bool>(&mut self, operand: &Expression, needs_parens: F) {
+        match operand {
+            Expression::Compound { op, .. } if needs_parens(op.precedence()) => {
+                self.write("(");
+                self.visit(operand);
+                self.write(")");
+            }
+            _ => self.visit(operand),
+        }
+    }
+
+    fn visit_condition_operand<F: FnOnce(u8) -> bool>(
+        &mut self,
+        operand: &Condition,
+        needs_parens: F,
+    ) {
+        match operand {
+            Condition::Compound { op, .. } if needs_parens(op.precedence()) => {
+                self.write("(");
+                self.visit(operand);
+                self.write(")");
+            }
+            _ => self.visit(operand),
+        }
+    }
+
+    fn visit_list<V: Visitable>(&mut self, items: &[V]) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.visit(item);
+        }
+    }
+
+    fn visit_body(&mut self, declarations: &Option<Declarations>, commands: &Commands, end: usize) {
+        if let Some(declarations) = declarations {
+            self.write_line("DECLARE");
+            self.indent += 1;
+            self.visit_declarations(declarations);
+            if let Some(last) = declarations.last() {
+                self.last_line = last.span().line;
+                self.flush_comments(last.span().end);
+            }
+            self.indent -= 1;
+        }
+
+        self.write_line("BEGIN");
+        self.visit_block(commands, end);
+        self.write_line("END");
+    }
+
+    fn block_end(commands: &Commands, default: usize) -> usize {
+        commands
+            .first()
+            .map_or(default, |command| command.span().start)
+    }
+}
+
+pub fn print(program: &Program) -> String {
+    print_with_comments(program, &[])
+}
+
+pub fn print_with_comments(program: &Program, comments: &[Comment]) -> String {
+    let mut printer = Printer::new(comments);
+    printer.visit_program(program);
+    printer.finish()
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&print(self))
+    }
+}
+
+impl Display for RelOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            RelOp::EQ => "EQ",
+            RelOp::NEQ => "NEQ",
+            RelOp::LEQ => "LEQ",
+            RelOp::LT => "LE",
+            RelOp::GEQ => "GEQ",
+            RelOp::GT => "GE",
+        };
+        f.write_str(op)
+    }
+}
+
+impl Display for ExprOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            ExprOp::Plus => "PLUS",
+            ExprOp::Minus => "MINUS",
+            ExprOp::Times => "TIMES",
+            ExprOp::Div => "DIV",
+            ExprOp::Mod => "MOD",
+        };
+        f.write_str(op)
+    }
+}
+
+impl Display for LogicOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            LogicOp::And => "AND",
+            LogicOp::Or => "OR",
+        };
+        f.write_str(op)
+    }
+}
+
+impl Display for Identifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Identifier::VarAccess { name, .. } => write!(f, "{}", name),
+            Identifier::ArrAccess { name, index, .. } => write!(f, "{}({})", name, index),
+            Identifier::ArrConstAccess { name, index, .. } => write!(f, "{}({})", name, index),
+            Identifier::ArrExprAccess { name, index, .. } => write!(f, "{}({})", name, index),
+            Identifier::ArrMultiAccess { name, indices, .. } => {
+                let indices: Vec<_> = indices.iter().map(Expression::to_string).collect();
+                write!(f, "{}({})", name, indices.join(", "))
+            }
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::new(&[]);
+        printer.visit(self);
+        f.write_str(&printer.finish())
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Num { value, .. } => write!(f, "{}", value),
+            #[cfg(feature = "bignum")]
+            Value::BigNum { value, .. } => write!(f, "{}", value),
+            Value::Identifier(identifier) => write!(f, "{}", identifier),
+        }
+    }
+}
+
+impl Display for Declaration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Declaration::Var { name, .. } => write!(f, "{}", name),
+            Declaration::Array {
+                name, dimensions, ..
+            } => {
+                let dimensions: Vec<_> = dimensions
+                    .iter()
+                    .map(|(start, end)| format!("{}:{}", start, end))
+                    .collect();
+                write!(f, "{}({})", name, dimensions.join(", "))
+            }
+        }
+    }
+}
+
+impl Visitor for Printer<'_> {
+    type Result = ();
+
+    fn visit_program(&mut self, program: &Program) -> Self::Result {
+        self.flush_comments(program.span.start);
+
+        for procedure in &program.procedures {
+            self.visit(procedure);
+            self.end_line();
+        }
+
+        self.visit_body(&program.declarations, &program.commands, program.span.end);
+        self.flush_comments(usize::MAX);
+    }
+
+    fn visit_procedure(&mut self, procedure: &Procedure) -> Self::Result {
+        self.flush_comments(procedure.span.start);
+        self.last_line = procedure.span.line;
+
+        let parameters: Vec<_> = procedure
+            .parameters
+            .iter()
+            .map(|parameter| {
+                if parameter.array {
+                    format!("T {}", parameter.name)
+                } else {
+                    parameter.name.clone()
+                }
+            })
+            .collect();
+        self.write_line(&format!(
+            "PROCEDURE {}({}) IS",
+            procedure.name,
+            parameters.join(", ")
+        ));
+        self.visit_body(
+            &procedure.declarations,
+            &procedure.commands,
+            procedure.span.end,
+        );
+    }
+
+    fn visit_declarations(&mut self, declarations: &Declarations) -> Self::Result {
+        // long lists are wrapped, with every line aligned to the same column
+        let indent_width = self.indent * INDENT.len();
+        let mut width = indent_width;
+
+        self.start_line();
+        for (i, declaration) in declarations.iter().enumerate() {
+            let text = declaration.to_string();
+            if i > 0 {
+                if width + ", ".len() + text.len() > MAX_WIDTH {
+                    self.write(",");
+                    self.end_line();
+                    self.start_line();
+                    width = indent_width;
+                } else {
+                    self.write(", ");
+                    width += ", ".len();
+                }
+            }
+            self.write(&text);
+            width += text.len();
+        }
+        self.end_line();
+    }
+
+    fn visit_declaration(&mut self, declaration: &Declaration) -> Self::Result {
+        self.write(&declaration.to_string());
+    }
+
+    fn visit_command(&mut self, command: &Command) -> Self::Result {
+        let span = command.span();
+        self.flush_comments(span.start);
+        self.last_line = span.line;
+
+        // blocks need the span of the whole command to know which comments they contain
+        match command {
+            Command::IfElse {
+                condition,
+                positive,
+                negative,
+                ..
+            } => {
+                self.start_line();
+                self.write("IF ");
+                self.visit(condition);
+                self.write(" THEN");
+                self.end_line();
+                self.visit_block(positive, Self::block_end(negative, span.end));
+                self.write_line("ELSE");
+                self.visit_block(negative, span.end);
+                self.write_line("ENDIF");
+            }
+            Command::If {
+                condition,
+                positive,
+                ..
+            } => {
+                self.start_line();
+                self.write("IF ");
+                self.visit(condition);
+                self.write(" THEN");
+                self.end_line();
+                self.visit_block(positive, span.end);
+                self.write_line("ENDIF");
+            }
+            Command::While {
+                condition,
+                commands,
+                ..
+            } => {
+                self.start_line();
+                self.write("WHILE ");
+                self.visit(condition);
+                self.write(" DO");
+                self.end_line();
+                self.visit_block(commands, span.end);
+                self.write_line("ENDWHILE");
+            }
+            Command::Do {
+                commands,
+                condition,
+                ..
+            } => {
+                self.write_line("DO");
+                self.visit_block(commands, condition.span().start);
+                self.start_line();
+                self.write("WHILE ");
+                self.visit(condition);
+                self.write(" ENDDO");
+                self.end_line();
+            }
+            Command::For {
+                counter,
+                ascending,
+                from,
+                to,
+                step,
+                commands,
+                ..
+            } => {
+                self.start_line();
+                self.write("FOR ");
+                self.write(counter);
+                self.write(" FROM ");
+                self.visit(from);
+                self.write(if *ascending { " TO " } else { " DOWNTO " });
+                self.visit(to);
+                if let Some(step) = step {
+                    self.write(" STEP ");
+                    self.visit(step);
+                }
+                self.write(" DO");
+                self.end_line();
+                self.visit_block(commands, span.end);
+                self.write_line("ENDFOR");
+            }
+            _ => walk_command(self, command),
+        }
+    }
+
+    fn visit_read_command(&mut self, targets: &[Identifier]) -> Self::Result {
+        self.start_line();
+        self.write("READ ");
+        self.visit_list(targets);
+        self.write(";");
+        self.end_line();
+    }
+
+    fn visit_write_command(&mut self, values: &[Value]) -> Self::Result {
+        self.start_line();
+        self.write("WRITE ");
+        self.visit_list(values);
+        self.write(";");
+        self.end_line();
+    }
+
+    fn visit_assign_command(&mut self, target: &Identifier, expr: &Expression) -> Self::Result {
+        self.start_line();
+        self.visit(target);
+        self.write(" ASSIGN ");
+        self.visit(expr);
+        self.write(";");
+        self.end_line();
+    }
+
+    fn visit_compound_expression(
+        &mut self,
+        left: &Expression,
+        op: &ExprOp,
+        right: &Expression,
+    ) -> Self::Result {
+        // operations are left-associative, so the right operand needs parentheses
+        // even when its operator binds as tightly as `op`
+        self.visit_operand(left, |precedence| precedence < op.precedence());
+        self.write(&format!(" {} ", op));
+        self.visit_operand(right, |precedence| precedence <= op.precedence());
+    }
+
+    fn visit_relation_condition(
+        &mut self,
+        left: &Value,
+        op: &RelOp,
+        right: &Value,
+    ) -> Self::Result {
+        self.visit(left);
+        self.write(&format!(" {} ", op));
+        self.visit(right);
+    }
+
+    fn visit_not_condition(&mut self, condition: &Condition) -> Self::Result {
+        self.write("NOT ");
+        self.visit_condition_operand(condition, |_| true);
+    }
+
+    fn visit_compound_condition(
+        &mut self,
+        left: &Condition,
+        op: &LogicOp,
+        right: &Condition,
+    ) -> Self::Result {
+        self.visit_condition_operand(left, |precedence| precedence < op.precedence());
+        self.write(&format!(" {} ", op));
+        self.visit_condition_operand(right, |precedence| precedence <= op.precedence());
+    }
+
+    fn visit_call_command(&mut self, name: &str, arguments: &[Argument]) -> Self::Result {
+        let arguments: Vec<_> = arguments
+            .iter()
+            .map(|argument| argument.name.as_str())
+            .collect();
+        self.write_line(&format!("{}({});", name, arguments.join(", ")));
+    }
+
+    fn visit_include_command(&mut self, path: &str) -> Self::Result {
+        self.write_line(&format!("INCLUDE \"{}\";", path));
+    }
+
+    fn visit_break_command(&mut self) -> Self::Result {
+        self.write_line("BREAK;");
+    }
+
+    fn visit_continue_command(&mut self) -> Self::Result {
+        self.write_line("CONTINUE;");
+    }
+
+    fn visit_num_value(&mut self, num: i64) -> Self::Result {
+        self.write(&num.to_string());
+    }
+
+    #[cfg(feature = "bignum")]
+    fn visit_big_num_value(&mut self, num: &BigInt) -> Self::Result {
+        self.write(&num.to_string());
+    }
+
+    fn visit_identifier(&mut self, identifier: &Identifier) -> Self::Result {
+        self.write(&identifier.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_ast, parse_ast_with_comments};
+
+    const SOURCE: &str = r#"
+[ header ]
+DECLARE a,b ,t(-1:3)
+BEGIN
+  READ a; [ trailing ]
+  IF a GEQ 0 THEN
+      t(a) ASSIGN a TIMES 2;
+  [ before else ]
+  ELSE WRITE t(-1); ENDIF
+  [ inside loop ]
+  FOR i FROM 1 DOWNTO b DO DO WRITE i; WHILE a LE 10 ENDDO ENDFOR
+END
+"#;
+
+    #[test]
+    fn canonical_layout() {
+        let program = parse_ast(SOURCE).unwrap();
+        let expected = "\
+DECLARE
+    a, b, t(-1:3)
+BEGIN
+    READ a;
+    IF a GEQ 0 THEN
+        t(a) ASSIGN a TIMES 2;
+    ELSE
+        WRITE t(-1);
+    ENDIF
+    FOR i FROM 1 DOWNTO b DO
+        DO
+            WRITE i;
+        WHILE a LE 10 ENDDO
+    ENDFOR
+END
+";
+
+        assert_eq!(print(&program), expected);
+        assert_eq!(parse_ast(&print(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn comments() {
+        let program = parse_ast(SOURCE).unwrap();
+        let comments: Vec<_> = ["header", "trailing", "before else", "inside loop"]
+            .iter()
+            .map(|text| {
+                let start = SOURCE.find(text).unwrap() - 2;
+                let end = start + text.len() + 4;
+                let line = SOURCE[..start].lines().count();
+                let column = start - SOURCE[..start].rfind('\n').unwrap();
+                Comment {
+                    text: format!(" {} ", text),
+                    span: Span::new(start, end, line, column),
+                }
+            })
+            .collect();
+        let expected = "\
+[ header ]
+DECLARE
+    a, b, t(-1:3)
+BEGIN
+    READ a; [ trailing ]
+    IF a GEQ 0 THEN
+        t(a) ASSIGN a TIMES 2;
+        [ before else ]
+    ELSE
+        WRITE t(-1);
+    ENDIF
+    [ inside loop ]
+    FOR i FROM 1 DOWNTO b DO
+        DO
+            WRITE i;
+        WHILE a LE 10 ENDDO
+    ENDFOR
+END
+";
+
+        assert_eq!(print_with_comments(&program, &comments), expected);
+    }
+
+    #[test]
+    fn long_declarations() {
+        let names: Vec<_> = (b'a'..=b'z')
+            .map(|c| format!("{}{}", c as char, "_".repeat(4)))
+            .collect();
+        let source = format!("DECLARE {}, t(0:9) BEGIN WRITE 0; END", names.join(","));
+        let program = parse_ast(&source).unwrap();
+        let printed = print(&program);
+        let lines: Vec<_> = printed.lines().collect();
+
+        assert_eq!(lines[1], format!("    {},", names[..11].join(", ")));
+        assert_eq!(lines[2], format!("    {},", names[11..22].join(", ")));
+        assert_eq!(lines[3], format!("    {}, t(0:9)", names[22..].join(", ")));
+        assert_eq!(lines[4], "BEGIN");
+        assert!(lines.iter().all(|line| line.len() <= MAX_WIDTH));
+    }
+
+    #[test]
+    fn parentheses() {
+        let expressions = [
+            ("a PLUS b TIMES c", "a PLUS b TIMES c"),
+            ("(a PLUS b) TIMES c", "(a PLUS b) TIMES c"),
+            ("((a MINUS b)) MINUS c", "a MINUS b MINUS c"),
+            ("a MINUS (b MINUS c)", "a MINUS (b MINUS c)"),
+            ("a DIV (b TIMES c)", "a DIV (b TIMES c)"),
+            ("(a TIMES b) MOD (c PLUS (1))", "a TIMES b MOD (c PLUS 1)"),
+        ];
+
+        for (source, expected) in expressions.iter() {
+            let source = format!("BEGIN x ASSIGN {}; END", source);
+            let program = parse_ast(&source).unwrap();
+            let printed = print(&program);
+
+            assert_eq!(
+                printed.lines().nth(1).unwrap().trim(),
+                format!("x ASSIGN {};", expected)
+            );
+            assert_eq!(parse_ast(&printed).unwrap(), program);
+        }
+    }
+
+    #[test]
+    fn condition_parentheses() {
+        let conditions = [
+            ("a EQ 0 OR b EQ 0 AND c EQ 0", "a EQ 0 OR b EQ 0 AND c EQ 0"),
+            (
+                "(a EQ 0 OR b EQ 0) AND c EQ 0",
+                "(a EQ 0 OR b EQ 0) AND c EQ 0",
+            ),
+            (
+                "a EQ 0 AND (b EQ 0 AND c EQ 0)",
+                "a EQ 0 AND (b EQ 0 AND c EQ 0)",
+            ),
+            (
+                "((a EQ 0) AND b EQ 0) AND c EQ 0",
+                "a EQ 0 AND b EQ 0 AND c EQ 0",
+            ),
+            ("NOT (a EQ 0 AND b EQ 0)", "NOT (a EQ 0 AND b EQ 0)"),
+            (
+                "NOT NOT (a LE 0) OR NOT b GE 0",
+                "NOT NOT a LE 0 OR NOT b GE 0",
+            ),
+        ];
+
+        for (source, expected) in conditions.iter() {
+            let source = format!("BEGIN IF {} THEN WRITE 0; ENDIF END", source);
+            let program = parse_ast(&source).unwrap();
+            let printed = print(&program);
+
+            assert_eq!(
+                printed.lines().nth(1).unwrap().trim(),
+                format!("IF {} THEN", expected)
+            );
+            assert_eq!(parse_ast(&printed).unwrap(), program);
+        }
+    }
+
+    #[test]
+    fn procedures() {
+        let source = "PROCEDURE swap(a,b) IS DECLARE tmp BEGIN tmp ASSIGN a; a ASSIGN b; \
+                      b ASSIGN tmp; END PROCEDURE clear(T t,n) IS BEGIN t(n) ASSIGN 0; END \
+                      DECLARE x, y BEGIN swap(x,y); clear(); END";
+        let program = parse_ast(source).unwrap();
+        let expected = "\
+PROCEDURE swap(a, b) IS
+DECLARE
+    tmp
+BEGIN
+    tmp ASSIGN a;
+    a ASSIGN b;
+    b ASSIGN tmp;
+END
+
+PROCEDURE clear(T t, n) IS
+BEGIN
+    t(n) ASSIGN 0;
+END
+
+DECLARE
+    x, y
+BEGIN
+    swap(x, y);
+    clear();
+END
+";
+
+        assert_eq!(print(&program), expected);
+        assert_eq!(parse_ast(&print(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn multiple_targets() {
+        let program = parse_ast("BEGIN READ a,b , t(a); WRITE a,5,t(-1); END").unwrap();
+        let expected = "\
+BEGIN
+    READ a, b, t(a);
+    WRITE a, 5, t(-1);
+END
+";
+
+        assert_eq!(print(&program), expected);
+    }
+
+    #[test]
+    fn for_step() {
+        let source = "BEGIN FOR i FROM 0 TO n STEP 2 DO FOR j FROM i DOWNTO 0 STEP s DO WRITE j; ENDFOR ENDFOR END";
+        let program = parse_ast(source).unwrap();
+        let expected = "\
+BEGIN
+    FOR i FROM 0 TO n STEP 2 DO
+        FOR j FROM i DOWNTO 0 STEP s DO
+            WRITE j;
+        ENDFOR
+    ENDFOR
+END
+";
+
+        assert_eq!(print(&program), expected);
+        assert_eq!(parse_ast(&print(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let source = "BEGIN DO IF a EQ 0 THEN BREAK; ELSE CONTINUE; ENDIF WHILE a GE 0 ENDDO END";
+        let program = parse_ast(source).unwrap();
+        let expected = "\
+BEGIN
+    DO
+        IF a EQ 0 THEN
+            BREAK;
+        ELSE
+            CONTINUE;
+        ENDIF
+    WHILE a GE 0 ENDDO
+END
+";
+
+        assert_eq!(print(&program), expected);
+        assert_eq!(parse_ast(&print(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn multi_dimensional_arrays() {
+        let source = "DECLARE t(0:9,-5:5), n BEGIN READ t(n,-5); WRITE t( 1 , n ); END";
+        let program = parse_ast(source).unwrap();
+        let expected = "\
+DECLARE
+    t(0:9, -5:5), n
+BEGIN
+    READ t(n, -5);
+    WRITE t(1, n);
+END
+";
+
+        assert_eq!(print(&program), expected);
+        assert_eq!(parse_ast(&print(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn expression_indices() {
+        let source =
+            "BEGIN t(i PLUS 1) ASSIGN t(u( j )); WRITE m((a PLUS b) TIMES 2, u(i MINUS 1)); END";
+        let program = parse_ast(source).unwrap();
+        let expected = "\
+BEGIN
+    t(i PLUS 1) ASSIGN t(u(j));
+    WRITE m((a PLUS b) TIMES 2, u(i MINUS 1));
+END
+";
+
+        assert_eq!(print(&program), expected);
+        assert_eq!(parse_ast(&print(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn includes() {
+        let source =
+            "BEGIN INCLUDE \"lib/[gcd].imp\"; IF a EQ 0 THEN INCLUDE \"zero.imp\"; ENDIF END";
+        let (program, comments) = parse_ast_with_comments(source, Default::default()).unwrap();
+        let expected = "\
+BEGIN
+    INCLUDE \"lib/[gcd].imp\";
+    IF a EQ 0 THEN
+        INCLUDE \"zero.imp\";
+    ENDIF
+END
+";
+
+        assert!(comments.is_empty());
+        assert_eq!(print(&program), expected);
+        assert_eq!(parse_ast(&print(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn normalized_comments() {
+        let program = parse_ast("  BEGIN WRITE 0; END").unwrap();
+        let comments = [
+            Comment {
+                text: "  spread\n   over   lines ".to_owned(),
+                span: Span::new(0, 0, 1, 1),
+            },
+            Comment {
+                text: " ".to_owned(),
+                span: Span::new(0, 0, 1, 1),
+            },
+        ];
+
+        assert_eq!(
+            print_with_comments(&program, &comments),
+            "[ spread over lines ]\n[]\nBEGIN\n    WRITE 0;\nEND\n"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Location of a node in the source text.
+///
+/// `start` and `end` are byte offsets, `line` and `column` describe the
+/// position of `start` and are counted from 1. `file` is the index of the
+/// source file in `Program::files`, the main file being 0.
+///
+/// Spans never take part in comparisons, so that trees parsed
+/// from differently formatted sources are still equal.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub file: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Span {
+            start,
+            end,
+            line,
+            column,
+            file: 0,
+        }
+    }
+
+    /// Returns the same span in the given source file.
+    pub fn in_file(self, file: usize) -> Span {
+        Span { file, ..self }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns a span that starts with `self` and ends with `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end, self.line, self.column).in_file(self.file)
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Self {
+        let (line, column) = span.start_pos().line_col();
+        Span::new(span.start(), span.end(), line, column)
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
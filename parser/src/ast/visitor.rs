@@ -1,4 +1,6 @@
 use crate::ast::*;
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
 
 pub trait Visitable {
     fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Result;
@@ -10,6 +12,12 @@ impl Visitable for Program {
     }
 }
 
+impl Visitable for Procedure {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Result {
+        visitor.visit_procedure(self)
+    }
+}
+
 impl Visitable for Declaration {
     fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Result {
         visitor.visit_declaration(self)
@@ -182,15 +190,21 @@ pub trait Visitor: Sized {
     }
 
     fn visit_program(&mut self, program: &Program) -> Self::Result {
+        let res = self.visit_collection(&program.procedures);
+
         let res = if let Some(declarations) = &program.declarations {
-            self.visit_declarations(declarations)
+            res.combine(self.visit_declarations(declarations))
         } else {
-            Self::Result::identity()
+            res
         };
 
         res.combine(self.visit_commands(&program.commands))
     }
 
+    fn visit_procedure(&mut self, procedure: &Procedure) -> Self::Result {
+        walk_procedure(self, procedure)
+    }
+
     fn visit_declarations(&mut self, declarations: &Declarations) -> Self::Result {
         self.visit_collection(declarations)
     }
@@ -226,59 +240,49 @@ pub trait Visitor: Sized {
         _ascending: bool,
         from: &Value,
         to: &Value,
+        step: Option<&Value>,
         commands: &Commands,
     ) -> Self::Result {
         self.visit(from)
             .combine(self.visit(to))
+            .combine(self.visit_collection(step))
             .combine(self.visit_commands(commands))
     }
 
-    fn visit_read_command(&mut self, target: &Identifier) -> Self::Result {
-        self.visit(target)
+    fn visit_read_command(&mut self, targets: &[Identifier]) -> Self::Result {
+        self.visit_collection(targets)
     }
 
-    fn visit_write_command(&mut self, value: &Value) -> Self::Result {
-        self.visit(value)
+    fn visit_write_command(&mut self, values: &[Value]) -> Self::Result {
+        self.visit_collection(values)
     }
 
     fn visit_assign_command(&mut self, target: &Identifier, expr: &Expression) -> Self::Result {
         self.visit(target).combine(self.visit(expr))
     }
 
+    fn visit_call_command(&mut self, _name: &str, _arguments: &[Argument]) -> Self::Result {
+        Self::Result::identity()
+    }
+
+    fn visit_include_command(&mut self, _path: &str) -> Self::Result {
+        Self::Result::identity()
+    }
+
+    fn visit_break_command(&mut self) -> Self::Result {
+        Self::Result::identity()
+    }
+
+    fn visit_continue_command(&mut self) -> Self::Result {
+        Self::Result::identity()
+    }
+
     fn visit_commands(&mut self, commands: &Commands) -> Self::Result {
         self.visit_collection(commands)
     }
 
     fn visit_command(&mut self, command: &Command) -> Self::Result {
-        match command {
-            Command::IfElse {
-                condition,
-                positive,
-                negative,
-            } => self.visit_if_else_command(condition, positive, negative),
-            Command::If {
-                condition,
-                positive,
-            } => self.visit_if_command(condition, positive),
-            Command::While {
-                condition,
-                commands,
-            } => self.visit_while_command(condition, commands),
-            Command::Do {
-                commands,
-                condition,
-            } => self.visit_do_command(commands, condition),
-            Command::For {
-                counter,
-                ascending,
-                from,
-                to,
-                commands,
-            } => self.visit_for_command(counter, *ascending, from, to, commands),
-            Command::Read { target } => self.visit_read_command(target),
-            Command::Write { value } => self.visit_write_command(value),
-            Command::Assign { target, expr } => self.visit_assign_command(target, expr),
-        }
+        walk_command(self, command)
     }
 
     fn visit_simple_expression(&mut self, value: &Value) -> Self::Result {
@@ -287,38 +291,127 @@ pub trait Visitor: Sized {
 
     fn visit_compound_expression(
         &mut self,
-        left: &Value,
+        left: &Expression,
         _op: &ExprOp,
-        right: &Value,
+        right: &Expression,
     ) -> Self::Result {
         self.visit(left).combine(self.visit(right))
     }
 
     fn visit_expression(&mut self, expr: &Expression) -> Self::Result {
         match expr {
-            Expression::Simple { value } => self.visit_simple_expression(value),
-            Expression::Compound { left, op, right } => {
-                self.visit_compound_expression(left, op, right)
-            }
+            Expression::Simple { value, .. } => self.visit_simple_expression(value),
+            Expression::Compound {
+                left, op, right, ..
+            } => self.visit_compound_expression(left, op, right),
         }
     }
 
+    fn visit_relation_condition(
+        &mut self,
+        left: &Value,
+        _op: &RelOp,
+        right: &Value,
+    ) -> Self::Result {
+        self.visit(left).combine(self.visit(right))
+    }
+
+    fn visit_not_condition(&mut self, condition: &Condition) -> Self::Result {
+        self.visit(condition)
+    }
+
+    fn visit_compound_condition(
+        &mut self,
+        left: &Condition,
+        _op: &LogicOp,
+        right: &Condition,
+    ) -> Self::Result {
+        self.visit(left).combine(self.visit(right))
+    }
+
     fn visit_condition(&mut self, condition: &Condition) -> Self::Result {
-        self.visit(&condition.left)
-            .combine(self.visit(&condition.right))
+        match condition {
+            Condition::Relation {
+                left, op, right, ..
+            } => self.visit_relation_condition(left, op, right),
+            Condition::Not { condition, .. } => self.visit_not_condition(condition),
+            Condition::Compound {
+                left, op, right, ..
+            } => self.visit_compound_condition(left, op, right),
+        }
     }
 
     fn visit_num_value(&mut self, num: i64) -> Self::Result;
+    #[cfg(feature = "bignum")]
+    fn visit_big_num_value(&mut self, num: &BigInt) -> Self::Result;
     fn visit_identifier_value(&mut self, identifier: &Identifier) -> Self::Result {
         self.visit(identifier)
     }
 
     fn visit_value(&mut self, value: &Value) -> Self::Result {
         match value {
-            Value::Num(num) => self.visit_num_value(*num),
+            Value::Num { value, .. } => self.visit_num_value(*value),
+            #[cfg(feature = "bignum")]
+            Value::BigNum { value, .. } => self.visit_big_num_value(value),
             Value::Identifier(identifier) => self.visit_identifier_value(identifier),
         }
     }
 
     fn visit_identifier(&mut self, identifier: &Identifier) -> Self::Result;
 }
+
+/// Default traversal of `Visitor::visit_procedure`, usable from implementations that override it.
+pub fn walk_procedure<V: Visitor>(visitor: &mut V, procedure: &Procedure) -> V::Result {
+    let res = if let Some(declarations) = &procedure.declarations {
+        visitor.visit_declarations(declarations)
+    } else {
+        V::Result::identity()
+    };
+
+    res.combine(visitor.visit_commands(&procedure.commands))
+}
+
+/// Default dispatch of `Visitor::visit_command`, usable from implementations that override it.
+pub fn walk_command<V: Visitor>(visitor: &mut V, command: &Command) -> V::Result {
+    match command {
+        Command::IfElse {
+            condition,
+            positive,
+            negative,
+            ..
+        } => visitor.visit_if_else_command(condition, positive, negative),
+        Command::If {
+            condition,
+            positive,
+            ..
+        } => visitor.visit_if_command(condition, positive),
+        Command::While {
+            condition,
+            commands,
+            ..
+        } => visitor.visit_while_command(condition, commands),
+        Command::Do {
+            commands,
+            condition,
+            ..
+        } => visitor.visit_do_command(commands, condition),
+        Command::For {
+            counter,
+            ascending,
+            from,
+            to,
+            step,
+            commands,
+            ..
+        } => visitor.visit_for_command(counter, *ascending, from, to, step.as_ref(), commands),
+        Command::Read { targets, .. } => visitor.visit_read_command(targets),
+        Command::Write { values, .. } => visitor.visit_write_command(values),
+        Command::Assign { target, expr, .. } => visitor.visit_assign_command(target, expr),
+        Command::Call {
+            name, arguments, ..
+        } => visitor.visit_call_command(name, arguments),
+        Command::Include { path, .. } => visitor.visit_include_command(path),
+        Command::Break { .. } => visitor.visit_break_command(),
+        Command::Continue { .. } => visitor.visit_continue_command(),
+    }
+}
@@ -0,0 +1,174 @@
+use crate::ast::Span;
+use crate::Rule;
+use pest::error::{ErrorVariant, InputLocation, LineColLocation};
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    UnexpectedToken {
+        expected: Vec<Rule>,
+        found: String,
+        span: Span,
+    },
+    UnexpectedEnd {
+        expected: Vec<Rule>,
+        span: Span,
+    },
+    IntegerOverflow {
+        literal: String,
+        span: Span,
+    },
+    IncludeCycle {
+        path: PathBuf,
+        span: Span,
+    },
+    /// An error in an included file, `path` is the file its span refers to.
+    InFile {
+        path: PathBuf,
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    pub(crate) fn from_pest(error: pest::error::Error<Rule>, text: &str) -> Self {
+        let start = match error.location {
+            InputLocation::Pos(pos) => pos,
+            InputLocation::Span((start, _)) => start,
+        };
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(line_col) => line_col,
+            LineColLocation::Span(line_col, _) => line_col,
+        };
+
+        let expected = match error.variant {
+            ErrorVariant::ParsingError { positives, .. } => positives,
+            ErrorVariant::CustomError { .. } => vec![],
+        };
+
+        let rest = &text[start..];
+        let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+        let found = match rest.chars().next() {
+            Some(c) if is_word(c) => rest.chars().take_while(|&c| is_word(c)).collect(),
+            Some(c) => c.to_string(),
+            None => String::new(),
+        };
+        let span = Span::new(start, start + found.len(), line, column);
+
+        if found.is_empty() {
+            Error::UnexpectedEnd { expected, span }
+        } else {
+            Error::UnexpectedToken {
+                expected,
+                found,
+                span,
+            }
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        use Error::*;
+        match self {
+            Io { .. } => None,
+            UnexpectedToken { span, .. }
+            | UnexpectedEnd { span, .. }
+            | IntegerOverflow { span, .. }
+            | IncludeCycle { span, .. } => Some(*span),
+            InFile { error, .. } => error.span(),
+        }
+    }
+
+    /// Returns the included file the span of the error refers to,
+    /// or `None` if it refers to the file that was parsed.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::InFile { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+fn describe_rule(rule: &Rule) -> &'static str {
+    match rule {
+        Rule::EOI => "end of input",
+        Rule::pidentifier => "identifier",
+        Rule::num => "number",
+        Rule::declarations | Rule::declaration | Rule::arr_decl | Rule::var_decl => "declaration",
+        Rule::arr_range => "array range",
+        Rule::program | Rule::program_text => "`PROCEDURE`, `DECLARE` or `BEGIN`",
+        Rule::procedure => "procedure",
+        Rule::parameters | Rule::parameter | Rule::arr_param | Rule::var_param => "parameter",
+        Rule::arguments | Rule::argument => "argument",
+        Rule::string | Rule::string_text => "string",
+        Rule::commands
+        | Rule::command
+        | Rule::cmd_ifelse
+        | Rule::cmd_if
+        | Rule::cmd_while
+        | Rule::cmd_do
+        | Rule::cmd_for
+        | Rule::cmd_read
+        | Rule::cmd_write
+        | Rule::cmd_call
+        | Rule::cmd_include
+        | Rule::cmd_break
+        | Rule::cmd_continue
+        | Rule::cmd_assign => "command",
+        Rule::cmd_for_step => "`TO` or `DOWNTO`",
+        Rule::add_op | Rule::mul_op => "arithmetic operator",
+        Rule::expression | Rule::term | Rule::factor => "expression",
+        Rule::relop => "relational operator",
+        Rule::condition | Rule::conjunction | Rule::negation | Rule::relation => "condition",
+        Rule::value => "value",
+        Rule::identifier => "variable",
+        _ => "valid syntax",
+    }
+}
+
+fn write_expected(f: &mut Formatter<'_>, expected: &[Rule]) -> fmt::Result {
+    let mut descriptions: Vec<&str> = Vec::with_capacity(expected.len());
+    for description in expected.iter().map(describe_rule) {
+        if !descriptions.contains(&description) {
+            descriptions.push(description);
+        }
+    }
+
+    match descriptions.split_last() {
+        None => write!(f, "unexpected input"),
+        Some((last, [])) => write!(f, "expected {}", last),
+        Some((last, rest)) => write!(f, "expected {} or {}", rest.join(", "), last),
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            Io { path, error } => write!(f, "couldn't read {}: {}", path.display(), error),
+            UnexpectedToken {
+                expected, found, ..
+            } => {
+                write_expected(f, expected)?;
+                write!(f, ", found `{}`", found)
+            }
+            UnexpectedEnd { expected, .. } => {
+                write_expected(f, expected)?;
+                write!(f, ", found end of input")
+            }
+            IntegerOverflow { literal, .. } => {
+                write!(f, "integer literal {} doesn't fit in 64 bits", literal)
+            }
+            IncludeCycle { path, .. } => {
+                write!(f, "{} includes itself", path.display())
+            }
+            InFile { error, .. } => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
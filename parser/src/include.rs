@@ -0,0 +1,321 @@
+use crate::ast::*;
+use crate::{parse_source, Error, Options};
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+type IncludeResult<T> = Result<T, Error>;
+
+struct Resolver {
+    options: Options,
+    /// Paths of the loaded files as they were first written, for display.
+    files: Vec<PathBuf>,
+    /// Canonical paths of the loaded files, in the same order.
+    canonical_files: Vec<PathBuf>,
+    /// Canonical paths of the files being resolved, the innermost one last.
+    stack: Vec<PathBuf>,
+}
+
+pub(crate) fn parse_file(path: &Path, options: Options) -> IncludeResult<Program> {
+    let mut resolver = Resolver {
+        options,
+        files: vec![],
+        canonical_files: vec![],
+        stack: vec![],
+    };
+
+    let mut program = resolver.load(path)?;
+    program.files = resolver.files;
+    Ok(program)
+}
+
+fn merge<T: PartialEq>(items: &mut Vec<T>, item: T) {
+    if !items.contains(&item) {
+        items.push(item);
+    }
+}
+
+impl Resolver {
+    fn load(&mut self, path: &Path) -> IncludeResult<Program> {
+        let io_error = |error| Error::Io {
+            path: path.to_owned(),
+            error,
+        };
+        let canonical = fs::canonicalize(path).map_err(io_error)?;
+        let text = fs::read_to_string(path).map_err(io_error)?;
+
+        // a file included more than once keeps its index, however its path is written
+        let file = match self
+            .canonical_files
+            .iter()
+            .position(|file| *file == canonical)
+        {
+            Some(file) => file,
+            None => {
+                self.files.push(path.to_owned());
+                self.canonical_files.push(canonical.clone());
+                self.files.len() - 1
+            }
+        };
+        self.stack.push(canonical);
+        let result = self.resolve_program(&text, path, file);
+        self.stack.pop();
+
+        // errors from nested includes already know their file
+        result.map_err(|error| match error {
+            Error::Io { .. } | Error::InFile { .. } => error,
+            error if file == 0 => error,
+            error => Error::InFile {
+                path: path.to_owned(),
+                error: Box::new(error),
+            },
+        })
+    }
+
+    fn include(&mut self, path: &Path, span: Span) -> IncludeResult<Program> {
+        let canonical = fs::canonicalize(path).map_err(|error| Error::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        if self.stack.contains(&canonical) {
+            return Err(Error::IncludeCycle {
+                path: path.to_owned(),
+                span,
+            });
+        }
+
+        self.load(path)
+    }
+
+    fn resolve_program(&mut self, text: &str, path: &Path, file: usize) -> IncludeResult<Program> {
+        let program = parse_source(text, self.options, file)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        // included procedures go first, so that the procedures of this file can call them
+        let mut procedures = vec![];
+        let mut own_procedures = Vec::with_capacity(program.procedures.len());
+        for mut procedure in program.procedures {
+            let commands = mem::take(&mut procedure.commands);
+            procedure.commands = self.resolve_commands(
+                commands,
+                directory,
+                &mut procedure.declarations,
+                &mut procedures,
+            )?;
+            own_procedures.push(procedure);
+        }
+
+        let mut declarations = program.declarations;
+        let commands = self.resolve_commands(
+            program.commands,
+            directory,
+            &mut declarations,
+            &mut procedures,
+        )?;
+
+        for procedure in own_procedures {
+            merge(&mut procedures, procedure);
+        }
+
+        Ok(Program {
+            files: vec![],
+            procedures,
+            declarations,
+            commands,
+            span: program.span,
+        })
+    }
+
+    /// Replaces the includes among the commands with the included commands,
+    /// adding the included declarations and procedures to the given ones.
+    fn resolve_commands(
+        &mut self,
+        commands: Commands,
+        directory: &Path,
+        declarations: &mut Option<Declarations>,
+        procedures: &mut Vec<Procedure>,
+    ) -> IncludeResult<Commands> {
+        let mut resolved = Vec::with_capacity(commands.len());
+
+        for mut command in commands {
+            if let Command::Include { path, span } = command {
+                let included = self.include(&directory.join(path), span)?;
+                for declaration in included.declarations.into_iter().flatten() {
+                    merge(declarations.get_or_insert_with(Vec::new), declaration);
+                }
+                for procedure in included.procedures {
+                    merge(procedures, procedure);
+                }
+                resolved.extend(included.commands);
+                continue;
+            }
+
+            let blocks = match &mut command {
+                Command::IfElse {
+                    positive, negative, ..
+                } => vec![positive, negative],
+                Command::If { positive, .. } => vec![positive],
+                Command::While { commands, .. }
+                | Command::Do { commands, .. }
+                | Command::For { commands, .. } => vec![commands],
+                _ => vec![],
+            };
+            for block in blocks {
+                let commands = mem::take(block);
+                *block = self.resolve_commands(commands, directory, declarations, procedures)?;
+            }
+            resolved.push(command);
+        }
+
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_file;
+
+    /// Writes the files into a fresh directory and returns its path.
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("gembiler-include-{}-{}", name, std::process::id()));
+        for (path, text) in files {
+            let path = directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        directory
+    }
+
+    const MAIN: &str = r#"
+DECLARE a, b
+BEGIN
+    READ a;
+    READ b;
+    INCLUDE "lib/gcd.imp";
+    WRITE a;
+    IF a GE 1 THEN
+        INCLUDE "lib/../lib/gcd.imp";
+    ENDIF
+END
+"#;
+
+    const GCD: &str = r#"
+PROCEDURE swap(x, y) IS
+DECLARE
+    t
+BEGIN
+    t ASSIGN x;
+    x ASSIGN y;
+    y ASSIGN t;
+END
+DECLARE a, b, c
+BEGIN
+    INCLUDE "zero.imp";
+    WHILE b NEQ 0 DO
+        c ASSIGN a MOD b;
+        swap(a, b);
+        b ASSIGN c;
+    ENDWHILE
+END
+"#;
+
+    const ZERO: &str = "DECLARE c BEGIN c ASSIGN 0; END";
+
+    #[test]
+    fn merges_included_programs() {
+        let directory = write_files(
+            "merge",
+            &[
+                ("main.imp", MAIN),
+                ("lib/gcd.imp", GCD),
+                ("lib/zero.imp", ZERO),
+            ],
+        );
+        let main = directory.join("main.imp");
+        let program = parse_file(&main).unwrap();
+
+        assert_eq!(
+            program.files,
+            vec![
+                main.clone(),
+                directory.join("lib/gcd.imp"),
+                directory.join("lib/zero.imp")
+            ]
+        );
+
+        let procedures: Vec<_> = program.procedures.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(procedures, ["swap"]);
+        let declarations: Vec<_> = program
+            .declarations
+            .iter()
+            .flatten()
+            .map(Declaration::name)
+            .collect();
+        assert_eq!(declarations, ["a", "b", "c"]);
+
+        let commands = &program.commands;
+        assert_eq!(commands.len(), 6);
+        assert!(matches!(commands[2], Command::Assign { .. }));
+        assert_eq!(commands[2].span().file, 2);
+        assert!(matches!(commands[3], Command::While { .. }));
+        assert_eq!(commands[3].span().file, 1);
+        assert_eq!(commands[3].span().line, 13);
+        assert_eq!(commands[4].span().file, 0);
+        match &commands[5] {
+            Command::If { positive, .. } => {
+                assert_eq!(positive.len(), 2);
+                // the same file, included through another path
+                assert_eq!(positive[1].span().file, 1);
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn include_cycle() {
+        let directory = write_files(
+            "cycle",
+            &[
+                ("a.imp", "BEGIN INCLUDE \"b.imp\"; END"),
+                ("b.imp", "BEGIN WRITE 1;\nINCLUDE \"a.imp\"; END"),
+            ],
+        );
+        let error = parse_file(directory.join("a.imp")).unwrap_err();
+
+        assert_eq!(error.path(), Some(directory.join("b.imp").as_path()));
+        assert_eq!(error.span().map(|span| span.line), Some(2));
+        match error {
+            Error::InFile { error, .. } => {
+                assert!(matches!(*error, Error::IncludeCycle { .. }));
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn errors_in_included_files() {
+        let directory = write_files(
+            "errors",
+            &[
+                ("main.imp", "BEGIN INCLUDE \"broken.imp\"; END"),
+                ("broken.imp", "BEGIN\n    WRITE ; END"),
+                ("missing.imp", "BEGIN INCLUDE \"nowhere.imp\"; END"),
+            ],
+        );
+
+        let error = parse_file(directory.join("main.imp")).unwrap_err();
+        assert_eq!(error.path(), Some(directory.join("broken.imp").as_path()));
+        assert_eq!(
+            error.span().map(|span| (span.line, span.column)),
+            Some((2, 11))
+        );
+
+        let error = parse_file(directory.join("missing.imp")).unwrap_err();
+        match error {
+            Error::Io { path, .. } => assert_eq!(path, directory.join("nowhere.imp")),
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+}
@@ -3,246 +3,518 @@ extern crate pest;
 extern crate pest_derive;
 
 pub mod ast;
+mod error;
+mod include;
+mod trivia;
 
 use crate::ast::*;
-use pest::iterators::Pairs;
+pub use crate::error::Error;
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
+use pest::iterators::{Pair, Pairs};
 use pest::Parser;
-use std::fs;
 use std::path::Path;
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[grammar = "program.pest"]
 struct ProgramParser;
 
-type AstResult = Result<ast::Program, String>;
+type AstResult = Result<ast::Program, Error>;
+type ParseResult<T> = Result<T, Error>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Accept integer literals that don't fit in 64 bits as `Value::BigNum`
+    /// instead of reporting them as errors.
+    #[cfg(feature = "bignum")]
+    pub big_literals: bool,
+}
 
 pub fn parse_file<P: AsRef<Path>>(path: P) -> AstResult {
-    let program_text = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    parse_ast(&program_text)
+    parse_file_with_options(path, Options::default())
+}
+
+/// Parses the program in the file, along with the files it includes.
+///
+/// `INCLUDE` paths are relative to the including file. The procedures of an
+/// included program are placed before those of the including one, its
+/// declarations are added to the including scope and its commands replace
+/// the directive. Declarations and procedures identical to ones already
+/// in scope are merged, so the same file can be included more than once.
+pub fn parse_file_with_options<P: AsRef<Path>>(path: P, options: Options) -> AstResult {
+    include::parse_file(path.as_ref(), options)
 }
 
 pub fn parse_ast(text: &str) -> AstResult {
-    let mut program: Pairs<Rule> =
-        ProgramParser::parse(Rule::program, text).map_err(|e| e.to_string())?;
-
-    program = program
-        .next()
-        .unwrap()
-        .into_inner()
-        .next()
-        .unwrap()
-        .into_inner();
-
-    let optional_declarations = program.next().unwrap();
-
-    let (declarations, commands) = match optional_declarations.as_rule() {
-        Rule::declarations => {
-            let pairs = optional_declarations.into_inner();
-            (Some(parse_declarations(pairs)), program.next().unwrap())
-        }
-        Rule::commands => (None, optional_declarations),
-        _ => unreachable!(),
-    };
+    parse_ast_with_options(text, Options::default())
+}
 
-    let commands = parse_commands(commands.into_inner());
+pub fn parse_ast_with_options(text: &str, options: Options) -> AstResult {
+    parse_source(text, options, 0)
+}
+
+/// Parses the text of the source file with the given index in `Program::files`.
+fn parse_source(text: &str, options: Options, file: usize) -> AstResult {
+    let mut program: Pairs<Rule> =
+        ProgramParser::parse(Rule::program, text).map_err(|error| Error::from_pest(error, text))?;
 
-    Ok(ast::Program {
-        declarations,
-        commands,
-    })
+    AstBuilder { options, file }.parse_program(program.next().unwrap())
 }
 
-fn parse_declaration(mut pairs: Pairs<Rule>) -> Declaration {
-    let declaration = pairs.next().unwrap();
-    match declaration.as_rule() {
-        Rule::arr_decl => {
-            let mut parts = declaration.into_inner();
-            Declaration::Array {
-                name: parts.next().unwrap().as_str().to_owned(),
-                start: parts.next().unwrap().as_str().parse().unwrap(),
-                end: parts.next().unwrap().as_str().parse().unwrap(),
-            }
+/// Parses a program together with the comments of its source text.
+///
+/// Keywords written in mixed case, like `While`, are accepted and parsed as
+/// if they were upper case, since identifiers can't contain capital letters.
+pub fn parse_ast_with_comments(
+    text: &str,
+    options: Options,
+) -> Result<(ast::Program, Vec<Comment>), Error> {
+    match trivia::scan(text) {
+        Some(trivia) => {
+            let program = parse_ast_with_options(&trivia.text, options)?;
+            Ok((program, trivia.comments))
         }
-        Rule::var_decl => Declaration::Var {
-            name: declaration.into_inner().next().unwrap().as_str().to_owned(),
-        },
-        _ => unreachable!(),
+        None => parse_ast_with_options(text, options).map(|program| (program, vec![])),
     }
 }
 
-fn parse_declarations(pairs: Pairs<Rule>) -> Declarations {
-    pairs
-        .map(|pair| parse_declaration(pair.into_inner()))
-        .collect()
+struct AstBuilder {
+    #[cfg_attr(not(feature = "bignum"), allow(dead_code))]
+    options: Options,
+    /// Index of the parsed file in `Program::files`.
+    file: usize,
 }
 
-fn parse_identifier(mut pairs: Pairs<Rule>) -> Identifier {
-    let name = pairs.next().unwrap().as_str().to_owned();
+impl AstBuilder {
+    fn span(&self, pair: &Pair<Rule>) -> Span {
+        Span::from(pair.as_span()).in_file(self.file)
+    }
 
-    if let Some(index) = pairs.next() {
-        match index.as_rule() {
-            Rule::pidentifier => Identifier::ArrAccess {
-                name,
-                index: index.as_str().to_owned(),
+    fn parse_num<T: FromStr>(&self, pair: &Pair<Rule>) -> ParseResult<T> {
+        // the grammar only lets digits through, so overflow is the only possible failure
+        pair.as_str().parse().map_err(|_| Error::IntegerOverflow {
+            literal: pair.as_str().to_owned(),
+            span: self.span(pair),
+        })
+    }
+
+    fn parse_program(&self, pair: Pair<Rule>) -> AstResult {
+        let program_text = pair.into_inner().next().unwrap();
+        let span = self.span(&program_text);
+        let mut program = program_text.into_inner().peekable();
+
+        let mut procedures = vec![];
+        while let Some(pair) = program.next_if(|pair| pair.as_rule() == Rule::procedure) {
+            procedures.push(self.parse_procedure(pair)?);
+        }
+
+        let (declarations, commands) = self.parse_body(program)?;
+
+        Ok(ast::Program {
+            files: vec![],
+            procedures,
+            declarations,
+            commands,
+            span,
+        })
+    }
+
+    fn parse_procedure(&self, pair: Pair<Rule>) -> ParseResult<Procedure> {
+        let span = self.span(&pair);
+        let mut pairs = pair.into_inner();
+
+        let name = pairs.next().unwrap().as_str().to_owned();
+        let parameters = pairs
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(|pair| self.parse_parameter(pair))
+            .collect();
+        let (declarations, commands) = self.parse_body(pairs)?;
+
+        Ok(Procedure {
+            name,
+            parameters,
+            declarations,
+            commands,
+            span,
+        })
+    }
+
+    fn parse_parameter(&self, pair: Pair<Rule>) -> Parameter {
+        let parameter = pair.into_inner().next().unwrap();
+        let span = self.span(&parameter);
+        let array = parameter.as_rule() == Rule::arr_param;
+        let name = parameter.into_inner().next().unwrap().as_str().to_owned();
+
+        Parameter { name, array, span }
+    }
+
+    /// Parses the optional declarations and the commands ending a program or procedure.
+    fn parse_body<'a>(
+        &self,
+        mut pairs: impl Iterator<Item = Pair<'a, Rule>>,
+    ) -> ParseResult<(Option<Declarations>, Commands)> {
+        let optional_declarations = pairs.next().unwrap();
+
+        let (declarations, commands) = match optional_declarations.as_rule() {
+            Rule::declarations => {
+                let declarations = self.parse_declarations(optional_declarations.into_inner())?;
+                (Some(declarations), pairs.next().unwrap())
+            }
+            Rule::commands => (None, optional_declarations),
+            _ => unreachable!(),
+        };
+
+        Ok((declarations, self.parse_commands(commands.into_inner())?))
+    }
+
+    fn parse_declaration(&self, pair: Pair<Rule>) -> ParseResult<Declaration> {
+        let declaration = pair.into_inner().next().unwrap();
+        let span = self.span(&declaration);
+        let declaration = match declaration.as_rule() {
+            Rule::arr_decl => {
+                let mut parts = declaration.into_inner();
+                let name = parts.next().unwrap().as_str().to_owned();
+                let dimensions = parts
+                    .map(|range| {
+                        let mut bounds = range.into_inner();
+                        let start = self.parse_num(&bounds.next().unwrap())?;
+                        let end = self.parse_num(&bounds.next().unwrap())?;
+                        Ok((start, end))
+                    })
+                    .collect::<ParseResult<_>>()?;
+
+                Declaration::Array {
+                    name,
+                    dimensions,
+                    span,
+                }
+            }
+            Rule::var_decl => Declaration::Var {
+                name: declaration.into_inner().next().unwrap().as_str().to_owned(),
+                span,
             },
-            Rule::num => Identifier::ArrConstAccess {
+            _ => unreachable!(),
+        };
+
+        Ok(declaration)
+    }
+
+    fn parse_declarations(&self, pairs: Pairs<Rule>) -> ParseResult<Declarations> {
+        pairs.map(|pair| self.parse_declaration(pair)).collect()
+    }
+
+    fn parse_identifier(&self, pair: Pair<Rule>) -> ParseResult<Identifier> {
+        let span = self.span(&pair);
+        let mut pairs = pair.into_inner();
+        let name = pairs.next().unwrap().as_str().to_owned();
+
+        if pairs.clone().count() > 1 {
+            let indices = pairs
+                .map(|index| self.parse_expression(index))
+                .collect::<ParseResult<_>>()?;
+
+            return Ok(Identifier::ArrMultiAccess {
                 name,
-                index: index.as_str().parse().unwrap(),
-            },
+                indices,
+                span,
+            });
+        }
+
+        let identifier = if let Some(index) = pairs.next() {
+            match index.as_rule() {
+                Rule::pidentifier => Identifier::ArrAccess {
+                    name,
+                    index: index.as_str().to_owned(),
+                    span,
+                },
+                Rule::num => Identifier::ArrConstAccess {
+                    name,
+                    index: self.parse_num(&index)?,
+                    span,
+                },
+                Rule::expression => Identifier::ArrExprAccess {
+                    name,
+                    index: Box::new(self.parse_expression(index)?),
+                    span,
+                },
+                _ => unreachable!(),
+            }
+        } else {
+            Identifier::VarAccess { name, span }
+        };
+
+        Ok(identifier)
+    }
+
+    fn parse_num_value(&self, pair: Pair<Rule>) -> ParseResult<Value> {
+        let span = self.span(&pair);
+        match self.parse_num(&pair) {
+            Ok(value) => Ok(Value::Num { value, span }),
+            #[cfg(feature = "bignum")]
+            Err(_) if self.options.big_literals => Ok(Value::BigNum {
+                value: pair.as_str().parse::<BigInt>().unwrap(),
+                span,
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn parse_value(&self, pair: Pair<Rule>) -> ParseResult<Value> {
+        let value = pair.into_inner().next().unwrap();
+        match value.as_rule() {
+            Rule::num => self.parse_num_value(value),
+            Rule::identifier => Ok(Value::Identifier(self.parse_identifier(value)?)),
             _ => unreachable!(),
         }
-    } else {
-        Identifier::VarAccess { name }
     }
-}
 
-fn parse_value(mut pairs: Pairs<Rule>) -> Value {
-    let value = pairs.next().unwrap();
-    match value.as_rule() {
-        Rule::num => Value::Num(value.as_str().parse().unwrap()),
-        Rule::identifier => Value::Identifier(parse_identifier(value.into_inner())),
-        _ => unreachable!(),
+    fn parse_relation(&self, pair: Pair<Rule>) -> ParseResult<Condition> {
+        let span = self.span(&pair);
+        let mut pairs = pair.into_inner();
+        let left = self.parse_value(pairs.next().unwrap())?;
+        let op = match pairs.next().unwrap().as_str() {
+            "EQ" => RelOp::EQ,
+            "NEQ" => RelOp::NEQ,
+            "LEQ" => RelOp::LEQ,
+            "LE" => RelOp::LT,
+            "GEQ" => RelOp::GEQ,
+            "GE" => RelOp::GT,
+            _ => unreachable!(),
+        };
+        let right = self.parse_value(pairs.next().unwrap())?;
+
+        Ok(Condition::Relation {
+            left,
+            op,
+            right,
+            span,
+        })
     }
-}
 
-fn parse_condition(mut pairs: Pairs<Rule>) -> Condition {
-    let left = parse_value(pairs.next().unwrap().into_inner());
-    let op = match pairs.next().unwrap().as_str() {
-        "EQ" => RelOp::EQ,
-        "NEQ" => RelOp::NEQ,
-        "LEQ" => RelOp::LEQ,
-        "LE" => RelOp::LT,
-        "GEQ" => RelOp::GEQ,
-        "GE" => RelOp::GT,
-        _ => unreachable!(),
-    };
-    let right = parse_value(pairs.next().unwrap().into_inner());
-
-    Condition { left, op, right }
-}
+    fn parse_condition(&self, pair: Pair<Rule>) -> ParseResult<Condition> {
+        match pair.as_rule() {
+            // both are a chain of left-associative operations on the level below
+            Rule::condition | Rule::conjunction => {
+                let op = match pair.as_rule() {
+                    Rule::condition => LogicOp::Or,
+                    _ => LogicOp::And,
+                };
+                let mut pairs = pair.into_inner();
+                let mut condition = self.parse_condition(pairs.next().unwrap())?;
 
-fn parse_expression(mut pairs: Pairs<Rule>) -> Expression {
-    let left = parse_value(pairs.next().unwrap().into_inner());
+                for right in pairs {
+                    let right = self.parse_condition(right)?;
+                    condition = Condition::compound(condition, op, right);
+                }
 
-    if let Some(op) = pairs.next() {
-        let op = match op.as_str() {
-            "PLUS" => ExprOp::Plus,
-            "MINUS" => ExprOp::Minus,
-            "TIMES" => ExprOp::Times,
-            "DIV" => ExprOp::Div,
-            "MOD" => ExprOp::Mod,
+                Ok(condition)
+            }
+            Rule::negation => {
+                let span = self.span(&pair);
+                let negation = pair.into_inner().next().unwrap();
+                match negation.as_rule() {
+                    Rule::negation => Ok(Condition::Not {
+                        condition: Box::new(self.parse_condition(negation)?),
+                        span,
+                    }),
+                    Rule::relation => self.parse_relation(negation),
+                    Rule::condition => self.parse_condition(negation),
+                    _ => unreachable!(),
+                }
+            }
             _ => unreachable!(),
-        };
-        let right = parse_value(pairs.next().unwrap().into_inner());
+        }
+    }
+
+    fn parse_expression(&self, pair: Pair<Rule>) -> ParseResult<Expression> {
+        match pair.as_rule() {
+            // both are a chain of left-associative operations on the level below
+            Rule::expression | Rule::term => {
+                let mut pairs = pair.into_inner();
+                let mut expression = self.parse_expression(pairs.next().unwrap())?;
 
-        Expression::Compound { left, op, right }
-    } else {
-        Expression::Simple { value: left }
+                while let Some(op) = pairs.next() {
+                    let op = match op.as_str() {
+                        "PLUS" => ExprOp::Plus,
+                        "MINUS" => ExprOp::Minus,
+                        "TIMES" => ExprOp::Times,
+                        "DIV" => ExprOp::Div,
+                        "MOD" => ExprOp::Mod,
+                        _ => unreachable!(),
+                    };
+                    let right = self.parse_expression(pairs.next().unwrap())?;
+                    expression = Expression::compound(expression, op, right);
+                }
+
+                Ok(expression)
+            }
+            Rule::factor => {
+                let factor = pair.into_inner().next().unwrap();
+                match factor.as_rule() {
+                    Rule::value => Ok(self.parse_value(factor)?.into()),
+                    Rule::expression => self.parse_expression(factor),
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        }
     }
-}
 
-fn parse_ifelse(mut pairs: Pairs<Rule>) -> Command {
-    let condition = parse_condition(pairs.next().unwrap().into_inner());
-    let positive = parse_commands(pairs.next().unwrap().into_inner());
-    let negative = parse_commands(pairs.next().unwrap().into_inner());
+    fn parse_ifelse(&self, mut pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let condition = self.parse_condition(pairs.next().unwrap())?;
+        let positive = self.parse_commands(pairs.next().unwrap().into_inner())?;
+        let negative = self.parse_commands(pairs.next().unwrap().into_inner())?;
 
-    Command::IfElse {
-        condition,
-        positive,
-        negative,
+        Ok(Command::IfElse {
+            condition,
+            positive,
+            negative,
+            span,
+        })
     }
-}
 
-fn parse_conditional_command(mut pairs: Pairs<Rule>) -> (Condition, Commands) {
-    let condition = parse_condition(pairs.next().unwrap().into_inner());
-    let commands = parse_commands(pairs.next().unwrap().into_inner());
+    fn parse_conditional_command(
+        &self,
+        mut pairs: Pairs<Rule>,
+    ) -> ParseResult<(Condition, Commands)> {
+        let condition = self.parse_condition(pairs.next().unwrap())?;
+        let commands = self.parse_commands(pairs.next().unwrap().into_inner())?;
 
-    (condition, commands)
-}
+        Ok((condition, commands))
+    }
 
-fn parse_if(pairs: Pairs<Rule>) -> Command {
-    let (condition, positive) = parse_conditional_command(pairs);
+    fn parse_if(&self, pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let (condition, positive) = self.parse_conditional_command(pairs)?;
 
-    Command::If {
-        condition,
-        positive,
+        Ok(Command::If {
+            condition,
+            positive,
+            span,
+        })
     }
-}
 
-fn parse_while(pairs: Pairs<Rule>) -> Command {
-    let (condition, commands) = parse_conditional_command(pairs);
+    fn parse_while(&self, pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let (condition, commands) = self.parse_conditional_command(pairs)?;
 
-    Command::While {
-        condition,
-        commands,
+        Ok(Command::While {
+            condition,
+            commands,
+            span,
+        })
     }
-}
 
-fn parse_do(pairs: Pairs<Rule>) -> Command {
-    let (condition, commands) = parse_conditional_command(pairs);
+    fn parse_do(&self, mut pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let commands = self.parse_commands(pairs.next().unwrap().into_inner())?;
+        let condition = self.parse_condition(pairs.next().unwrap())?;
 
-    Command::Do {
-        condition,
-        commands,
+        Ok(Command::Do {
+            condition,
+            commands,
+            span,
+        })
     }
-}
 
-fn parse_for(mut pairs: Pairs<Rule>) -> Command {
-    let counter = pairs.next().unwrap().as_str().to_owned();
-    let from = parse_value(pairs.next().unwrap().into_inner());
-    let ascending = match pairs.next().unwrap().as_str() {
-        "TO" => true,
-        "DOWNTO" => false,
-        _ => unreachable!(),
-    };
-    let to = parse_value(pairs.next().unwrap().into_inner());
-    let commands = parse_commands(pairs.next().unwrap().into_inner());
-
-    Command::For {
-        counter,
-        from,
-        ascending,
-        to,
-        commands,
+    fn parse_for(&self, mut pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let counter = pairs.next().unwrap().as_str().to_owned();
+        let from = self.parse_value(pairs.next().unwrap())?;
+        let ascending = match pairs.next().unwrap().as_str() {
+            "TO" => true,
+            "DOWNTO" => false,
+            _ => unreachable!(),
+        };
+        let to = self.parse_value(pairs.next().unwrap())?;
+        let step = match pairs.peek().unwrap().as_rule() {
+            Rule::value => Some(self.parse_value(pairs.next().unwrap())?),
+            _ => None,
+        };
+        let commands = self.parse_commands(pairs.next().unwrap().into_inner())?;
+
+        Ok(Command::For {
+            counter,
+            from,
+            ascending,
+            to,
+            step,
+            commands,
+            span,
+        })
     }
-}
 
-fn parse_read(mut pairs: Pairs<Rule>) -> Command {
-    let target = parse_identifier(pairs.next().unwrap().into_inner());
+    fn parse_read(&self, pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let targets = pairs
+            .map(|pair| self.parse_identifier(pair))
+            .collect::<ParseResult<_>>()?;
 
-    Command::Read { target }
-}
+        Ok(Command::Read { targets, span })
+    }
 
-fn parse_write(mut pairs: Pairs<Rule>) -> Command {
-    let value = parse_value(pairs.next().unwrap().into_inner());
+    fn parse_write(&self, pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let values = pairs
+            .map(|pair| self.parse_value(pair))
+            .collect::<ParseResult<_>>()?;
 
-    Command::Write { value }
-}
+        Ok(Command::Write { values, span })
+    }
 
-fn parse_assign(mut pairs: Pairs<Rule>) -> Command {
-    let target = parse_identifier(pairs.next().unwrap().into_inner());
-    let expr = parse_expression(pairs.next().unwrap().into_inner());
+    fn parse_assign(&self, mut pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let target = self.parse_identifier(pairs.next().unwrap())?;
+        let expr = self.parse_expression(pairs.next().unwrap())?;
 
-    Command::Assign { target, expr }
-}
+        Ok(Command::Assign { target, expr, span })
+    }
 
-fn parse_command(mut pairs: Pairs<Rule>) -> Command {
-    let command = pairs.next().unwrap();
-    match command.as_rule() {
-        Rule::cmd_ifelse => parse_ifelse(command.into_inner()),
-        Rule::cmd_if => parse_if(command.into_inner()),
-        Rule::cmd_while => parse_while(command.into_inner()),
-        Rule::cmd_do => parse_do(command.into_inner()),
-        Rule::cmd_for => parse_for(command.into_inner()),
-        Rule::cmd_read => parse_read(command.into_inner()),
-        Rule::cmd_write => parse_write(command.into_inner()),
-        Rule::cmd_assign => parse_assign(command.into_inner()),
-        _ => unreachable!(),
+    fn parse_call(&self, mut pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let name = pairs.next().unwrap().as_str().to_owned();
+        let arguments = pairs
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(|argument| Argument {
+                name: argument.as_str().to_owned(),
+                span: self.span(&argument),
+            })
+            .collect();
+
+        Ok(Command::Call {
+            name,
+            arguments,
+            span,
+        })
+    }
+
+    fn parse_include(&self, mut pairs: Pairs<Rule>, span: Span) -> ParseResult<Command> {
+        let string = pairs.next().unwrap();
+        let path = string.into_inner().next().unwrap().as_str().to_owned();
+
+        Ok(Command::Include { path, span })
+    }
+
+    fn parse_command(&self, pair: Pair<Rule>) -> ParseResult<Command> {
+        let command = pair.into_inner().next().unwrap();
+        let span = self.span(&command);
+        match command.as_rule() {
+            Rule::cmd_ifelse => self.parse_ifelse(command.into_inner(), span),
+            Rule::cmd_if => self.parse_if(command.into_inner(), span),
+            Rule::cmd_while => self.parse_while(command.into_inner(), span),
+            Rule::cmd_do => self.parse_do(command.into_inner(), span),
+            Rule::cmd_for => self.parse_for(command.into_inner(), span),
+            Rule::cmd_read => self.parse_read(command.into_inner(), span),
+            Rule::cmd_write => self.parse_write(command.into_inner(), span),
+            Rule::cmd_call => self.parse_call(command.into_inner(), span),
+            Rule::cmd_include => self.parse_include(command.into_inner(), span),
+            Rule::cmd_break => Ok(Command::Break { span }),
+            Rule::cmd_continue => Ok(Command::Continue { span }),
+            Rule::cmd_assign => self.parse_assign(command.into_inner(), span),
+            _ => unreachable!(),
+        }
     }
-}
 
-fn parse_commands(pairs: Pairs<Rule>) -> Commands {
-    pairs.map(|pair| parse_command(pair.into_inner())).collect()
+    fn parse_commands(&self, pairs: Pairs<Rule>) -> ParseResult<Commands> {
+        pairs.map(|pair| self.parse_command(pair)).collect()
+    }
 }
 
 #[cfg(test)]
@@ -254,10 +526,17 @@ mod tests {
         let text = "BEGIN WRITE 0; END";
         let parsed = parse_ast(text);
         let expected = ast::Program {
+            files: vec![],
+            procedures: vec![],
             declarations: None,
             commands: vec![Command::Write {
-                value: Value::Num(0),
+                values: vec![Value::Num {
+                    value: 0,
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             }],
+            span: Span::default(),
         };
 
         assert_eq!(parsed.unwrap(), expected);
@@ -273,22 +552,31 @@ mod tests {
         "#;
         let parsed = parse_ast(text);
         let expected = ast::Program {
+            files: vec![],
+            procedures: vec![],
             declarations: Some(vec![
                 Declaration::Var {
                     name: String::from("a"),
+                    span: Span::default(),
                 },
                 Declaration::Var {
                     name: String::from("b"),
+                    span: Span::default(),
                 },
                 Declaration::Array {
                     name: String::from("c"),
-                    start: 1,
-                    end: 10,
+                    dimensions: vec![(1, 10)],
+                    span: Span::default(),
                 },
             ]),
             commands: vec![Command::Write {
-                value: Value::Num(0),
+                values: vec![Value::Num {
+                    value: 0,
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             }],
+            span: Span::default(),
         };
 
         assert_eq!(parsed.unwrap(), expected);
@@ -321,78 +609,121 @@ mod tests {
 
         let var_a = Identifier::VarAccess {
             name: String::from("a"),
+            span: Span::default(),
         };
         let var_b = Identifier::VarAccess {
             name: String::from("b"),
+            span: Span::default(),
         };
 
         let expected = ast::Program {
+            files: vec![],
+            procedures: vec![],
             declarations: Some(vec![
                 Declaration::Var {
                     name: String::from("a"),
+                    span: Span::default(),
                 },
                 Declaration::Var {
                     name: String::from("b"),
+                    span: Span::default(),
                 },
             ]),
             commands: vec![
                 Command::Read {
-                    target: var_a.clone(),
+                    targets: vec![var_a.clone()],
+                    span: Span::default(),
                 },
                 Command::If {
-                    condition: Condition {
+                    condition: Condition::Relation {
                         left: Value::Identifier(var_a.clone()),
                         op: RelOp::GEQ,
-                        right: Value::Num(0),
+                        right: Value::Num {
+                            value: 0,
+                            span: Span::default(),
+                        },
+                        span: Span::default(),
                     },
                     positive: vec![Command::While {
-                        condition: Condition {
+                        condition: Condition::Relation {
                             left: Value::Identifier(var_a.clone()),
                             op: RelOp::GT,
-                            right: Value::Num(0),
+                            right: Value::Num {
+                                value: 0,
+                                span: Span::default(),
+                            },
+                            span: Span::default(),
                         },
                         commands: vec![
                             Command::Assign {
                                 target: var_b.clone(),
-                                expr: Expression::Compound {
-                                    left: Value::Identifier(var_a.clone()),
-                                    op: ExprOp::Div,
-                                    right: Value::Num(2),
-                                },
+                                expr: Expression::compound(
+                                    Value::Identifier(var_a.clone()).into(),
+                                    ExprOp::Div,
+                                    Value::Num {
+                                        value: 2,
+                                        span: Span::default(),
+                                    }
+                                    .into(),
+                                ),
+                                span: Span::default(),
                             },
                             Command::Assign {
                                 target: var_b.clone(),
-                                expr: Expression::Compound {
-                                    left: Value::Num(2),
-                                    op: ExprOp::Times,
-                                    right: Value::Identifier(var_b.clone()),
-                                },
+                                expr: Expression::compound(
+                                    Value::Num {
+                                        value: 2,
+                                        span: Span::default(),
+                                    }
+                                    .into(),
+                                    ExprOp::Times,
+                                    Value::Identifier(var_b.clone()).into(),
+                                ),
+                                span: Span::default(),
                             },
                             Command::IfElse {
-                                condition: Condition {
+                                condition: Condition::Relation {
                                     left: Value::Identifier(var_a.clone()),
                                     op: RelOp::GT,
                                     right: Value::Identifier(var_b.clone()),
+                                    span: Span::default(),
                                 },
                                 positive: vec![Command::Write {
-                                    value: Value::Num(1),
+                                    values: vec![Value::Num {
+                                        value: 1,
+                                        span: Span::default(),
+                                    }],
+                                    span: Span::default(),
                                 }],
                                 negative: vec![Command::Write {
-                                    value: Value::Num(0),
+                                    values: vec![Value::Num {
+                                        value: 0,
+                                        span: Span::default(),
+                                    }],
+                                    span: Span::default(),
                                 }],
+                                span: Span::default(),
                             },
                             Command::Assign {
                                 target: var_a.clone(),
-                                expr: Expression::Compound {
-                                    left: Value::Identifier(var_a.clone()),
-                                    op: ExprOp::Div,
-                                    right: Value::Num(2),
-                                },
+                                expr: Expression::compound(
+                                    Value::Identifier(var_a.clone()).into(),
+                                    ExprOp::Div,
+                                    Value::Num {
+                                        value: 2,
+                                        span: Span::default(),
+                                    }
+                                    .into(),
+                                ),
+                                span: Span::default(),
                             },
                         ],
+                        span: Span::default(),
                     }],
+                    span: Span::default(),
                 },
             ],
+            span: Span::default(),
         };
 
         assert_eq!(parsed.unwrap(), expected);
@@ -426,109 +757,749 @@ mod tests {
 
         let var_n = Identifier::VarAccess {
             name: String::from("n"),
+            span: Span::default(),
         };
         let var_j = Identifier::VarAccess {
             name: String::from("j"),
+            span: Span::default(),
         };
         let temp_i = Identifier::VarAccess {
             name: String::from("i"),
+            span: Span::default(),
         };
         let var_sieve = String::from("sieve");
 
         let expected = ast::Program {
+            files: vec![],
+            procedures: vec![],
             declarations: Some(vec![
                 Declaration::Var {
                     name: String::from("n"),
+                    span: Span::default(),
                 },
                 Declaration::Var {
                     name: String::from("j"),
+                    span: Span::default(),
                 },
                 Declaration::Array {
                     name: String::from("sieve"),
-                    start: 2,
-                    end: 100,
+                    dimensions: vec![(2, 100)],
+                    span: Span::default(),
                 },
             ]),
             commands: vec![
                 Command::Assign {
                     target: var_n.clone(),
                     expr: Expression::Simple {
-                        value: Value::Num(100),
+                        value: Value::Num {
+                            value: 100,
+                            span: Span::default(),
+                        },
+                        span: Span::default(),
                     },
+                    span: Span::default(),
                 },
                 Command::For {
                     counter: "i".to_string(),
                     ascending: false,
                     from: Value::Identifier(var_n.clone()),
-                    to: Value::Num(2),
+                    to: Value::Num {
+                        value: 2,
+                        span: Span::default(),
+                    },
+                    step: None,
                     commands: vec![Command::Assign {
                         target: Identifier::ArrAccess {
                             name: var_sieve.clone(),
                             index: String::from("i"),
+                            span: Span::default(),
                         },
                         expr: Expression::Simple {
-                            value: Value::Num(1),
+                            value: Value::Num {
+                                value: 1,
+                                span: Span::default(),
+                            },
+                            span: Span::default(),
                         },
+                        span: Span::default(),
                     }],
+                    span: Span::default(),
                 },
                 Command::For {
                     counter: "i".to_string(),
                     ascending: true,
-                    from: Value::Num(2),
+                    from: Value::Num {
+                        value: 2,
+                        span: Span::default(),
+                    },
                     to: Value::Identifier(var_n.clone()),
+                    step: None,
                     commands: vec![Command::If {
-                        condition: Condition {
+                        condition: Condition::Relation {
                             left: Value::Identifier(Identifier::ArrAccess {
                                 name: var_sieve.clone(),
                                 index: String::from("i"),
+                                span: Span::default(),
                             }),
                             op: RelOp::NEQ,
-                            right: Value::Num(0),
+                            right: Value::Num {
+                                value: 0,
+                                span: Span::default(),
+                            },
+                            span: Span::default(),
                         },
                         positive: vec![
                             Command::Assign {
                                 target: var_j.clone(),
-                                expr: Expression::Compound {
-                                    left: Value::Identifier(temp_i.clone()),
-                                    op: ExprOp::Plus,
-                                    right: Value::Identifier(temp_i.clone()),
-                                },
+                                expr: Expression::compound(
+                                    Value::Identifier(temp_i.clone()).into(),
+                                    ExprOp::Plus,
+                                    Value::Identifier(temp_i.clone()).into(),
+                                ),
+                                span: Span::default(),
                             },
                             Command::While {
-                                condition: Condition {
+                                condition: Condition::Relation {
                                     left: Value::Identifier(var_j.clone()),
                                     op: RelOp::LEQ,
                                     right: Value::Identifier(var_n.clone()),
+                                    span: Span::default(),
                                 },
                                 commands: vec![
                                     Command::Assign {
                                         target: Identifier::ArrAccess {
                                             name: var_sieve.clone(),
                                             index: String::from("j"),
+                                            span: Span::default(),
                                         },
                                         expr: Expression::Simple {
-                                            value: Value::Num(0),
+                                            value: Value::Num {
+                                                value: 0,
+                                                span: Span::default(),
+                                            },
+                                            span: Span::default(),
                                         },
+                                        span: Span::default(),
                                     },
                                     Command::Assign {
                                         target: var_j.clone(),
-                                        expr: Expression::Compound {
-                                            left: Value::Identifier(var_j.clone()),
-                                            op: ExprOp::Plus,
-                                            right: Value::Identifier(temp_i.clone()),
-                                        },
+                                        expr: Expression::compound(
+                                            Value::Identifier(var_j.clone()).into(),
+                                            ExprOp::Plus,
+                                            Value::Identifier(temp_i.clone()).into(),
+                                        ),
+                                        span: Span::default(),
                                     },
                                 ],
+                                span: Span::default(),
                             },
                             Command::Write {
-                                value: Value::Identifier(temp_i.clone()),
+                                values: vec![Value::Identifier(temp_i.clone())],
+                                span: Span::default(),
                             },
                         ],
+                        span: Span::default(),
                     }],
+                    span: Span::default(),
                 },
             ],
+            span: Span::default(),
+        };
+
+        assert_eq!(parsed.unwrap(), expected);
+    }
+
+    #[test]
+    fn do_while() {
+        let text = r#"
+            DECLARE a
+            BEGIN
+                DO
+                    a ASSIGN a MINUS 1;
+                WHILE a GE 0 ENDDO
+            END
+        "#;
+        let parsed = parse_ast(text);
+
+        let var_a = Identifier::VarAccess {
+            name: String::from("a"),
+            span: Span::default(),
+        };
+
+        let expected = ast::Program {
+            files: vec![],
+            procedures: vec![],
+            declarations: Some(vec![Declaration::Var {
+                name: String::from("a"),
+                span: Span::default(),
+            }]),
+            commands: vec![Command::Do {
+                commands: vec![Command::Assign {
+                    target: var_a.clone(),
+                    expr: Expression::compound(
+                        Value::Identifier(var_a.clone()).into(),
+                        ExprOp::Minus,
+                        Value::Num {
+                            value: 1,
+                            span: Span::default(),
+                        }
+                        .into(),
+                    ),
+                    span: Span::default(),
+                }],
+                condition: Condition::Relation {
+                    left: Value::Identifier(var_a),
+                    op: RelOp::GT,
+                    right: Value::Num {
+                        value: 0,
+                        span: Span::default(),
+                    },
+                    span: Span::default(),
+                },
+                span: Span::default(),
+            }],
+            span: Span::default(),
         };
 
         assert_eq!(parsed.unwrap(), expected);
     }
+
+    #[test]
+    fn spans() {
+        let text = "DECLARE\n    a, t(0:3)\nBEGIN\n    t(a) ASSIGN a PLUS 1;\nEND";
+        let program = parse_ast(text).unwrap();
+
+        let declarations = program.declarations.as_ref().unwrap();
+        let array = declarations[1].span();
+        assert_eq!(&text[array.start..array.end], "t(0:3)");
+        assert_eq!((array.line, array.column), (2, 8));
+
+        let command = &program.commands[0];
+        let command_span = command.span();
+        assert_eq!(
+            &text[command_span.start..command_span.end],
+            "t(a) ASSIGN a PLUS 1;"
+        );
+        assert_eq!((command_span.line, command_span.column), (4, 5));
+
+        if let Command::Assign { target, expr, .. } = command {
+            let target = target.span();
+            assert_eq!(&text[target.start..target.end], "t(a)");
+
+            let expr_span = expr.span();
+            assert_eq!(&text[expr_span.start..expr_span.end], "a PLUS 1");
+
+            if let Expression::Compound { right, .. } = expr {
+                let right = right.span();
+                assert_eq!(&text[right.start..right.end], "1");
+                assert_eq!((right.line, right.column), (4, 24));
+            } else {
+                panic!("expected compound expression");
+            }
+        } else {
+            panic!("expected assignment");
+        }
+    }
+
+    #[test]
+    fn unexpected_token() {
+        let text = "BEGIN\n    WRITE 1;\n    WRITE ;\nEND";
+        let error = parse_ast(text).unwrap_err();
+
+        match error {
+            Error::UnexpectedToken {
+                expected,
+                found,
+                span,
+            } => {
+                assert_eq!(expected, vec![Rule::value]);
+                assert_eq!(found, ";");
+                assert_eq!((span.line, span.column), (3, 11));
+                assert_eq!(&text[span.start..span.end], ";");
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn unexpected_end() {
+        let text = "BEGIN WRITE 1;";
+        let error = parse_ast(text).unwrap_err();
+
+        match error {
+            Error::UnexpectedEnd { span, .. } => {
+                assert_eq!(span.start, text.len());
+                assert!(span.is_empty());
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn missing_file() {
+        let error = parse_file("nonexistent/file.imp").unwrap_err();
+
+        match error {
+            Error::Io { path, .. } => assert_eq!(path, Path::new("nonexistent/file.imp")),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn integer_overflow() {
+        let text = "BEGIN\n    WRITE 99999999999999999999;\nEND";
+        let error = parse_ast(text).unwrap_err();
+
+        match error {
+            Error::IntegerOverflow { literal, span } => {
+                assert_eq!(literal, "99999999999999999999");
+                assert_eq!((span.line, span.column), (2, 11));
+                assert_eq!(&text[span.start..span.end], literal);
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn expression_precedence() {
+        let text = "BEGIN a ASSIGN a MINUS b TIMES (c PLUS d) MOD 2 MINUS e; END";
+        let program = parse_ast(text).unwrap();
+
+        let var = |name: &str| -> Expression {
+            Value::Identifier(Identifier::VarAccess {
+                name: name.to_owned(),
+                span: Span::default(),
+            })
+            .into()
+        };
+        let two = Value::Num {
+            value: 2,
+            span: Span::default(),
+        }
+        .into();
+
+        // (a - ((b * (c + d)) % 2)) - e
+        let product = Expression::compound(
+            var("b"),
+            ExprOp::Times,
+            Expression::compound(var("c"), ExprOp::Plus, var("d")),
+        );
+        let expected = Expression::compound(
+            Expression::compound(
+                var("a"),
+                ExprOp::Minus,
+                Expression::compound(product, ExprOp::Mod, two),
+            ),
+            ExprOp::Minus,
+            var("e"),
+        );
+
+        match &program.commands[0] {
+            Command::Assign { expr, .. } => {
+                assert_eq!(expr, &expected);
+                let span = expr.span();
+                let source = &text[span.start..span.end];
+                assert_eq!(source, "a MINUS b TIMES (c PLUS d) MOD 2 MINUS e");
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn logical_conditions() {
+        let text =
+            "BEGIN IF NOT a EQ 0 OR b LE 1 AND NOT (c GE 2 OR d NEQ 3) THEN WRITE 0; ENDIF END";
+        let program = parse_ast(text).unwrap();
+
+        let relation = |name: &str, op, value| Condition::Relation {
+            left: Value::Identifier(Identifier::VarAccess {
+                name: name.to_owned(),
+                span: Span::default(),
+            }),
+            op,
+            right: Value::Num {
+                value,
+                span: Span::default(),
+            },
+            span: Span::default(),
+        };
+        let not = |condition| Condition::Not {
+            condition: Box::new(condition),
+            span: Span::default(),
+        };
+
+        // (NOT a = 0) OR (b < 1 AND NOT (c > 2 OR d != 3))
+        let expected = Condition::compound(
+            not(relation("a", RelOp::EQ, 0)),
+            LogicOp::Or,
+            Condition::compound(
+                relation("b", RelOp::LT, 1),
+                LogicOp::And,
+                not(Condition::compound(
+                    relation("c", RelOp::GT, 2),
+                    LogicOp::Or,
+                    relation("d", RelOp::NEQ, 3),
+                )),
+            ),
+        );
+
+        match &program.commands[0] {
+            Command::If { condition, .. } => {
+                assert_eq!(condition, &expected);
+                let span = condition.span();
+                assert_eq!(
+                    &text[span.start..span.end],
+                    &text[9..text.find(" THEN").unwrap()]
+                );
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn procedures() {
+        let text = r#"
+            PROCEDURE clear(T t, n) IS
+            BEGIN
+                t(n) ASSIGN 0;
+            END
+            PROCEDURE reset() IS
+            DECLARE
+                a
+            BEGIN
+                a ASSIGN 1;
+            END
+            DECLARE
+                t(0:9)
+            BEGIN
+                clear(t, t);
+                reset();
+            END
+        "#;
+        let program = parse_ast(text).unwrap();
+
+        let parameter = |name: &str, array| Parameter {
+            name: name.to_owned(),
+            array,
+            span: Span::default(),
+        };
+        let argument = |name: &str| Argument {
+            name: name.to_owned(),
+            span: Span::default(),
+        };
+
+        assert_eq!(program.procedures.len(), 2);
+        let clear = &program.procedures[0];
+        assert_eq!(clear.name, "clear");
+        assert_eq!(
+            clear.parameters,
+            vec![parameter("t", true), parameter("n", false)]
+        );
+        assert_eq!(clear.declarations, None);
+        assert_eq!(clear.commands.len(), 1);
+
+        let reset = &program.procedures[1];
+        assert_eq!(reset.name, "reset");
+        assert!(reset.parameters.is_empty());
+        assert_eq!(reset.declarations.as_ref().map(Vec::len), Some(1));
+        assert!(text[reset.span.start..reset.span.end].starts_with("PROCEDURE reset"));
+
+        assert_eq!(
+            program.commands,
+            vec![
+                Command::Call {
+                    name: "clear".to_owned(),
+                    arguments: vec![argument("t"), argument("t")],
+                    span: Span::default(),
+                },
+                Command::Call {
+                    name: "reset".to_owned(),
+                    arguments: vec![],
+                    span: Span::default(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn multiple_targets() {
+        let text = "BEGIN READ a, b, t(a); WRITE a, 5, t(1); END";
+        let program = parse_ast(text).unwrap();
+
+        let var = |name: &str| Identifier::VarAccess {
+            name: name.to_owned(),
+            span: Span::default(),
+        };
+
+        assert_eq!(
+            program.commands,
+            vec![
+                Command::Read {
+                    targets: vec![
+                        var("a"),
+                        var("b"),
+                        Identifier::ArrAccess {
+                            name: "t".to_owned(),
+                            index: "a".to_owned(),
+                            span: Span::default(),
+                        },
+                    ],
+                    span: Span::default(),
+                },
+                Command::Write {
+                    values: vec![
+                        Value::Identifier(var("a")),
+                        Value::Num {
+                            value: 5,
+                            span: Span::default()
+                        },
+                        Value::Identifier(Identifier::ArrConstAccess {
+                            name: "t".to_owned(),
+                            index: 1,
+                            span: Span::default(),
+                        }),
+                    ],
+                    span: Span::default(),
+                },
+            ]
+        );
+
+        assert!(parse_ast("BEGIN READ a,; END").is_err());
+        assert!(parse_ast("BEGIN WRITE , a; END").is_err());
+    }
+
+    #[test]
+    fn for_step() {
+        let program = parse_ast("BEGIN FOR i FROM 0 TO 10 STEP s DO WRITE i; ENDFOR END").unwrap();
+
+        match &program.commands[0] {
+            Command::For { step, .. } => assert_eq!(
+                step,
+                &Some(Value::Identifier(Identifier::VarAccess {
+                    name: "s".to_owned(),
+                    span: Span::default(),
+                }))
+            ),
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        assert!(parse_ast("BEGIN FOR i FROM 0 TO 10 STEP DO WRITE i; ENDFOR END").is_err());
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let text = "BEGIN WHILE a GE 0 DO BREAK; CONTINUE; ENDWHILE END";
+        let program = parse_ast(text).unwrap();
+
+        match &program.commands[0] {
+            Command::While { commands, .. } => {
+                assert_eq!(
+                    commands,
+                    &vec![
+                        Command::Break {
+                            span: Span::default()
+                        },
+                        Command::Continue {
+                            span: Span::default()
+                        },
+                    ]
+                );
+                let span = commands[1].span();
+                assert_eq!(&text[span.start..span.end], "CONTINUE;");
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        assert!(parse_ast("BEGIN WHILE a GE 0 DO BREAK ENDWHILE END").is_err());
+    }
+
+    #[test]
+    fn multi_dimensional_arrays() {
+        let text = "DECLARE t(0:9, -5:5, 1:2) BEGIN t(i, -1, 2) ASSIGN t(1, 0, 1); END";
+        let program = parse_ast(text).unwrap();
+
+        assert_eq!(
+            program.declarations,
+            Some(vec![Declaration::Array {
+                name: "t".to_owned(),
+                dimensions: vec![(0, 9), (-5, 5), (1, 2)],
+                span: Span::default(),
+            }])
+        );
+        match &program.commands[0] {
+            Command::Assign { target, .. } => {
+                assert_eq!(
+                    target,
+                    &Identifier::ArrMultiAccess {
+                        name: "t".to_owned(),
+                        indices: vec![
+                            Value::Identifier(Identifier::VarAccess {
+                                name: "i".to_owned(),
+                                span: Span::default(),
+                            })
+                            .into(),
+                            Value::Num {
+                                value: -1,
+                                span: Span::default()
+                            }
+                            .into(),
+                            Value::Num {
+                                value: 2,
+                                span: Span::default()
+                            }
+                            .into(),
+                        ],
+                        span: Span::default(),
+                    }
+                );
+                let span = target.span();
+                assert_eq!(&text[span.start..span.end], "t(i, -1, 2)");
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        assert!(parse_ast("DECLARE t(0:9, 3) BEGIN WRITE 0; END").is_err());
+    }
+
+    #[test]
+    fn expression_indices() {
+        let text = "BEGIN WRITE t(i PLUS 1); WRITE t(u(j)); WRITE t(i, (j)); WRITE t((i)); END";
+        let program = parse_ast(text).unwrap();
+        let var = |name: &str| {
+            Value::Identifier(Identifier::VarAccess {
+                name: name.to_owned(),
+                span: Span::default(),
+            })
+        };
+        let write = |identifier| Command::Write {
+            values: vec![Value::Identifier(identifier)],
+            span: Span::default(),
+        };
+
+        assert_eq!(
+            program.commands,
+            vec![
+                write(Identifier::ArrExprAccess {
+                    name: "t".to_owned(),
+                    index: Box::new(Expression::compound(
+                        var("i").into(),
+                        ExprOp::Plus,
+                        Value::Num {
+                            value: 1,
+                            span: Span::default()
+                        }
+                        .into(),
+                    )),
+                    span: Span::default(),
+                }),
+                write(Identifier::ArrExprAccess {
+                    name: "t".to_owned(),
+                    index: Box::new(
+                        Value::Identifier(Identifier::ArrAccess {
+                            name: "u".to_owned(),
+                            index: "j".to_owned(),
+                            span: Span::default(),
+                        })
+                        .into()
+                    ),
+                    span: Span::default(),
+                }),
+                write(Identifier::ArrMultiAccess {
+                    name: "t".to_owned(),
+                    indices: vec![var("i").into(), var("j").into()],
+                    span: Span::default(),
+                }),
+                write(Identifier::ArrExprAccess {
+                    name: "t".to_owned(),
+                    index: Box::new(var("i").into()),
+                    span: Span::default(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let text = "[ first ]\nBEGIN\n    WRITE 0; [second]\nEND\n[]";
+        let (program, comments) = parse_ast_with_comments(text, Options::default()).unwrap();
+
+        assert_eq!(program, parse_ast(text).unwrap());
+        let comments: Vec<_> = comments
+            .iter()
+            .map(|comment| {
+                (
+                    comment.text.as_str(),
+                    comment.span.line,
+                    comment.span.column,
+                )
+            })
+            .collect();
+        assert_eq!(comments, [(" first ", 1, 1), ("second", 3, 14), ("", 5, 1)]);
+    }
+
+    #[test]
+    fn mixed_case_keywords() {
+        let text = "Declare do Begin Read do; While do Le 10 Do do Assign do Plus 1; EndWhile End";
+        let (program, _) = parse_ast_with_comments(text, Options::default()).unwrap();
+        let expected =
+            "DECLARE do BEGIN READ do; WHILE do LE 10 DO do ASSIGN do PLUS 1; ENDWHILE END";
+
+        assert_eq!(program, parse_ast(expected).unwrap());
+        assert!(parse_ast(text).is_err());
+
+        // keywords glued to identifiers keep their meaning
+        let text = "DECLARE q BEGIN READ q; WHILE q LEq DOwnto ASSIGN q; ENDWHILE END";
+        let (program, _) = parse_ast_with_comments(text, Options::default()).unwrap();
+        assert_eq!(program, parse_ast(text).unwrap());
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        let text = "BEGIN WRITE 0; END [ oops";
+
+        match parse_ast_with_comments(text, Options::default()) {
+            Err(Error::UnexpectedToken { found, .. }) => assert_eq!(found, "["),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[cfg(feature = "bignum")]
+    #[test]
+    fn big_literals() {
+        let text = "BEGIN WRITE -99999999999999999999; END";
+        let options = Options { big_literals: true };
+        let parsed = parse_ast_with_options(text, options);
+        let expected = ast::Program {
+            files: vec![],
+            procedures: vec![],
+            declarations: None,
+            commands: vec![Command::Write {
+                values: vec![Value::BigNum {
+                    value: "-99999999999999999999".parse().unwrap(),
+                    span: Span::default(),
+                }],
+                span: Span::default(),
+            }],
+            span: Span::default(),
+        };
+
+        assert_eq!(parsed.unwrap(), expected);
+    }
+
+    #[cfg(feature = "bignum")]
+    #[test]
+    fn big_literals_in_declarations() {
+        let options = Options { big_literals: true };
+        let texts = [
+            "DECLARE a(0:99999999999999999999) BEGIN WRITE 0; END",
+            "DECLARE a(0:10) BEGIN WRITE a(99999999999999999999); END",
+        ];
+
+        for text in texts.iter() {
+            match parse_ast_with_options(text, options) {
+                Err(Error::IntegerOverflow { .. }) => {}
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+    }
 }
@@ -0,0 +1,113 @@
+use crate::ast::{Comment, Span};
+use crate::{ProgramParser, Rule};
+use pest::Parser;
+
+const KEYWORDS: &[&str] = &[
+    "DECLARE",
+    "BEGIN",
+    "END",
+    "IF",
+    "THEN",
+    "ELSE",
+    "ENDIF",
+    "WHILE",
+    "DO",
+    "ENDWHILE",
+    "ENDDO",
+    "FOR",
+    "FROM",
+    "TO",
+    "DOWNTO",
+    "ENDFOR",
+    "READ",
+    "WRITE",
+    "ASSIGN",
+    "PLUS",
+    "MINUS",
+    "TIMES",
+    "DIV",
+    "MOD",
+    "EQ",
+    "NEQ",
+    "LEQ",
+    "LE",
+    "GEQ",
+    "GE",
+    "AND",
+    "OR",
+    "NOT",
+    "PROCEDURE",
+    "IS",
+    "T",
+    "INCLUDE",
+    "STEP",
+    "BREAK",
+    "CONTINUE",
+];
+
+/// Source text prepared for a comment-preserving parse.
+pub(crate) struct Trivia {
+    /// The source with keywords converted to upper case.
+    pub text: String,
+    pub comments: Vec<Comment>,
+}
+
+fn is_keyword_sequence(text: &str) -> bool {
+    text.is_empty()
+        || KEYWORDS
+            .iter()
+            .any(|keyword| text.starts_with(keyword) && is_keyword_sequence(&text[keyword.len()..]))
+}
+
+/// Whether the word could be valid as written, as keywords followed by an
+/// identifier with no whitespace in between, like `LEq` meaning `LE q`.
+fn is_glued(word: &str) -> bool {
+    let split = word
+        .find(|c: char| !c.is_ascii_uppercase())
+        .unwrap_or(word.len());
+    let (keywords, identifier) = word.split_at(split);
+    !identifier.chars().any(|c| c.is_ascii_uppercase()) && is_keyword_sequence(keywords)
+}
+
+/// Collects comments and normalizes the casing of keywords.
+///
+/// Only words with both upper and lower case letters are treated as keywords
+/// in the wrong case, identifiers are always lower case. Returns `None` if the
+/// source has an unterminated comment, the regular parse reports that error.
+pub(crate) fn scan(text: &str) -> Option<Trivia> {
+    let trivia = ProgramParser::parse(Rule::trivia, text)
+        .ok()?
+        .next()
+        .unwrap();
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut comments = Vec::new();
+    let mut copied = 0;
+
+    for pair in trivia.into_inner() {
+        match pair.as_rule() {
+            Rule::comment => {
+                let span = Span::from(pair.as_span());
+                let text = pair.into_inner().next().unwrap().as_str().to_owned();
+                comments.push(Comment { text, span });
+            }
+            Rule::word => {
+                let word = pair.as_str();
+                let upper = word.to_ascii_uppercase();
+                if word != upper && KEYWORDS.contains(&upper.as_str()) && !is_glued(word) {
+                    let span = pair.as_span();
+                    normalized.push_str(&text[copied..span.start()]);
+                    normalized.push_str(&upper);
+                    copied = span.end();
+                }
+            }
+            _ => (),
+        }
+    }
+    normalized.push_str(&text[copied..]);
+
+    Some(Trivia {
+        text: normalized,
+        comments,
+    })
+}
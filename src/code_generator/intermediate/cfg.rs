@@ -0,0 +1,593 @@
+use super::{Instruction, Label};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Index of a basic block in `Cfg::blocks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(usize);
+
+impl BlockId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A maximal sequence of instructions that is only entered at its start
+/// and only left at its end.
+///
+/// A block starts with its labels, if it has any, and ends with its jump,
+/// call or return, if it has one.
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub instructions: Vec<Instruction>,
+    successors: Vec<BlockId>,
+    predecessors: Vec<BlockId>,
+}
+
+impl BasicBlock {
+    pub fn successors(&self) -> &[BlockId] {
+        &self.successors
+    }
+
+    pub fn predecessors(&self) -> &[BlockId] {
+        &self.predecessors
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = Label> + '_ {
+        self.instructions
+            .iter()
+            .map_while(|instruction| match instruction {
+                Instruction::Label { label } => Some(*label),
+                _ => None,
+            })
+    }
+
+    /// Returns the instruction the block ends with, if it transfers control.
+    pub fn terminator(&self) -> Option<&Instruction> {
+        match self.instructions.last() {
+            Some(
+                terminator @ Instruction::Jump { .. }
+                | terminator @ Instruction::JNegative { .. }
+                | terminator @ Instruction::JPositive { .. }
+                | terminator @ Instruction::JZero { .. }
+                | terminator @ Instruction::Call { .. }
+                | terminator @ Instruction::Return { .. },
+            ) => Some(terminator),
+            _ => None,
+        }
+    }
+}
+
+/// Control-flow graph of the instructions of a `Context`.
+///
+/// Blocks keep the order of the instructions they were built from, so that
+/// concatenating them gives back a program in which every fallthrough still
+/// leads to the same block.
+///
+/// Calls are treated like any other instruction, with the block after the
+/// call as their successor. The entry of a procedure is only reached by calls,
+/// so it's one of the `entries` of the graph, and its returns have no successors.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    blocks: Vec<BasicBlock>,
+    entries: Vec<BlockId>,
+    labels: BTreeMap<Label, BlockId>,
+}
+
+impl Cfg {
+    pub fn new(instructions: &[Instruction]) -> Self {
+        let mut blocks = vec![];
+        let mut current: Vec<Instruction> = vec![];
+
+        for instruction in instructions {
+            let is_label = matches!(instruction, Instruction::Label { .. });
+            let starts_block = is_label
+                && current
+                    .iter()
+                    .any(|instruction| !matches!(instruction, Instruction::Label { .. }));
+            if starts_block {
+                blocks.push(std::mem::take(&mut current));
+            }
+
+            current.push(instruction.clone());
+
+            let ends_block = matches!(
+                instruction,
+                Instruction::Jump { .. }
+                    | Instruction::JNegative { .. }
+                    | Instruction::JPositive { .. }
+                    | Instruction::JZero { .. }
+                    | Instruction::Call { .. }
+                    | Instruction::Return { .. }
+            );
+            if ends_block {
+                blocks.push(std::mem::take(&mut current));
+            }
+        }
+        if !current.is_empty() || blocks.is_empty() {
+            blocks.push(current);
+        }
+
+        let mut cfg = Cfg {
+            blocks: blocks
+                .into_iter()
+                .map(|instructions| BasicBlock {
+                    instructions,
+                    successors: vec![],
+                    predecessors: vec![],
+                })
+                .collect(),
+            entries: vec![BlockId(0)],
+            labels: BTreeMap::new(),
+        };
+
+        for (index, block) in cfg.blocks.iter().enumerate() {
+            for label in block.labels() {
+                cfg.labels.insert(label, BlockId(index));
+            }
+        }
+
+        for index in 0..cfg.blocks.len() {
+            let next = Some(BlockId(index + 1)).filter(|next| next.0 < cfg.blocks.len());
+            let successors = match cfg.blocks[index].terminator() {
+                Some(Instruction::Jump { label }) => vec![cfg.labels[label]],
+                Some(Instruction::JNegative { label })
+                | Some(Instruction::JPositive { label })
+                | Some(Instruction::JZero { label }) => {
+                    let mut successors = vec![cfg.labels[label]];
+                    successors.extend(next.filter(|&next| next != cfg.labels[label]));
+                    successors
+                }
+                Some(Instruction::Call { label, .. }) => {
+                    let entry = cfg.labels[label];
+                    if !cfg.entries.contains(&entry) {
+                        cfg.entries.push(entry);
+                    }
+                    next.into_iter().collect()
+                }
+                Some(Instruction::Return { .. }) => vec![],
+                _ => next.into_iter().collect(),
+            };
+
+            for &successor in &successors {
+                cfg.blocks[successor.0].predecessors.push(BlockId(index));
+            }
+            cfg.blocks[index].successors = successors;
+        }
+
+        cfg
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id.0]
+    }
+
+    /// Gives access to the instructions of a block. Edges aren't updated,
+    /// so a pass that changes jumps has to build a new graph afterwards.
+    pub fn block_mut(&mut self, id: BlockId) -> &mut BasicBlock {
+        &mut self.blocks[id.0]
+    }
+
+    pub fn block_ids(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.blocks.len()).map(BlockId)
+    }
+
+    /// The start of the program, followed by the entries of called procedures.
+    pub fn entries(&self) -> &[BlockId] {
+        &self.entries
+    }
+
+    /// Returns the block starting with `label`.
+    pub fn label_block(&self, label: Label) -> BlockId {
+        self.labels[&label]
+    }
+
+    /// Returns the blocks reachable from the entries, every block after all of
+    /// its predecessors except the ones reached through back edges.
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut postorder = vec![];
+
+        // the first entry is searched last, so that it comes first after reversing
+        for &entry in self.entries.iter().rev() {
+            if visited[entry.0] {
+                continue;
+            }
+            visited[entry.0] = true;
+
+            // depth-first search with an explicit stack of (block, next successor)
+            let mut stack = vec![(entry, 0)];
+            while let Some((block, next)) = stack.pop() {
+                match self.blocks[block.0].successors.get(next) {
+                    Some(&successor) => {
+                        stack.push((block, next + 1));
+                        if !visited[successor.0] {
+                            visited[successor.0] = true;
+                            stack.push((successor, 0));
+                        }
+                    }
+                    None => postorder.push(block),
+                }
+            }
+        }
+
+        postorder.reverse();
+        postorder
+    }
+
+    pub fn is_reachable(&self, id: BlockId) -> bool {
+        self.reverse_postorder().contains(&id)
+    }
+
+    /// Computes the dominator tree with the algorithm of Cooper, Harvey and Kennedy.
+    pub fn dominators(&self) -> Dominators {
+        // positions in reverse postorder, after a virtual root at 0 that dominates all entries
+        let order = self.reverse_postorder();
+        let mut position = vec![None; self.blocks.len()];
+        for (i, block) in order.iter().enumerate() {
+            position[block.0] = Some(i + 1);
+        }
+
+        let root = 0;
+        let mut idom: Vec<Option<usize>> = vec![None; order.len() + 1];
+        idom[root] = Some(root);
+        for &entry in &self.entries {
+            idom[position[entry.0].unwrap()] = Some(root);
+        }
+
+        let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while a > b {
+                    a = idom[a].unwrap();
+                }
+                while b > a {
+                    b = idom[b].unwrap();
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, block) in order.iter().enumerate() {
+                if self.entries.contains(block) {
+                    continue;
+                }
+                let i = i + 1;
+
+                let new_idom = self.blocks[block.0]
+                    .predecessors
+                    .iter()
+                    .filter_map(|predecessor| position[predecessor.0])
+                    .filter(|&predecessor| idom[predecessor].is_some())
+                    .fold(None, |new_idom, predecessor| match new_idom {
+                        None => Some(predecessor),
+                        Some(new_idom) => Some(intersect(&idom, predecessor, new_idom)),
+                    });
+
+                if new_idom.is_some() && idom[i] != new_idom {
+                    idom[i] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut immediate = vec![None; self.blocks.len()];
+        for (i, block) in order.iter().enumerate() {
+            immediate[block.0] = idom[i + 1]
+                .filter(|&idom| idom != root)
+                .map(|idom| order[idom - 1]);
+        }
+
+        Dominators {
+            immediate,
+            reachable: position.iter().map(Option::is_some).collect(),
+        }
+    }
+
+    /// Finds the natural loops of the graph, given its dominators.
+    ///
+    /// Back edges to the same header make up a single loop.
+    pub fn loops(&self, dominators: &Dominators) -> Loops {
+        let mut bodies: BTreeMap<BlockId, BTreeSet<BlockId>> = BTreeMap::new();
+
+        for block in self.block_ids() {
+            for &successor in &self.blocks[block.0].successors {
+                if !dominators.dominates(successor, block) {
+                    continue;
+                }
+
+                // everything that reaches the back edge without passing through the header
+                let body = bodies.entry(successor).or_insert_with(|| {
+                    let mut body = BTreeSet::new();
+                    body.insert(successor);
+                    body
+                });
+                let mut stack = vec![block];
+                while let Some(member) = stack.pop() {
+                    if body.insert(member) {
+                        stack.extend(&self.blocks[member.0].predecessors);
+                    }
+                }
+            }
+        }
+
+        let mut loops: Vec<Loop> = bodies
+            .into_iter()
+            .map(|(header, blocks)| Loop {
+                header,
+                blocks,
+                parent: None,
+                depth: 1,
+            })
+            .collect();
+        // outer loops are bigger than the loops they contain
+        loops.sort_by_key(|l| std::cmp::Reverse(l.blocks.len()));
+
+        for i in 0..loops.len() {
+            let parent = (0..i)
+                .rev()
+                .find(|&j| loops[j].blocks.contains(&loops[i].header));
+            if let Some(parent) = parent {
+                loops[i].parent = Some(parent);
+                loops[i].depth = loops[parent].depth + 1;
+            }
+        }
+
+        let mut innermost = vec![None; self.blocks.len()];
+        for (i, l) in loops.iter().enumerate() {
+            for block in &l.blocks {
+                // later loops are nested deeper
+                innermost[block.0] = Some(i);
+            }
+        }
+
+        Loops { loops, innermost }
+    }
+
+    /// Returns the instructions of all blocks, in order.
+    pub fn to_instructions(&self) -> Vec<Instruction> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.instructions.iter().cloned())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    immediate: Vec<Option<BlockId>>,
+    reachable: Vec<bool>,
+}
+
+impl Dominators {
+    /// Returns `None` for entries and unreachable blocks.
+    pub fn immediate_dominator(&self, id: BlockId) -> Option<BlockId> {
+        self.immediate[id.0]
+    }
+
+    /// Every reachable block dominates itself.
+    pub fn dominates(&self, dominator: BlockId, id: BlockId) -> bool {
+        if !self.reachable[id.0] {
+            return false;
+        }
+
+        let mut current = Some(id);
+        while let Some(block) = current {
+            if block == dominator {
+                return true;
+            }
+            current = self.immediate[block.0];
+        }
+        false
+    }
+
+    /// Returns the blocks immediately dominated by `id`.
+    pub fn children(&self, id: BlockId) -> Vec<BlockId> {
+        self.immediate
+            .iter()
+            .enumerate()
+            .filter(|(_, idom)| **idom == Some(id))
+            .map(|(block, _)| BlockId(block))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Loop {
+    pub header: BlockId,
+    /// Blocks of the loop, including the header and the blocks of nested loops.
+    pub blocks: BTreeSet<BlockId>,
+    /// Index of the innermost loop containing this one.
+    pub parent: Option<usize>,
+    /// 1 for outermost loops.
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Loops {
+    loops: Vec<Loop>,
+    innermost: Vec<Option<usize>>,
+}
+
+impl Loops {
+    /// Outer loops come before the loops nested in them.
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    /// Returns the index of the innermost loop containing `id`.
+    pub fn innermost_loop(&self, id: BlockId) -> Option<usize> {
+        self.innermost[id.0]
+    }
+
+    /// Returns how many loops contain `id`, 0 outside of loops.
+    pub fn depth(&self, id: BlockId) -> usize {
+        self.innermost[id.0].map_or(0, |l| self.loops[l].depth)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::code_generator::intermediate::generate;
+
+    fn build(code: &str) -> (Vec<Instruction>, Cfg) {
+        let program = parser::parse_ast(code).expect("invalid test program");
+        let context = generate(&program).expect("IR generation failed");
+        let instructions = context.instructions().to_vec();
+        let cfg = Cfg::new(&instructions);
+        (instructions, cfg)
+    }
+
+    fn ends_with_jump(block: &BasicBlock) -> bool {
+        matches!(block.terminator(), Some(Instruction::Jump { .. }))
+    }
+
+    #[test]
+    fn straight_line() {
+        let (instructions, cfg) = build("DECLARE a BEGIN READ a; WRITE a; END");
+
+        assert_eq!(cfg.blocks().len(), 1);
+        assert!(cfg.block(BlockId(0)).successors().is_empty());
+        assert_eq!(cfg.block(BlockId(0)).instructions.len(), instructions.len());
+    }
+
+    #[test]
+    fn if_else_diamond() {
+        let (_, cfg) = build(
+            "DECLARE a BEGIN READ a; IF a EQ 0 THEN WRITE 1; ELSE WRITE 2; ENDIF WRITE a; END",
+        );
+        let dominators = cfg.dominators();
+
+        // condition, first branch, second branch, join
+        assert_eq!(cfg.blocks().len(), 4);
+        let entry = BlockId(0);
+        let join = BlockId(3);
+        assert_eq!(cfg.block(entry).successors().len(), 2);
+        assert_eq!(cfg.block(join).predecessors().len(), 2);
+        assert_eq!(dominators.immediate_dominator(join), Some(entry));
+        assert!(!dominators.dominates(BlockId(1), join));
+        assert!(dominators.dominates(entry, BlockId(2)));
+        assert_eq!(dominators.children(entry).len(), 3);
+        assert!(cfg.loops(&dominators).loops().is_empty());
+    }
+
+    #[test]
+    fn nested_loops() {
+        let (_, cfg) = build(
+            r#"
+            DECLARE n, s
+            BEGIN
+                READ n;
+                s ASSIGN 0;
+                FOR i FROM 1 TO n DO
+                    WHILE s LE i DO
+                        s ASSIGN s PLUS 1;
+                    ENDWHILE
+                ENDFOR
+                WRITE s;
+            END
+            "#,
+        );
+        let dominators = cfg.dominators();
+        let loops = cfg.loops(&dominators);
+
+        assert_eq!(loops.loops().len(), 2);
+        let (outer, inner) = (&loops.loops()[0], &loops.loops()[1]);
+        assert_eq!(outer.depth, 1);
+        assert_eq!(inner.depth, 2);
+        assert_eq!(inner.parent, Some(0));
+        assert!(inner.blocks.is_subset(&outer.blocks));
+        for l in loops.loops() {
+            for &block in &l.blocks {
+                assert!(dominators.dominates(l.header, block));
+            }
+        }
+
+        assert_eq!(loops.depth(BlockId(0)), 0);
+        let last = BlockId(cfg.blocks().len() - 1);
+        assert_eq!(loops.depth(last), 0);
+        let deepest = cfg.block_ids().map(|block| loops.depth(block)).max();
+        assert_eq!(deepest, Some(2));
+    }
+
+    #[test]
+    fn procedures_are_entries() {
+        let (_, cfg) = build(
+            r#"
+            PROCEDURE p(a) IS
+            BEGIN
+                a ASSIGN a PLUS 1;
+            END
+            PROCEDURE unused(a) IS
+            BEGIN
+                WRITE a;
+            END
+            DECLARE a
+            BEGIN
+                READ a;
+                p(a);
+                p(a);
+                WRITE a;
+            END
+            "#,
+        );
+
+        // the program jumps over the procedures
+        assert!(ends_with_jump(cfg.block(BlockId(0))));
+        assert_eq!(cfg.entries().len(), 2);
+        let procedure = cfg.entries()[1];
+        assert!(cfg.block(procedure).predecessors().is_empty());
+        assert!(matches!(
+            cfg.block(procedure).terminator(),
+            Some(Instruction::Return { .. })
+        ));
+
+        let order = cfg.reverse_postorder();
+        assert_eq!(order[0], BlockId(0));
+        assert!(order.contains(&procedure));
+        let unused = BlockId(procedure.index() + 1);
+        assert!(!cfg.is_reachable(unused));
+        let dominators = cfg.dominators();
+        assert_eq!(dominators.immediate_dominator(procedure), None);
+        assert!(!dominators.dominates(unused, unused));
+    }
+
+    #[test]
+    fn linear_form_round_trip() {
+        let (instructions, cfg) = build(
+            r#"
+            DECLARE n, t(0:9)
+            BEGIN
+                READ n;
+                DO
+                    IF n GE 5 AND n LE 100 THEN
+                        t(n MOD 10) ASSIGN n;
+                        BREAK;
+                    ENDIF
+                    n ASSIGN n PLUS 7;
+                WHILE n LE 50 ENDDO
+                WRITE n;
+            END
+            "#,
+        );
+
+        assert_eq!(
+            format!("{:?}", cfg.to_instructions()),
+            format!("{:?}", instructions)
+        );
+        for block in cfg.blocks() {
+            for &successor in block.successors() {
+                let predecessors = cfg.block(successor).predecessors();
+                assert!(predecessors
+                    .iter()
+                    .any(|&p| std::ptr::eq(cfg.block(p), block)));
+            }
+        }
+    }
+}
@@ -1,22 +1,51 @@
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
+#[cfg(feature = "bignum")]
+use num_traits::ToPrimitive;
 use parser::ast;
 use parser::ast::visitor::Visitable;
 
+pub mod cfg;
+pub mod optimizations;
 mod variable;
 use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::Debug;
 pub use variable::*;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Constant(pub i64);
+#[cfg(not(feature = "bignum"))]
+pub type ConstantValue = i64;
+#[cfg(feature = "bignum")]
+pub type ConstantValue = BigInt;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Constant(pub ConstantValue);
 
 impl Constant {
     pub fn repr(&self) -> String {
         format!("const({})", self.0)
     }
 
-    pub fn value(&self) -> i64 {
-        self.0
+    pub fn value(&self) -> &ConstantValue {
+        &self.0
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        #[cfg(feature = "bignum")]
+        {
+            self.0.to_i64()
+        }
+
+        #[cfg(not(feature = "bignum"))]
+        {
+            Some(self.0)
+        }
+    }
+}
+
+impl From<i64> for Constant {
+    fn from(value: i64) -> Self {
+        Constant(value.into())
     }
 }
 
@@ -25,7 +54,24 @@ pub enum Access {
     Constant(Constant),
     Variable(VariableIndex),
     ArrayStatic(VariableIndex, Constant),
-    ArrayDynamic(VariableIndex, VariableIndex),
+    /// An element of an array at the index read from another access,
+    /// which may be an array element itself.
+    ArrayDynamic(VariableIndex, Box<Access>),
+    /// The unit variable whose address is held by a reference.
+    Indirect(VariableIndex),
+    /// The address of a unit variable, only ever loaded.
+    Address(VariableIndex),
+}
+
+impl Access {
+    /// Returns the access itself, followed by the accesses nested in its index.
+    pub fn with_nested(&self) -> Vec<&Access> {
+        let mut accesses = vec![self];
+        if let Access::ArrayDynamic(_, index) = self {
+            accesses.extend(index.with_nested());
+        }
+        accesses
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -91,8 +137,27 @@ pub enum Instruction {
         label: Label,
     },
 
+    /// Halts the program if `index` isn't between `start` and `end`,
+    /// when the program is translated with bounds checks. Overwrites the accumulator.
+    CheckBounds {
+        index: Access,
+        start: Access,
+        end: Access,
+    },
+
     Get, // print p0
     Put, // read p0
+
+    // jumps to a procedure, which returns to the label following the call
+    Call {
+        label: Label,
+        return_address: VariableIndex,
+        return_label: Label,
+    },
+    // jumps back to the call site recorded in `return_address`
+    Return {
+        return_address: VariableIndex,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -129,6 +194,11 @@ impl Context {
     pub fn instructions(&self) -> &[Instruction] {
         self.instructions.as_slice()
     }
+
+    /// Replaces the instructions, e.g. with the linear form of an optimized `cfg::Cfg`.
+    pub fn set_instructions(&mut self, instructions: Vec<Instruction>) {
+        self.instructions = instructions;
+    }
 }
 
 impl Debug for Context {
@@ -196,8 +266,8 @@ impl Context {
     }
 
     pub fn register_constant(&mut self, constant: Constant) -> VariableIndex {
-        if self.constants.contains_key(&constant) {
-            self.constants[&constant]
+        if let Some(&index) = self.constants.get(&constant) {
+            index
         } else {
             let index = self.add_variable(Variable::Unit {
                 name: constant.repr(),
@@ -238,19 +308,59 @@ impl AccessStack {
     }
 }
 
+#[derive(Debug, Clone)]
+struct Procedure {
+    name: String,
+    label: Label,
+    return_address: VariableIndex,
+    parameters: Vec<VariableIndex>,
+}
+
+/// Where BREAK and CONTINUE jump to inside a loop.
+#[derive(Debug, Clone, Copy)]
+struct Loop {
+    next: Label,
+    exit: Label,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Emit `Instruction::CheckBounds` for the indices the translator can't check on its own,
+    /// which are indices into array parameters and the index of every dimension of
+    /// multi-dimensional arrays. The bounds of arrays passed to procedures are then
+    /// passed along with them.
+    pub bounds_checks: bool,
+}
+
 #[derive(Debug)]
 struct CodeGenerator {
+    options: Options,
     context: Context,
     locals: Vec<VariableIndex>,
     access_stack: AccessStack,
+    temporaries: Vec<VariableIndex>,
+    used_temporaries: usize,
+    procedures: Vec<Procedure>,
+    dereferenced: BTreeMap<VariableIndex, VariableIndex>,
+    /// Variables holding the first and the last index of the array passed
+    /// to an array parameter, if bounds checks are enabled.
+    parameter_bounds: BTreeMap<VariableIndex, (VariableIndex, VariableIndex)>,
+    loops: Vec<Loop>,
 }
 
 impl CodeGenerator {
-    fn new() -> Self {
+    fn new(options: Options) -> Self {
         CodeGenerator {
+            options,
             context: Context::new(),
             locals: vec![],
             access_stack: AccessStack::new(),
+            temporaries: vec![],
+            used_temporaries: 0,
+            procedures: vec![],
+            dereferenced: BTreeMap::new(),
+            parameter_bounds: BTreeMap::new(),
+            loops: vec![],
         }
     }
 
@@ -271,12 +381,58 @@ impl CodeGenerator {
         }
     }
 
+    /// Returns a temporary for an intermediate result of an expression.
+    ///
+    /// Temporaries are reused once the operation that acquired them is emitted,
+    /// so nesting depth, not expression count, decides how many are allocated.
+    fn acquire_temporary(&mut self) -> VariableIndex {
+        if self.used_temporaries == self.temporaries.len() {
+            let name = format!("tmp$expr{}", self.temporaries.len());
+            let temporary = self.context.add_variable(Variable::Unit { name });
+            self.temporaries.push(temporary);
+        }
+
+        self.used_temporaries += 1;
+        self.temporaries[self.used_temporaries - 1]
+    }
+
+    /// Returns the variable with the given name in the current scope.
+    ///
+    /// Procedures are generated before the main program, so the variables of
+    /// the scope being generated are the most recently added ones.
     fn find_variable_by_name(&self, name: &str) -> Option<&UniqueVariable> {
+        let is_named = |var: &&UniqueVariable| var.variable().name() == name;
         self.locals
             .iter()
             .map(|ind| self.context.get_variable(ind))
-            .rfind(|&var| var.variable().name() == name)
-            .or_else(|| self.context.find_variable_by_name(name))
+            .rfind(is_named)
+            .or_else(|| self.context.variables().iter().rfind(is_named))
+    }
+
+    /// Returns a variable holding the value of a unit reference,
+    /// so that it can be used as an array index.
+    fn dereference(&mut self, reference: VariableIndex) -> VariableIndex {
+        let value = match self.dereferenced.get(&reference) {
+            Some(&value) => value,
+            None => {
+                let name = self.context.get_variable(&reference).variable().name();
+                let name = format!("{}$value", name);
+                let value = self.context.add_variable(Variable::Unit { name });
+                self.dereferenced.insert(reference, value);
+                value
+            }
+        };
+
+        self.emit(Instruction::PreStore {
+            access: Access::Variable(value),
+        });
+        self.emit(Instruction::Load {
+            access: Access::Indirect(reference),
+        });
+        self.emit(Instruction::Store {
+            access: Access::Variable(value),
+        });
+        value
     }
 
     fn push_access(&mut self, access: Access) {
@@ -305,13 +461,13 @@ impl CodeGenerator {
         let access = self.access_stack.pop();
         self.emit(Instruction::Store { access });
     }
-//
-//    fn emit_temporary_store(&mut self) -> VariableIndex {
-//        let index = self.find_variable_by_name("tmp$op").unwrap().id();
-//        let access = Access::Variable(index);
-//        self.emit(Instruction::Store { access });
-//        index
-//    }
+    //
+    //    fn emit_temporary_store(&mut self) -> VariableIndex {
+    //        let index = self.find_variable_by_name("tmp$op").unwrap().id();
+    //        let access = Access::Variable(index);
+    //        self.emit(Instruction::Store { access });
+    //        index
+    //    }
 
     fn new_label(&mut self) -> Label {
         self.context.new_label()
@@ -321,7 +477,12 @@ impl CodeGenerator {
 mod visitor_impl;
 
 pub fn generate(program: &ast::Program) -> Result<Context, ()> {
-    let mut generator = CodeGenerator::new();
+    generate_with_options(program, Options::default())
+}
+
+#[allow(clippy::result_unit_err)]
+pub fn generate_with_options(program: &ast::Program, options: Options) -> Result<Context, ()> {
+    let mut generator = CodeGenerator::new(options);
     program.accept(&mut generator);
 
     Ok(generator.context)
@@ -335,22 +496,33 @@ mod test {
     fn it_works() {
         let var_a = ast::Identifier::VarAccess {
             name: String::from("a"),
+            span: ast::Span::default(),
         };
         let program = ast::Program {
+            files: vec![],
+            procedures: vec![],
             declarations: Some(vec![ast::Declaration::Var {
                 name: String::from("a"),
+                span: ast::Span::default(),
             }]),
             commands: vec![
                 ast::Command::Read {
-                    target: var_a.clone(),
+                    targets: vec![var_a.clone()],
+                    span: ast::Span::default(),
                 },
                 ast::Command::Write {
-                    value: ast::Value::Num(1),
+                    values: vec![ast::Value::Num {
+                        value: 1,
+                        span: ast::Span::default(),
+                    }],
+                    span: ast::Span::default(),
                 },
                 ast::Command::Write {
-                    value: ast::Value::Identifier(var_a.clone()),
+                    values: vec![ast::Value::Identifier(var_a.clone())],
+                    span: ast::Span::default(),
                 },
             ],
+            span: ast::Span::default(),
         };
 
         let result = super::generate(&program);
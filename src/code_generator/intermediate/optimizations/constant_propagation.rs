@@ -0,0 +1,488 @@
+use super::super::cfg::{BlockId, Cfg};
+use super::super::{
+    Access, Constant, ConstantValue, Context, Instruction, OperationType, Variable, VariableIndex,
+};
+use num_integer::Integer;
+use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, Signed, ToPrimitive, Zero};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What is known about the values at a point of the program.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct State {
+    /// Unit variables known to hold a constant.
+    variables: BTreeMap<VariableIndex, Constant>,
+    /// The value left by the last load or operation, if it's known.
+    accumulator: Option<Constant>,
+}
+
+impl State {
+    /// Keeps only the facts that also hold in `other`. Returns whether anything was forgotten.
+    fn meet(&mut self, other: &State) -> bool {
+        let known = (self.variables.len(), self.accumulator.is_some());
+
+        self.variables
+            .retain(|variable, value| other.variables.get(variable) == Some(value));
+        if self.accumulator != other.accumulator {
+            self.accumulator = None;
+        }
+
+        known != (self.variables.len(), self.accumulator.is_some())
+    }
+
+    fn value(&self, access: &Access) -> Option<Constant> {
+        match access {
+            Access::Constant(constant) => Some(constant.clone()),
+            Access::Variable(variable) => self.variables.get(variable).cloned(),
+            _ => None,
+        }
+    }
+}
+
+struct Propagation<'a> {
+    context: &'a mut Context,
+    /// Variables passed to procedures, which can change them through references.
+    address_taken: BTreeSet<VariableIndex>,
+}
+
+impl Propagation<'_> {
+    fn new(context: &mut Context) -> Propagation<'_> {
+        let address_taken = context
+            .instructions()
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Load {
+                    access: Access::Address(variable),
+                } => Some(*variable),
+                _ => None,
+            })
+            .collect();
+
+        Propagation {
+            context,
+            address_taken,
+        }
+    }
+
+    fn is_tracked(&self, variable: VariableIndex) -> bool {
+        matches!(
+            self.context.get_variable(&variable).variable(),
+            Variable::Unit { .. }
+        )
+    }
+
+    fn forget_address_taken(&self, state: &mut State) {
+        state
+            .variables
+            .retain(|variable, _| !self.address_taken.contains(variable));
+    }
+
+    /// Updates the state to hold after the instruction.
+    fn transfer(&self, state: &mut State, instruction: &Instruction) {
+        match instruction {
+            Instruction::Load { access } => state.accumulator = state.value(access),
+            Instruction::Store {
+                access: Access::Variable(variable),
+            } if self.is_tracked(*variable) => match &state.accumulator {
+                Some(value) => {
+                    state.variables.insert(*variable, value.clone());
+                }
+                None => {
+                    state.variables.remove(variable);
+                }
+            },
+            Instruction::Store {
+                access: Access::Indirect(_),
+            } => self.forget_address_taken(state),
+            Instruction::Operation { left, op, right } => {
+                state.accumulator = match (state.value(left), state.value(right)) {
+                    (Some(left), Some(right)) => fold(&left, *op, &right),
+                    _ => None,
+                };
+            }
+            Instruction::Get | Instruction::CheckBounds { .. } => state.accumulator = None,
+            Instruction::Call { return_address, .. } => {
+                self.forget_address_taken(state);
+                state.variables.remove(return_address);
+                state.accumulator = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the states at the starts of the blocks, `None` for unreachable ones.
+    fn analyze(&self, cfg: &Cfg) -> Vec<Option<State>> {
+        let mut states = vec![None; cfg.blocks().len()];
+        for entry in cfg.entries() {
+            states[entry.index()] = Some(State::default());
+        }
+
+        let order = cfg.reverse_postorder();
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for &id in &order {
+                let mut state = match &states[id.index()] {
+                    Some(state) => state.clone(),
+                    None => continue,
+                };
+                for instruction in &cfg.block(id).instructions {
+                    self.transfer(&mut state, instruction);
+                }
+
+                for successor in cfg.block(id).successors() {
+                    match &mut states[successor.index()] {
+                        Some(successor_state) => changed |= successor_state.meet(&state),
+                        successor_state @ None => {
+                            *successor_state = Some(state.clone());
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        states
+    }
+
+    fn constant(&mut self, value: Constant) -> Access {
+        self.context.register_constant(value.clone());
+        Access::Constant(value)
+    }
+
+    /// Replaces a read of a variable with a known value by the value itself.
+    fn substitute(&mut self, state: &State, access: &Access) -> Access {
+        match access {
+            Access::Variable(variable) => match state.variables.get(variable) {
+                Some(value) => self.constant(value.clone()),
+                None => access.clone(),
+            },
+            _ => self.substitute_index(state, access),
+        }
+    }
+
+    /// Replaces the index of an array element with its value if it's known,
+    /// turning the element into a static one if the index is within bounds.
+    fn substitute_index(&mut self, state: &State, access: &Access) -> Access {
+        match access {
+            Access::ArrayDynamic(array, index) => {
+                let index = self.substitute(state, index);
+                match index {
+                    Access::Constant(value) if self.is_within_bounds(*array, &value) => {
+                        Access::ArrayStatic(*array, value)
+                    }
+                    index => Access::ArrayDynamic(*array, Box::new(index)),
+                }
+            }
+            _ => access.clone(),
+        }
+    }
+
+    fn is_within_bounds(&self, array: VariableIndex, index: &Constant) -> bool {
+        let variable = self.context.get_variable(&array).variable();
+        match (variable, index.as_i64()) {
+            (Variable::Array { .. }, Some(index)) => {
+                let (start, end) = variable.linear_range();
+                start <= index && index <= end
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the instruction with the known values substituted,
+    /// or `None` for a conditional jump that is never taken and a bounds check that always passes.
+    fn rewrite(&mut self, state: &State, instruction: &Instruction) -> Option<Instruction> {
+        let rewritten = match instruction {
+            Instruction::Load { access } => Instruction::Load {
+                access: self.substitute(state, access),
+            },
+            Instruction::PreStore { access } => Instruction::PreStore {
+                access: self.substitute_index(state, access),
+            },
+            Instruction::Store { access } => Instruction::Store {
+                access: self.substitute_index(state, access),
+            },
+            Instruction::Operation { left, op, right } => {
+                let folded = match (state.value(left), state.value(right)) {
+                    (Some(left), Some(right)) => fold(&left, *op, &right),
+                    _ => None,
+                };
+                match folded {
+                    Some(value) => Instruction::Load {
+                        access: self.constant(value),
+                    },
+                    None => Instruction::Operation {
+                        left: self.substitute(state, left),
+                        op: *op,
+                        right: self.substitute(state, right),
+                    },
+                }
+            }
+            Instruction::JNegative { label }
+            | Instruction::JZero { label }
+            | Instruction::JPositive { label } => match &state.accumulator {
+                Some(value) if is_taken(instruction, value) => Instruction::Jump { label: *label },
+                Some(_) => return None,
+                None => instruction.clone(),
+            },
+            Instruction::CheckBounds { index, start, end } => {
+                let values = (state.value(index), state.value(start), state.value(end));
+                if let (Some(index), Some(start), Some(end)) = values {
+                    if start <= index && index <= end {
+                        return None;
+                    }
+                }
+                Instruction::CheckBounds {
+                    index: self.substitute(state, index),
+                    start: self.substitute(state, start),
+                    end: self.substitute(state, end),
+                }
+            }
+            _ => instruction.clone(),
+        };
+
+        Some(rewritten)
+    }
+
+    fn rewrite_block(&mut self, cfg: &mut Cfg, id: BlockId, mut state: State) {
+        let instructions = std::mem::take(&mut cfg.block_mut(id).instructions);
+        let mut rewritten = Vec::with_capacity(instructions.len());
+
+        for instruction in &instructions {
+            rewritten.extend(self.rewrite(&state, instruction));
+            self.transfer(&mut state, instruction);
+        }
+
+        cfg.block_mut(id).instructions = rewritten;
+    }
+}
+
+fn is_taken(jump: &Instruction, value: &Constant) -> bool {
+    match jump {
+        Instruction::JNegative { .. } => value.value().is_negative(),
+        Instruction::JZero { .. } => value.value().is_zero(),
+        Instruction::JPositive { .. } => value.value().is_positive(),
+        _ => panic!("{:?} is not a conditional jump", jump),
+    }
+}
+
+/// Returns the result of an operation on constants, as computed by the virtual machine,
+/// or `None` if it can't be represented.
+///
+/// Division rounds towards negative infinity, the remainder takes the sign of the divisor,
+/// and both give 0 for a divisor of 0. Shifts are only folded to the left.
+fn fold(left: &Constant, op: OperationType, right: &Constant) -> Option<Constant> {
+    let (left, right) = (left.value(), right.value());
+
+    let value: ConstantValue = match op {
+        OperationType::Plus => CheckedAdd::checked_add(left, right)?,
+        OperationType::Minus => CheckedSub::checked_sub(left, right)?,
+        OperationType::Times => CheckedMul::checked_mul(left, right)?,
+        OperationType::Div | OperationType::Mod if right.is_zero() => ConstantValue::zero(),
+        OperationType::Div | OperationType::Mod => {
+            // only fails on overflow once the divisor isn't 0
+            CheckedDiv::checked_div(left, right)?;
+            if op == OperationType::Div {
+                left.div_floor(right)
+            } else {
+                left.mod_floor(right)
+            }
+        }
+        OperationType::Shift => {
+            let amount = right.to_usize().filter(|&amount| amount < 64)?;
+            let factor = num_traits::checked_pow(ConstantValue::from(2), amount)?;
+            CheckedMul::checked_mul(left, &factor)?
+        }
+    };
+
+    Some(Constant(value))
+}
+
+/// Replaces reads of variables whose values are known at compile time with
+/// the constants they hold, and operations on constants with their results.
+///
+/// The values are found by a forward dataflow analysis over the control-flow
+/// graph, in which only unit variables are tracked and nothing is known at the
+/// start of the program or of a procedure. Conditional jumps on a known value
+/// are replaced with unconditional ones or removed, and instructions in
+/// unreachable blocks are left untouched.
+pub fn propagate_constants(context: &mut Context) {
+    let mut cfg = Cfg::new(context.instructions());
+    let mut propagation = Propagation::new(context);
+
+    let states = propagation.analyze(&cfg);
+    for (id, state) in cfg.block_ids().zip(states) {
+        if let Some(state) = state {
+            propagation.rewrite_block(&mut cfg, id, state);
+        }
+    }
+
+    context.set_instructions(cfg.to_instructions());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::code_generator::intermediate::generate;
+
+    fn propagated(code: &str) -> Vec<Instruction> {
+        let program = parser::parse_ast(code).expect("invalid test program");
+        let mut context = generate(&program).expect("IR generation failed");
+        propagate_constants(&mut context);
+        context.instructions().to_vec()
+    }
+
+    fn operations(instructions: &[Instruction]) -> Vec<OperationType> {
+        instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Operation { op, .. } => Some(*op),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn loaded_constants(instructions: &[Instruction]) -> Vec<i64> {
+        instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Load {
+                    access: Access::Constant(constant),
+                } => constant.as_i64(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn folded(left: i64, op: OperationType, right: i64) -> Option<i64> {
+        fold(&Constant::from(left), op, &Constant::from(right)).and_then(|value| value.as_i64())
+    }
+
+    #[test]
+    fn folds_like_the_virtual_machine() {
+        use OperationType::*;
+
+        assert_eq!(folded(5, Times, 8), Some(40));
+        assert_eq!(folded(7, Minus, 10), Some(-3));
+        assert_eq!(folded(7, Div, 2), Some(3));
+        assert_eq!(folded(-7, Div, 2), Some(-4));
+        assert_eq!(folded(7, Div, -2), Some(-4));
+        assert_eq!(folded(-7, Mod, 2), Some(1));
+        assert_eq!(folded(7, Mod, -2), Some(-1));
+        assert_eq!(folded(7, Div, 0), Some(0));
+        assert_eq!(folded(7, Mod, 0), Some(0));
+        assert_eq!(folded(3, Shift, 4), Some(48));
+        assert_eq!(folded(48, Shift, -4), None);
+    }
+
+    #[test]
+    fn propagates_through_assignments() {
+        let instructions =
+            propagated("DECLARE a, b BEGIN a ASSIGN 5; b ASSIGN a TIMES 8; WRITE b; END");
+
+        assert!(operations(&instructions).is_empty());
+        assert_eq!(loaded_constants(&instructions), vec![5, 40, 40]);
+    }
+
+    #[test]
+    fn forgets_values_at_joins() {
+        let instructions = propagated(
+            r#"
+            DECLARE a, b, c, n BEGIN
+                READ n;
+                a ASSIGN 1;
+                b ASSIGN 1;
+                IF n EQ 0 THEN a ASSIGN 2; ENDIF
+                c ASSIGN a PLUS b;
+                WRITE c;
+            END
+            "#,
+        );
+
+        // the condition and the final sum, in which only b is known
+        let sums: Vec<_> = instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Operation {
+                    left,
+                    op: OperationType::Plus,
+                    right,
+                } => Some((left.clone(), right.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sums.len(), 1);
+        assert!(matches!(
+            sums[0],
+            (Access::Variable(_), Access::Constant(_))
+        ));
+    }
+
+    #[test]
+    fn forgets_values_changed_in_loops() {
+        let instructions = propagated(
+            r#"
+            DECLARE a, n BEGIN
+                READ n;
+                a ASSIGN 0;
+                WHILE n GE 0 DO
+                    a ASSIGN a PLUS 2;
+                    n ASSIGN n MINUS 1;
+                ENDWHILE
+                WRITE a;
+            END
+            "#,
+        );
+
+        assert_eq!(
+            operations(&instructions)
+                .iter()
+                .filter(|&&op| op == OperationType::Plus)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn constant_indices_become_static() {
+        let instructions =
+            propagated("DECLARE t(1:3), i BEGIN i ASSIGN 2; t(i) ASSIGN 7; WRITE t(i); END");
+
+        let dynamic = instructions.iter().any(|instruction| match instruction {
+            Instruction::Load { access } | Instruction::Store { access } => {
+                matches!(access, Access::ArrayDynamic(..))
+            }
+            _ => false,
+        });
+        assert!(!dynamic);
+    }
+
+    #[test]
+    fn out_of_bounds_indices_stay_dynamic() {
+        let instructions = propagated("DECLARE t(1:3), i BEGIN i ASSIGN 4; t(i) ASSIGN 7; END");
+
+        assert!(instructions.iter().any(|instruction| matches!(
+            instruction,
+            Instruction::Store {
+                access: Access::ArrayDynamic(_, _)
+            }
+        )));
+    }
+
+    #[test]
+    fn forgets_arguments_of_calls() {
+        let instructions = propagated(
+            r#"
+            PROCEDURE set(x) IS BEGIN x ASSIGN 3; END
+            DECLARE a, b BEGIN
+                a ASSIGN 1;
+                set(a);
+                b ASSIGN a PLUS 1;
+                WRITE b;
+            END
+            "#,
+        );
+
+        assert_eq!(operations(&instructions), vec![OperationType::Plus]);
+    }
+}
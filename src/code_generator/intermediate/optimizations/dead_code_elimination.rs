@@ -0,0 +1,359 @@
+use super::super::cfg::{BlockId, Cfg};
+use super::super::{Access, Context, Instruction, VariableIndex};
+use std::collections::BTreeSet;
+
+/// What may still be read at a point of the program.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Live {
+    variables: BTreeSet<VariableIndex>,
+    accumulator: bool,
+}
+
+impl Live {
+    /// Adds what is live in `other`. Returns whether anything was added.
+    fn join(&mut self, other: &Live) -> bool {
+        let count = (self.variables.len(), self.accumulator);
+
+        self.variables.extend(other.variables.iter().cloned());
+        self.accumulator |= other.accumulator;
+
+        count != (self.variables.len(), self.accumulator)
+    }
+
+    /// Marks the variables the access reads, including its index.
+    fn read(&mut self, access: &Access) {
+        match access {
+            Access::Constant(_) => {}
+            Access::Variable(variable)
+            | Access::ArrayStatic(variable, _)
+            | Access::Address(variable) => {
+                self.variables.insert(*variable);
+            }
+            Access::ArrayDynamic(array, index) => {
+                self.variables.insert(*array);
+                self.read(index);
+            }
+            Access::Indirect(reference) => {
+                self.variables.insert(*reference);
+            }
+        }
+    }
+
+    /// Marks the variables an access stored into reads, which are only the ones in its index.
+    fn read_index(&mut self, access: &Access) {
+        if let Access::ArrayDynamic(_, index) = access {
+            self.read(index);
+        }
+    }
+}
+
+/// Whether evaluating the access can stop the program, with bounds checks enabled.
+fn may_trap(access: &Access) -> bool {
+    matches!(access, Access::ArrayDynamic(..))
+}
+
+struct Liveness<'a> {
+    /// Everything, live at calls and returns, since the callee or the caller
+    /// may read any variable of theirs.
+    all: Live,
+    /// Variables whose address is passed to procedures, read by indirect loads.
+    address_taken: &'a BTreeSet<VariableIndex>,
+}
+
+impl Liveness<'_> {
+    /// Updates `live` to hold before the instruction.
+    /// Returns `false` if the instruction can be removed instead.
+    fn transfer(&self, live: &mut Live, instruction: &Instruction) -> bool {
+        match instruction {
+            Instruction::Load { access } => {
+                if !live.accumulator && !may_trap(access) {
+                    return false;
+                }
+                live.accumulator = false;
+                live.read(access);
+                if let Access::Indirect(_) = access {
+                    live.variables.extend(self.address_taken.iter().cloned());
+                }
+            }
+            Instruction::PreStore { .. } => {}
+            Instruction::Store {
+                access: Access::Variable(variable),
+            } => {
+                if !live.variables.remove(variable) {
+                    return false;
+                }
+                live.accumulator = true;
+            }
+            Instruction::Store { access } => {
+                live.read_index(access);
+                if let Access::Indirect(reference) = access {
+                    live.variables.insert(*reference);
+                }
+                live.accumulator = true;
+            }
+            Instruction::Operation { left, right, .. } => {
+                if !live.accumulator && !may_trap(left) && !may_trap(right) {
+                    return false;
+                }
+                live.accumulator = false;
+                live.read(left);
+                live.read(right);
+            }
+            Instruction::CheckBounds { index, start, end } => {
+                live.accumulator = false;
+                live.read(index);
+                live.read(start);
+                live.read(end);
+            }
+            Instruction::JNegative { .. }
+            | Instruction::JZero { .. }
+            | Instruction::JPositive { .. }
+            | Instruction::Put => live.accumulator = true,
+            Instruction::Get => live.accumulator = false,
+            Instruction::Call { .. } | Instruction::Return { .. } => *live = self.all.clone(),
+            Instruction::Label { .. } | Instruction::Jump { .. } => {}
+        }
+
+        true
+    }
+
+    fn live_in(&self, cfg: &Cfg, id: BlockId, mut live: Live) -> Live {
+        for instruction in cfg.block(id).instructions.iter().rev() {
+            self.transfer(&mut live, instruction);
+        }
+        live
+    }
+
+    /// Returns what is live at the ends of the blocks.
+    fn analyze(&self, cfg: &Cfg) -> Vec<Live> {
+        let mut live_in = vec![Live::default(); cfg.blocks().len()];
+        let mut live_out = vec![Live::default(); cfg.blocks().len()];
+
+        let mut order = cfg.reverse_postorder();
+        order.reverse();
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for &id in &order {
+                let mut live = Live::default();
+                for successor in cfg.block(id).successors() {
+                    live.join(&live_in[successor.index()]);
+                }
+                live_out[id.index()] = live.clone();
+
+                let live = self.live_in(cfg, id, live);
+                changed |= live_in[id.index()].join(&live);
+            }
+        }
+
+        live_out
+    }
+
+    fn rewrite_block(&self, cfg: &mut Cfg, id: BlockId, mut live: Live) {
+        let instructions = std::mem::take(&mut cfg.block_mut(id).instructions);
+        // whether the stores seen so far, and not yet matched with their
+        // `PreStore`s, are kept, with the innermost one last
+        let mut stores_kept = Vec::new();
+        let mut kept: Vec<_> = instructions
+            .into_iter()
+            .rev()
+            .filter(|instruction| match instruction {
+                Instruction::PreStore { .. } => stores_kept.pop().unwrap_or(true),
+                Instruction::Store { .. } => {
+                    let is_kept = self.transfer(&mut live, instruction);
+                    stores_kept.push(is_kept);
+                    is_kept
+                }
+                _ => self.transfer(&mut live, instruction),
+            })
+            .collect();
+        kept.reverse();
+
+        cfg.block_mut(id).instructions = kept;
+    }
+}
+
+/// Removes the blocks that are never executed and the instructions whose results are never read.
+///
+/// Stores into unit variables are removed if the variable is overwritten or
+/// the program ends before it's read again, and loads and operations if the
+/// accumulator is. `Get` and `Put`, which interact with the outside, are always
+/// kept, and so are bounds checks and accesses to elements at dynamic indices,
+/// which can be bounds checked. Everything is assumed to be read by calls and returns.
+///
+/// Branches of conditions that `propagate_constants` decided become unreachable
+/// and are removed with the rest.
+pub fn eliminate_dead_code(context: &mut Context) {
+    let cfg = Cfg::new(context.instructions());
+    let reachable: BTreeSet<_> = cfg.reverse_postorder().into_iter().collect();
+    let instructions: Vec<_> = cfg
+        .block_ids()
+        .filter(|id| reachable.contains(id))
+        .flat_map(|id| cfg.block(id).instructions.iter().cloned())
+        .collect();
+    let mut cfg = Cfg::new(&instructions);
+
+    let address_taken = context
+        .instructions()
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Load {
+                access: Access::Address(variable),
+            } => Some(*variable),
+            _ => None,
+        })
+        .collect();
+    let liveness = Liveness {
+        all: Live {
+            variables: context
+                .variables()
+                .iter()
+                .map(|variable| variable.id())
+                .collect(),
+            accumulator: false,
+        },
+        address_taken: &address_taken,
+    };
+
+    let live_out = liveness.analyze(&cfg);
+    for (id, live) in cfg.block_ids().zip(live_out) {
+        liveness.rewrite_block(&mut cfg, id, live);
+    }
+
+    context.set_instructions(cfg.to_instructions());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::code_generator::intermediate::generate;
+    use crate::code_generator::intermediate::optimizations::propagate_constants;
+
+    fn eliminated(code: &str) -> Vec<Instruction> {
+        let program = parser::parse_ast(code).expect("invalid test program");
+        let mut context = generate(&program).expect("IR generation failed");
+        propagate_constants(&mut context);
+        eliminate_dead_code(&mut context);
+        context.instructions().to_vec()
+    }
+
+    fn count(instructions: &[Instruction], predicate: fn(&Instruction) -> bool) -> usize {
+        instructions
+            .iter()
+            .filter(|&instruction| predicate(instruction))
+            .count()
+    }
+
+    fn is_store(instruction: &Instruction) -> bool {
+        matches!(instruction, Instruction::Store { .. })
+    }
+
+    fn is_put(instruction: &Instruction) -> bool {
+        matches!(instruction, Instruction::Put)
+    }
+
+    #[test]
+    fn removes_branches_on_constant_conditions() {
+        let instructions = eliminated(
+            "DECLARE a BEGIN IF 1 EQ 2 THEN WRITE 1; ELSE WRITE 2; ENDIF IF 1 LE 2 THEN WRITE 3; ENDIF END",
+        );
+
+        assert_eq!(count(&instructions, is_put), 2);
+        assert!(!instructions.iter().any(|instruction| matches!(
+            instruction,
+            Instruction::JNegative { .. }
+                | Instruction::JZero { .. }
+                | Instruction::JPositive { .. }
+        )));
+    }
+
+    #[test]
+    fn removes_overwritten_stores() {
+        let instructions = eliminated(
+            "DECLARE a, n BEGIN READ n; a ASSIGN n PLUS 1; a ASSIGN n MINUS 1; WRITE a; END",
+        );
+
+        // n and the second value of a
+        assert_eq!(count(&instructions, is_store), 2);
+        assert_eq!(
+            count(&instructions, |instruction| matches!(
+                instruction,
+                Instruction::PreStore { .. }
+            )),
+            2
+        );
+        assert_eq!(
+            count(&instructions, |instruction| matches!(
+                instruction,
+                Instruction::Operation { .. }
+            )),
+            1
+        );
+    }
+
+    #[test]
+    fn keeps_input_and_output() {
+        let instructions = eliminated("DECLARE a, b BEGIN READ a; READ b; WRITE 1; END");
+
+        assert_eq!(
+            count(&instructions, |instruction| matches!(
+                instruction,
+                Instruction::Get
+            )),
+            2
+        );
+        assert_eq!(count(&instructions, is_put), 1);
+        assert_eq!(count(&instructions, is_store), 0);
+        assert_eq!(
+            count(&instructions, |instruction| matches!(
+                instruction,
+                Instruction::PreStore { .. }
+            )),
+            0
+        );
+    }
+
+    #[test]
+    fn keeps_stores_read_in_loops() {
+        let instructions = eliminated(
+            r#"
+            DECLARE a, n BEGIN
+                READ n;
+                a ASSIGN 0;
+                WHILE n GE 0 DO
+                    a ASSIGN a PLUS n;
+                    n ASSIGN n MINUS 1;
+                ENDWHILE
+                WRITE a;
+            END
+            "#,
+        );
+
+        // n read, a initialized, a and n updated
+        assert_eq!(count(&instructions, is_store), 4);
+    }
+
+    #[test]
+    fn removes_uncalled_procedures() {
+        let instructions = eliminated(
+            r#"
+            PROCEDURE unused(x) IS BEGIN WRITE x; END
+            PROCEDURE used(x) IS BEGIN x ASSIGN 3; END
+            DECLARE a BEGIN
+                used(a);
+                WRITE a;
+            END
+            "#,
+        );
+
+        assert_eq!(count(&instructions, is_put), 1);
+        assert_eq!(
+            count(&instructions, |instruction| matches!(
+                instruction,
+                Instruction::Return { .. }
+            )),
+            1
+        );
+    }
+}
@@ -0,0 +1,16 @@
+//! Passes rewriting the instructions of a `Context` into cheaper ones
+//! with the same behaviour, built on top of its `cfg::Cfg`.
+
+mod constant_propagation;
+mod dead_code_elimination;
+
+use super::Context;
+
+pub use constant_propagation::propagate_constants;
+pub use dead_code_elimination::eliminate_dead_code;
+
+/// Runs all passes on the instructions of the context.
+pub fn optimize(context: &mut Context) {
+    propagate_constants(context);
+    eliminate_dead_code(context);
+}
@@ -1,15 +1,44 @@
 #[derive(Debug)]
 pub enum Variable {
-    Unit { name: String },
-    Array { name: String, start: i64, end: i64 },
+    Unit {
+        name: String,
+    },
+    /// Stored in row-major order, with one `(start, end)` range per dimension.
+    Array {
+        name: String,
+        dimensions: Vec<(i64, i64)>,
+    },
+    /// Procedure parameter holding the address of a unit variable,
+    /// or the base pointer of an array.
+    Reference {
+        name: String,
+        array: bool,
+    },
 }
 
 impl Variable {
     pub fn size(&self) -> usize {
         use Variable::*;
         match self {
-            Unit { .. } => 1,
-            Array { start, end, .. } => (end - start + 1) as usize,
+            Unit { .. } | Reference { .. } => 1,
+            Array { dimensions, .. } => dimensions
+                .iter()
+                .map(|(start, end)| (end - start + 1) as usize)
+                .product(),
+        }
+    }
+
+    /// Returns the linear indices of the first and the last element of an array.
+    pub fn linear_range(&self) -> (i64, i64) {
+        match self {
+            Variable::Array { dimensions, .. } => {
+                let (starts, ends): (Vec<_>, Vec<_>) = dimensions.iter().cloned().unzip();
+                (
+                    linear_index(dimensions, &starts),
+                    linear_index(dimensions, &ends),
+                )
+            }
+            _ => panic!("{} is not an array", self.name()),
         }
     }
 
@@ -18,10 +47,31 @@ impl Variable {
         match self {
             Unit { name } => name,
             Array { name, .. } => name,
+            Reference { name, .. } => name,
         }
     }
 }
 
+/// Returns how far apart consecutive indices of every dimension are in memory.
+pub fn strides(dimensions: &[(i64, i64)]) -> Vec<i64> {
+    let mut strides = vec![1; dimensions.len()];
+    for i in (1..dimensions.len()).rev() {
+        let (start, end) = dimensions[i];
+        strides[i - 1] = strides[i] * (end - start + 1);
+    }
+    strides
+}
+
+/// Maps the indices of an element to a single index, as if the array was
+/// one-dimensional. For one-dimensional arrays, that's the index itself.
+pub fn linear_index(dimensions: &[(i64, i64)], indices: &[i64]) -> i64 {
+    strides(dimensions)
+        .iter()
+        .zip(indices)
+        .map(|(stride, index)| stride * index)
+        .sum()
+}
+
 #[derive(Debug)]
 pub struct UniqueVariable {
     id: VariableIndex,
@@ -1,9 +1,13 @@
-use super::CodeGenerator;
+use super::{CodeGenerator, Loop, Procedure};
 use crate::code_generator::intermediate::variable::Variable;
-use crate::code_generator::intermediate::{Access, Constant, Instruction};
+use crate::code_generator::intermediate::{
+    Access, Constant, Instruction, Label, OperationType, VariableIndex,
+};
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
 use parser::ast;
 use parser::ast::visitor::Visitor;
-use parser::ast::{ExprOp, RelOp};
+use parser::ast::{ExprOp, LogicOp, RelOp};
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 enum Order {
@@ -20,26 +24,35 @@ impl CodeGenerator {
         let negative_label = self.new_label();
         let endif_label = self.new_label();
 
-        self.visit(condition);
+        let (first_order, second_order) = match condition {
+            // a single relation needs only one jump if the branches are placed in the right order
+            ast::Condition::Relation { op, .. } => {
+                self.visit(condition);
 
-        let (first_order, second_order) = match condition.op {
-            RelOp::NEQ | RelOp::LEQ | RelOp::GEQ => (Order::First, Order::Second),
-            RelOp::EQ | RelOp::LT | RelOp::GT => (Order::Second, Order::First),
-        };
+                let cond_jump = match op {
+                    RelOp::EQ | RelOp::NEQ => Instruction::JZero {
+                        label: negative_label,
+                    },
+                    RelOp::GT | RelOp::LEQ => Instruction::JPositive {
+                        label: negative_label,
+                    },
+                    RelOp::LT | RelOp::GEQ => Instruction::JNegative {
+                        label: negative_label,
+                    },
+                };
+                self.emit(cond_jump);
 
-        let cond_jump = match condition.op {
-            RelOp::EQ | RelOp::NEQ => Instruction::JZero {
-                label: negative_label,
-            },
-            RelOp::GT | RelOp::LEQ => Instruction::JPositive {
-                label: negative_label,
-            },
-            RelOp::LT | RelOp::GEQ => Instruction::JNegative {
-                label: negative_label,
-            },
+                match op {
+                    RelOp::NEQ | RelOp::LEQ | RelOp::GEQ => (Order::First, Order::Second),
+                    RelOp::EQ | RelOp::LT | RelOp::GT => (Order::Second, Order::First),
+                }
+            }
+            _ => {
+                self.emit_condition_jump(condition, false, negative_label);
+                (Order::First, Order::Second)
+            }
         };
 
-        self.emit(cond_jump);
         emit_body(self, first_order);
         self.emit(Instruction::Jump { label: endif_label });
         self.emit(Instruction::Label {
@@ -49,14 +62,284 @@ impl CodeGenerator {
         self.emit(Instruction::Label { label: endif_label });
     }
 
-    fn emit_do<F: FnMut(&mut Self)>(&mut self, condition: &ast::Condition, mut emit_body: F) {
+    /// Jumps to `target` if the condition evaluates to `jump_if`. Operands of
+    /// AND and OR are only evaluated until the result is known.
+    fn emit_condition_jump(&mut self, condition: &ast::Condition, jump_if: bool, target: Label) {
+        match condition {
+            ast::Condition::Relation { op, .. } => {
+                self.visit(condition);
+
+                // signs of `left - right` for which the relation holds
+                let (negative, zero, positive) = match op {
+                    RelOp::EQ => (false, true, false),
+                    RelOp::NEQ => (true, false, true),
+                    RelOp::LT => (true, false, false),
+                    RelOp::LEQ => (true, true, false),
+                    RelOp::GT => (false, false, true),
+                    RelOp::GEQ => (false, true, true),
+                };
+
+                if negative == jump_if {
+                    self.emit(Instruction::JNegative { label: target });
+                }
+                if zero == jump_if {
+                    self.emit(Instruction::JZero { label: target });
+                }
+                if positive == jump_if {
+                    self.emit(Instruction::JPositive { label: target });
+                }
+            }
+            ast::Condition::Not { condition, .. } => {
+                self.emit_condition_jump(condition, !jump_if, target)
+            }
+            ast::Condition::Compound {
+                left, op, right, ..
+            } => {
+                // the value that decides the result of the operation on its own
+                let decisive = *op == LogicOp::Or;
+
+                if jump_if == decisive {
+                    self.emit_condition_jump(left, jump_if, target);
+                    self.emit_condition_jump(right, jump_if, target);
+                } else {
+                    let skip_label = self.new_label();
+                    self.emit_condition_jump(left, decisive, skip_label);
+                    self.emit_condition_jump(right, jump_if, target);
+                    self.emit(Instruction::Label { label: skip_label });
+                }
+            }
+        }
+    }
+
+    /// Returns the access to an operand of an operation. Values are used
+    /// directly, nested operations are computed into a temporary first.
+    fn emit_operand(&mut self, operand: &ast::Expression) -> Access {
+        match operand {
+            ast::Expression::Simple { value, .. } => {
+                self.visit(value);
+                self.pop_access()
+            }
+            ast::Expression::Compound { .. } => {
+                self.visit(operand);
+                let access = Access::Variable(self.acquire_temporary());
+                self.emit(Instruction::PreStore {
+                    access: access.clone(),
+                });
+                self.emit(Instruction::Store {
+                    access: access.clone(),
+                });
+                access
+            }
+        }
+    }
+
+    /// Returns the variable holding the value of `index`, dereferenced if it's a reference.
+    fn index_variable(&mut self, index: &str) -> VariableIndex {
+        let index_var = self.find_variable_by_name(index).unwrap();
+        match index_var.variable() {
+            Variable::Reference { .. } => {
+                let reference = index_var.id();
+                self.dereference(reference)
+            }
+            _ => index_var.id(),
+        }
+    }
+
+    /// Returns the access to an element of a multi-dimensional array.
+    ///
+    /// The linear index `(i1 * n2 + i2) * n3 + i3 ...` is computed into a temporary,
+    /// which stays acquired until the caller releases it. Constant parts are folded,
+    /// and multiplications by sizes that are powers of two become shifts.
+    /// With bounds checks, every index is checked against its own dimension first.
+    fn emit_linear_index(&mut self, name: &str, indices: &[ast::Expression]) -> Access {
+        enum Partial {
+            Constant(i64),
+            Variable(VariableIndex),
+        }
+
+        let array = self.find_variable_by_name(name).unwrap();
+        let array_index = array.id();
+        let dimensions = match array.variable() {
+            Variable::Array { dimensions, .. } => dimensions.clone(),
+            _ => panic!("{} is not a multi-dimensional array", name),
+        };
+
+        let mut temporary = None;
+        let mut linear = Partial::Constant(0);
+        for (index, &(start, end)) in indices.iter().zip(&dimensions) {
+            let size = end - start + 1;
+            let index = match index {
+                ast::Expression::Simple {
+                    value: ast::Value::Num { value, .. },
+                    ..
+                } => Partial::Constant(*value),
+                ast::Expression::Simple {
+                    value: ast::Value::Identifier(ast::Identifier::VarAccess { name, .. }),
+                    ..
+                } => Partial::Variable(self.index_variable(name)),
+                index => Partial::Variable(self.emit_index_expression(index)),
+            };
+            // constant indices are checked by the verifier
+            if let Partial::Variable(index) = index {
+                if self.options.bounds_checks {
+                    let (start, end) = (self.constant_access(start), self.constant_access(end));
+                    self.emit(Instruction::CheckBounds {
+                        index: Access::Variable(index),
+                        start,
+                        end,
+                    });
+                }
+            }
+
+            linear = match (linear, index) {
+                (Partial::Constant(linear), Partial::Constant(index)) => {
+                    Partial::Constant(linear * size + index)
+                }
+                (Partial::Constant(0), index) => index,
+                (Partial::Constant(linear), Partial::Variable(index)) => {
+                    let offset = self.constant_access(linear * size);
+                    self.emit(Instruction::Operation {
+                        left: Access::Variable(index),
+                        op: OperationType::Plus,
+                        right: offset,
+                    });
+                    Partial::Variable(self.emit_store_index(&mut temporary))
+                }
+                (Partial::Variable(linear), index) => {
+                    let scaled = if size == 1 {
+                        linear
+                    } else {
+                        self.emit_scaling(Access::Variable(linear), size);
+                        self.emit_store_index(&mut temporary)
+                    };
+                    let index = match index {
+                        Partial::Constant(0) => None,
+                        Partial::Constant(index) => Some(self.constant_access(index)),
+                        Partial::Variable(index) => Some(Access::Variable(index)),
+                    };
+                    match index {
+                        Some(index) => {
+                            self.emit(Instruction::Operation {
+                                left: Access::Variable(scaled),
+                                op: OperationType::Plus,
+                                right: index,
+                            });
+                            Partial::Variable(self.emit_store_index(&mut temporary))
+                        }
+                        None => Partial::Variable(scaled),
+                    }
+                }
+            };
+        }
+
+        match linear {
+            Partial::Constant(linear) => Access::ArrayStatic(array_index, Constant::from(linear)),
+            Partial::Variable(linear) => {
+                Access::ArrayDynamic(array_index, Box::new(Access::Variable(linear)))
+            }
+        }
+    }
+
+    /// Computes an index into a temporary, which stays acquired until the caller releases it.
+    fn emit_index_expression(&mut self, index: &ast::Expression) -> VariableIndex {
+        self.visit(index);
+        let temporary = self.acquire_temporary();
+        self.emit(Instruction::PreStore {
+            access: Access::Variable(temporary),
+        });
+        self.emit(Instruction::Store {
+            access: Access::Variable(temporary),
+        });
+        temporary
+    }
+
+    /// Multiplies `value` by a dimension size, leaving the result in the accumulator.
+    fn emit_scaling(&mut self, value: Access, size: i64) {
+        let (op, right) = if size.count_ones() == 1 {
+            (OperationType::Shift, i64::from(size.trailing_zeros()))
+        } else {
+            (OperationType::Times, size)
+        };
+        let right = self.constant_access(right);
+        self.emit(Instruction::Operation {
+            left: value,
+            op,
+            right,
+        });
+    }
+
+    /// Stores the accumulator into the temporary holding a linear index,
+    /// acquiring it on first use.
+    fn emit_store_index(&mut self, temporary: &mut Option<VariableIndex>) -> VariableIndex {
+        let temporary = match *temporary {
+            Some(temporary) => temporary,
+            None => *temporary.insert(self.acquire_temporary()),
+        };
+        self.emit(Instruction::PreStore {
+            access: Access::Variable(temporary),
+        });
+        self.emit(Instruction::Store {
+            access: Access::Variable(temporary),
+        });
+        temporary
+    }
+
+    /// Emits a check of an index into an array parameter against the bounds
+    /// of the array passed to it, if bounds checks are enabled.
+    fn emit_parameter_bounds_check(&mut self, array: VariableIndex, index: &Access) {
+        if let Some(&(start, end)) = self.parameter_bounds.get(&array) {
+            self.emit(Instruction::CheckBounds {
+                index: index.clone(),
+                start: Access::Variable(start),
+                end: Access::Variable(end),
+            });
+        }
+    }
+
+    /// Stores the value read from `access` into `variable`.
+    fn emit_copy(&mut self, access: Access, variable: VariableIndex) {
+        self.emit(Instruction::PreStore {
+            access: Access::Variable(variable),
+        });
+        self.emit(Instruction::Load { access });
+        self.emit(Instruction::Store {
+            access: Access::Variable(variable),
+        });
+    }
+
+    fn constant_access(&mut self, value: i64) -> Access {
+        self.context.register_constant(Constant::from(value));
+        Access::Constant(Constant::from(value))
+    }
+
+    fn emit_do<F: FnMut(&mut Self)>(&mut self, condition: &ast::Condition, emit_body: F) {
+        let exit_label = self.new_label();
+        self.emit_do_with_exit(condition, exit_label, emit_body);
+        self.emit(Instruction::Label { label: exit_label });
+    }
+
+    /// Emits a DO loop that BREAK leaves by jumping to `exit_label`,
+    /// which the caller places after the loop.
+    fn emit_do_with_exit<F: FnMut(&mut Self)>(
+        &mut self,
+        condition: &ast::Condition,
+        exit_label: Label,
+        mut emit_body: F,
+    ) {
         // do { commands } while(condition)
         // is the same as:
-        // start: { commands } if(condition) jump start;
+        // start: { commands } next: if(condition) jump start;
         let start_label = self.new_label();
+        let next_label = self.new_label();
 
         self.emit(Instruction::Label { label: start_label });
+        self.loops.push(Loop {
+            next: next_label,
+            exit: exit_label,
+        });
         emit_body(self);
+        self.loops.pop();
+        self.emit(Instruction::Label { label: next_label });
         let emit_jump = |gen: &mut Self, order| {
             if order == Order::First {
                 gen.emit(Instruction::Jump { label: start_label });
@@ -69,25 +352,84 @@ impl CodeGenerator {
         // while(condition) { commands }
         // is the same as:
         // if(condition) { do { commands } while(condition) }
+        let exit_label = self.new_label();
         let emit_if = |gen: &mut Self, order| {
             if order == Order::First {
-                gen.emit_do(condition, &mut emit_body);
+                gen.emit_do_with_exit(condition, exit_label, &mut emit_body);
             }
         };
         self.emit_if_else(condition, emit_if);
+        self.emit(Instruction::Label { label: exit_label });
     }
 }
 
 impl Visitor for CodeGenerator {
     type Result = ();
 
+    fn visit_program(&mut self, program: &ast::Program) -> Self::Result {
+        // procedures are placed before the main program, which jumps over them
+        if !program.procedures.is_empty() {
+            let main_label = self.new_label();
+            self.emit(Instruction::Jump { label: main_label });
+            self.visit_collection(&program.procedures);
+            self.emit(Instruction::Label { label: main_label });
+        }
+
+        if let Some(declarations) = &program.declarations {
+            self.visit_declarations(declarations);
+        }
+        self.visit_commands(&program.commands);
+    }
+
+    fn visit_procedure(&mut self, procedure: &ast::Procedure) -> Self::Result {
+        let label = self.new_label();
+        let return_address = self.context.add_variable(Variable::Unit {
+            name: format!("{}$return", procedure.name),
+        });
+        let parameters = procedure
+            .parameters
+            .iter()
+            .map(|parameter| {
+                let reference = self.context.add_variable(Variable::Reference {
+                    name: parameter.name.clone(),
+                    array: parameter.array,
+                });
+                if parameter.array && self.options.bounds_checks {
+                    let mut bound = |suffix| {
+                        self.context.add_variable(Variable::Unit {
+                            name: format!("{}${}", parameter.name, suffix),
+                        })
+                    };
+                    let bounds = (bound("start"), bound("end"));
+                    self.parameter_bounds.insert(reference, bounds);
+                }
+                reference
+            })
+            .collect();
+        if let Some(declarations) = &procedure.declarations {
+            self.visit_declarations(declarations);
+        }
+
+        self.procedures.push(Procedure {
+            name: procedure.name.clone(),
+            label,
+            return_address,
+            parameters,
+        });
+
+        self.emit(Instruction::Label { label });
+        self.visit_commands(&procedure.commands);
+        self.emit(Instruction::Return { return_address });
+    }
+
     fn visit_declaration(&mut self, declaration: &ast::Declaration) -> Self::Result {
         let var = match declaration {
-            ast::Declaration::Var { name } => Variable::Unit { name: name.clone() },
-            ast::Declaration::Array { name, start, end } => Variable::Array {
+            ast::Declaration::Var { name, .. } => Variable::Unit { name: name.clone() },
+            ast::Declaration::Array {
+                name, dimensions, ..
+            } => Variable::Array {
                 name: name.clone(),
-                start: *start,
-                end: *end,
+                dimensions: dimensions.clone(),
             },
         };
 
@@ -142,6 +484,7 @@ impl Visitor for CodeGenerator {
         ascending: bool,
         from: &ast::Value,
         to: &ast::Value,
+        step: Option<&ast::Value>,
         commands: &ast::Commands,
     ) -> Self::Result {
         let counter_var = self.add_local(Variable::Unit {
@@ -150,6 +493,7 @@ impl Visitor for CodeGenerator {
         let tmp = self.add_local(Variable::Unit {
             name: counter.to_owned() + "$to",
         });
+        let temporaries = self.used_temporaries;
 
         self.emit(Instruction::PreStore {
             access: Access::Variable(counter_var),
@@ -179,10 +523,76 @@ impl Visitor for CodeGenerator {
         let tmp_name = self.context.get_variable(&tmp).variable().name().to_owned();
         debug_assert_eq!(tmp_name.as_str(), (counter_name.clone() + "$to").as_str());
 
+        // constant steps are added directly, so steps of 1 become INC or DEC;
+        // the direction comes from TO or DOWNTO, so only the absolute value is used
+        let mut skip_label = None;
+        let (step, step_var) = match step {
+            None => (
+                ast::Value::Num {
+                    value: 1,
+                    span: ast::Span::default(),
+                },
+                None,
+            ),
+            Some(&ast::Value::Num { value, span }) if value.checked_abs().is_some() => (
+                ast::Value::Num {
+                    value: value.abs(),
+                    span,
+                },
+                None,
+            ),
+            Some(step) => {
+                // evaluated once, like the bounds
+                let step_var = self.add_local(Variable::Unit {
+                    name: counter.to_owned() + "$step",
+                });
+                self.emit(Instruction::PreStore {
+                    access: Access::Variable(step_var),
+                });
+                self.visit(step);
+                self.emit_load_visited();
+                self.emit(Instruction::Store {
+                    access: Access::Variable(step_var),
+                });
+
+                // a step of 0 would never move the counter, so the loop is skipped,
+                // as if its range was empty
+                let label = self.new_label();
+                self.emit(Instruction::JZero { label });
+                skip_label = Some(label);
+
+                // a negative step is replaced with its negation, found in the accumulator
+                let positive_label = self.new_label();
+                let zero = self.constant_access(0);
+                self.emit(Instruction::Operation {
+                    left: zero,
+                    op: OperationType::Minus,
+                    right: Access::Variable(step_var),
+                });
+                self.emit(Instruction::JNegative {
+                    label: positive_label,
+                });
+                self.emit(Instruction::Store {
+                    access: Access::Variable(step_var),
+                });
+                self.emit(Instruction::Label {
+                    label: positive_label,
+                });
+
+                let step = ast::Value::Identifier(ast::Identifier::VarAccess {
+                    name: counter_name.clone() + "$step",
+                    span: ast::Span::default(),
+                });
+                (step, Some(step_var))
+            }
+        };
+        self.used_temporaries = temporaries;
+
         self.emit_while(
-            &ast::Condition {
+            &ast::Condition::Relation {
                 left: ast::Value::Identifier(ast::Identifier::VarAccess {
                     name: counter_name.clone(),
+                    span: ast::Span::default(),
                 }),
                 op: if ascending {
                     ast::RelOp::LEQ
@@ -191,44 +601,86 @@ impl Visitor for CodeGenerator {
                 },
                 right: ast::Value::Identifier(ast::Identifier::VarAccess {
                     name: tmp_name.clone(),
+                    span: ast::Span::default(),
                 }),
+                span: ast::Span::default(),
             },
             |gen| {
+                // CONTINUE has to skip to the increment rather than the condition
+                let next_label = gen.new_label();
+                let exit_label = gen.loops.last().expect("not in a loop").exit;
+                gen.loops.push(Loop {
+                    next: next_label,
+                    exit: exit_label,
+                });
                 gen.visit_commands(commands);
+                gen.loops.pop();
+                gen.emit(Instruction::Label { label: next_label });
                 gen.visit_assign_command(
                     &ast::Identifier::VarAccess {
                         name: counter_name.clone(),
+                        span: ast::Span::default(),
                     },
-                    &ast::Expression::Compound {
-                        left: ast::Value::Identifier(ast::Identifier::VarAccess {
+                    &ast::Expression::compound(
+                        ast::Value::Identifier(ast::Identifier::VarAccess {
                             name: counter_name.clone(),
-                        }),
-                        op: if ascending {
+                            span: ast::Span::default(),
+                        })
+                        .into(),
+                        if ascending {
                             ast::ExprOp::Plus
                         } else {
                             ast::ExprOp::Minus
                         },
-                        right: ast::Value::Num(1),
-                    },
+                        step.clone().into(),
+                    ),
                 );
             },
         );
 
+        if let Some(label) = skip_label {
+            self.emit(Instruction::Label { label });
+        }
+        if let Some(step_var) = step_var {
+            self.pop_local(step_var);
+        }
         self.pop_local(tmp);
         self.pop_local(counter_var);
     }
 
-    fn visit_read_command(&mut self, target: &ast::Identifier) -> Self::Result {
-        self.visit(target);
-        self.emit_pre_store_visited();
-        self.emit(Instruction::Get);
-        self.emit_store_visited();
+    fn visit_break_command(&mut self) -> Self::Result {
+        let label = self.loops.last().expect("BREAK outside of a loop").exit;
+        self.emit(Instruction::Jump { label });
     }
 
-    fn visit_write_command(&mut self, value: &ast::Value) -> Self::Result {
-        self.visit(value);
-        self.emit_load_visited();
-        self.emit(Instruction::Put);
+    fn visit_continue_command(&mut self) -> Self::Result {
+        let label = self.loops.last().expect("CONTINUE outside of a loop").next;
+        self.emit(Instruction::Jump { label });
+    }
+
+    fn visit_read_command(&mut self, targets: &[ast::Identifier]) -> Self::Result {
+        for target in targets {
+            let temporaries = self.used_temporaries;
+
+            self.visit(target);
+            self.emit_pre_store_visited();
+            self.emit(Instruction::Get);
+            self.emit_store_visited();
+
+            self.used_temporaries = temporaries;
+        }
+    }
+
+    fn visit_write_command(&mut self, values: &[ast::Value]) -> Self::Result {
+        for value in values {
+            let temporaries = self.used_temporaries;
+
+            self.visit(value);
+            self.emit_load_visited();
+            self.emit(Instruction::Put);
+
+            self.used_temporaries = temporaries;
+        }
     }
 
     fn visit_assign_command(
@@ -236,10 +688,69 @@ impl Visitor for CodeGenerator {
         target: &ast::Identifier,
         expr: &ast::Expression,
     ) -> Self::Result {
+        // the index of an array element is computed before the expression and kept until the store
+        let temporaries = self.used_temporaries;
+
         self.visit(target);
         self.emit_pre_store_visited();
         self.visit(expr);
         self.emit_store_visited();
+
+        self.used_temporaries = temporaries;
+    }
+
+    fn visit_call_command(&mut self, name: &str, arguments: &[ast::Argument]) -> Self::Result {
+        let procedure = self
+            .procedures
+            .iter()
+            .find(|procedure| procedure.name == name)
+            .cloned()
+            .expect("call to an undeclared procedure");
+
+        // references are passed on as they are, unit variables by their address
+        // and arrays by their base pointer
+        for (argument, &parameter) in arguments.iter().zip(&procedure.parameters) {
+            let variable = self.find_variable_by_name(&argument.name).unwrap();
+            let id = variable.id();
+            let (access, range) = match variable.variable() {
+                Variable::Unit { .. } => (Access::Address(id), None),
+                array @ Variable::Array { .. } => {
+                    (Access::Variable(id), Some(array.linear_range()))
+                }
+                Variable::Reference { .. } => (Access::Variable(id), None),
+            };
+
+            self.emit_copy(access, parameter);
+
+            // arrays passed on by a procedure have their bounds passed on as well
+            if let Some(&(start, end)) = self.parameter_bounds.get(&parameter) {
+                let (start_access, end_access) = match range {
+                    Some((first, last)) => {
+                        (self.constant_access(first), self.constant_access(last))
+                    }
+                    None => {
+                        let (first, last) = self.parameter_bounds[&id];
+                        (Access::Variable(first), Access::Variable(last))
+                    }
+                };
+                self.emit_copy(start_access, start);
+                self.emit_copy(end_access, end);
+            }
+        }
+
+        let return_label = self.new_label();
+        self.emit(Instruction::Call {
+            label: procedure.label,
+            return_address: procedure.return_address,
+            return_label,
+        });
+        self.emit(Instruction::Label {
+            label: return_label,
+        });
+    }
+
+    fn visit_include_command(&mut self, _path: &str) -> Self::Result {
+        unreachable!("includes are resolved by the parser")
     }
 
     //
@@ -258,51 +769,123 @@ impl Visitor for CodeGenerator {
 
     fn visit_compound_expression(
         &mut self,
-        left: &ast::Value,
+        left: &ast::Expression,
         op: &ast::ExprOp,
+        right: &ast::Expression,
+    ) -> Self::Result {
+        let temporaries = self.used_temporaries;
+
+        let left = self.emit_operand(left);
+        let right = self.emit_operand(right);
+        self.emit(Instruction::Operation {
+            left,
+            op: (*op).into(),
+            right,
+        });
+
+        self.used_temporaries = temporaries;
+    }
+
+    // fn visit_expression(&mut self, expr: &ast::Expression) -> Self::Result {
+    //     unimplemented!()
+    // }
+
+    fn visit_relation_condition(
+        &mut self,
+        left: &ast::Value,
+        _op: &RelOp,
         right: &ast::Value,
     ) -> Self::Result {
+        let temporaries = self.used_temporaries;
+
         self.visit(left);
         let left = self.pop_access();
         self.visit(right);
         let right = self.pop_access();
         self.emit(Instruction::Operation {
             left,
-            op: (*op).into(),
+            op: ExprOp::Minus.into(),
             right,
         });
+
+        self.used_temporaries = temporaries;
     }
 
-    // fn visit_expression(&mut self, expr: &ast::Expression) -> Self::Result {
-    //     unimplemented!()
-    // }
+    fn visit_not_condition(&mut self, _condition: &ast::Condition) -> Self::Result {
+        unreachable!("logical conditions are lowered to jumps by emit_condition_jump")
+    }
 
-    fn visit_condition(&mut self, condition: &ast::Condition) -> Self::Result {
-        self.visit_compound_expression(&condition.left, &ExprOp::Minus, &condition.right);
+    fn visit_compound_condition(
+        &mut self,
+        _left: &ast::Condition,
+        _op: &LogicOp,
+        _right: &ast::Condition,
+    ) -> Self::Result {
+        unreachable!("logical conditions are lowered to jumps by emit_condition_jump")
     }
 
     fn visit_num_value(&mut self, num: i64) -> Self::Result {
-        self.context.register_constant(Constant(num));
-        self.push_access(Access::Constant(Constant(num)));
+        self.context.register_constant(Constant::from(num));
+        self.push_access(Access::Constant(Constant::from(num)));
+    }
+
+    #[cfg(feature = "bignum")]
+    fn visit_big_num_value(&mut self, num: &BigInt) -> Self::Result {
+        self.context.register_constant(Constant(num.clone()));
+        self.push_access(Access::Constant(Constant(num.clone())));
     }
 
     fn visit_identifier(&mut self, identifier: &ast::Identifier) -> Self::Result {
         use ast::Identifier::*;
         match identifier {
-            ArrAccess { name, index } => {
+            ArrAccess { name, index, .. } => {
                 let name_index = self.find_variable_by_name(name).unwrap().id();
-                let index_index = self.find_variable_by_name(index).unwrap().id();
+                let index_index = self.index_variable(index);
+                let index = Access::Variable(index_index);
 
-                self.push_access(Access::ArrayDynamic(name_index, index_index))
+                self.emit_parameter_bounds_check(name_index, &index);
+                self.push_access(Access::ArrayDynamic(name_index, Box::new(index)))
             }
-            ArrConstAccess { name, index } => {
+            ArrExprAccess { name, index, .. } => {
                 let name_index = self.find_variable_by_name(name).unwrap().id();
-                self.context.register_constant(Constant(*index));
-                self.push_access(Access::ArrayStatic(name_index, Constant(*index)));
+                let index = match &**index {
+                    // nested accesses are read when the address of the element is computed
+                    ast::Expression::Simple { value, .. } => {
+                        self.visit(value);
+                        self.pop_access()
+                    }
+                    index => Access::Variable(self.emit_index_expression(index)),
+                };
+
+                self.emit_parameter_bounds_check(name_index, &index);
+                self.push_access(Access::ArrayDynamic(name_index, Box::new(index)))
             }
-            VarAccess { name } => {
-                let name_index = self.find_variable_by_name(name).unwrap().id();
-                self.push_access(Access::Variable(name_index));
+            ArrMultiAccess { name, indices, .. } => {
+                let access = self.emit_linear_index(name, indices);
+                self.push_access(access);
+            }
+            ArrConstAccess { name, index, .. } => {
+                let name_var = self.find_variable_by_name(name).unwrap();
+                let name_index = name_var.id();
+                let is_reference = matches!(name_var.variable(), Variable::Reference { .. });
+                let index_constant = self.context.register_constant(Constant::from(*index));
+                // the bounds of an array parameter aren't known, so its base pointer is used
+                let access = if is_reference {
+                    let index = Access::Variable(index_constant);
+                    self.emit_parameter_bounds_check(name_index, &index);
+                    Access::ArrayDynamic(name_index, Box::new(index))
+                } else {
+                    Access::ArrayStatic(name_index, Constant::from(*index))
+                };
+                self.push_access(access);
+            }
+            VarAccess { name, .. } => {
+                let name_var = self.find_variable_by_name(name).unwrap();
+                let access = match name_var.variable() {
+                    Variable::Reference { .. } => Access::Indirect(name_var.id()),
+                    _ => Access::Variable(name_var.id()),
+                };
+                self.push_access(access);
             }
         }
     }
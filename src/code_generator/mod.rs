@@ -1,2 +1,3 @@
 pub mod intermediate;
+pub mod peephole;
 pub mod translator;
@@ -0,0 +1,310 @@
+//! Local cleanup of the translated program, removing instructions that the
+//! translation of separate intermediate instructions leaves behind.
+
+use std::collections::BTreeSet;
+use virtual_machine::instruction::Instruction;
+
+/// Marks the instructions that can be removed,
+/// given the positions jumped to from anywhere in the program.
+type Rule = fn(&[Instruction], &BTreeSet<usize>) -> Vec<bool>;
+
+const RULES: &[Rule] = &[
+    remove_unreachable,
+    remove_jumps_to_next,
+    remove_inc_dec_pairs,
+    remove_stores_of_loaded,
+];
+
+fn target(instruction: &Instruction) -> Option<u64> {
+    match *instruction {
+        Instruction::Jump(target)
+        | Instruction::Jpos(target)
+        | Instruction::Jzero(target)
+        | Instruction::Jneg(target) => Some(target),
+        _ => None,
+    }
+}
+
+fn retarget(instruction: Instruction, target: u64) -> Instruction {
+    match instruction {
+        Instruction::Jump(_) => Instruction::Jump(target),
+        Instruction::Jpos(_) => Instruction::Jpos(target),
+        Instruction::Jzero(_) => Instruction::Jzero(target),
+        Instruction::Jneg(_) => Instruction::Jneg(target),
+        _ => panic!("{:?} is not a jump", instruction),
+    }
+}
+
+fn jump_targets(instructions: &[Instruction]) -> BTreeSet<usize> {
+    instructions
+        .iter()
+        .filter_map(target)
+        .map(|target| target as usize)
+        .collect()
+}
+
+/// Removes the marked instructions. Jumps to a removed instruction
+/// are moved to the first instruction after it that is kept.
+fn remove(instructions: Vec<Instruction>, removed: &[bool]) -> Vec<Instruction> {
+    // the new position of every instruction, followed by the end of the program
+    let mut positions = Vec::with_capacity(removed.len() + 1);
+    let mut kept = 0;
+    for &removed in removed {
+        positions.push(kept);
+        if !removed {
+            kept += 1;
+        }
+    }
+    positions.push(kept);
+
+    instructions
+        .into_iter()
+        .zip(removed)
+        .filter(|&(_, &removed)| !removed)
+        .map(|(instruction, _)| match target(&instruction) {
+            Some(target) => retarget(instruction, positions[target as usize]),
+            None => instruction,
+        })
+        .collect()
+}
+
+/// Makes jumps to a `JUMP` go straight to where it leads, and conditional jumps
+/// to a jump of the same kind, which is taken as well. Returns whether anything changed.
+fn thread_jumps(instructions: &mut [Instruction]) -> bool {
+    let mut changed = false;
+
+    for position in 0..instructions.len() {
+        let instruction = instructions[position];
+        let mut destination = match target(&instruction) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        // jumps that only jump to each other are left alone
+        let mut visited = BTreeSet::new();
+        let cycle = loop {
+            if !visited.insert(destination) {
+                break true;
+            }
+            let next = match instructions.get(destination as usize) {
+                Some(&next) => next,
+                None => break false,
+            };
+            let is_taken = matches!(next, Instruction::Jump(_))
+                || std::mem::discriminant(&next) == std::mem::discriminant(&instruction);
+            if !is_taken {
+                break false;
+            }
+            destination = target(&next).unwrap();
+        };
+
+        if !cycle && Some(destination) != target(&instruction) {
+            instructions[position] = retarget(instruction, destination);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// Code after a `JUMP` or `HALT` that no jump leads to is never executed.
+fn remove_unreachable(instructions: &[Instruction], targets: &BTreeSet<usize>) -> Vec<bool> {
+    let mut removed = vec![false; instructions.len()];
+    let mut reachable = true;
+
+    for (position, instruction) in instructions.iter().enumerate() {
+        reachable |= targets.contains(&position);
+        removed[position] = !reachable;
+        if let Instruction::Jump(_) | Instruction::Halt = instruction {
+            reachable = false;
+        }
+    }
+
+    removed
+}
+
+/// A jump to the next instruction gets there whether it's taken or not.
+fn remove_jumps_to_next(instructions: &[Instruction], _: &BTreeSet<usize>) -> Vec<bool> {
+    instructions
+        .iter()
+        .enumerate()
+        .map(|(position, instruction)| target(instruction) == Some(position as u64 + 1))
+        .collect()
+}
+
+/// `INC; DEC` and `DEC; INC` leave the accumulator as it was,
+/// unless something jumps in between.
+fn remove_inc_dec_pairs(instructions: &[Instruction], targets: &BTreeSet<usize>) -> Vec<bool> {
+    let mut removed = vec![false; instructions.len()];
+
+    let mut position = 0;
+    while position + 1 < instructions.len() {
+        let pair = (instructions[position], instructions[position + 1]);
+        let cancels = matches!(
+            pair,
+            (Instruction::Inc, Instruction::Dec) | (Instruction::Dec, Instruction::Inc)
+        );
+        if cancels && !targets.contains(&(position + 1)) {
+            removed[position] = true;
+            removed[position + 1] = true;
+            position += 2;
+        } else {
+            position += 1;
+        }
+    }
+
+    removed
+}
+
+/// `STORE x` right after `LOAD x` writes the value the cell already holds,
+/// unless something jumps to the store.
+fn remove_stores_of_loaded(instructions: &[Instruction], targets: &BTreeSet<usize>) -> Vec<bool> {
+    let mut removed = vec![false; instructions.len()];
+
+    for position in 1..instructions.len() {
+        if let (Instruction::Load(loaded), Instruction::Store(stored)) =
+            (instructions[position - 1], instructions[position])
+        {
+            removed[position] = loaded == stored && !targets.contains(&position);
+        }
+    }
+
+    removed
+}
+
+/// Applies the rules to the program until none of them changes it anymore.
+pub fn optimize(mut instructions: Vec<Instruction>) -> Vec<Instruction> {
+    loop {
+        let mut changed = thread_jumps(&mut instructions);
+
+        for rule in RULES {
+            let removed = rule(&instructions, &jump_targets(&instructions));
+            if removed.contains(&true) {
+                instructions = remove(instructions, &removed);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return instructions;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use virtual_machine::interpreter::{self, memval};
+    use Instruction::*;
+
+    /// Checks that the optimized program writes the same output for every input,
+    /// and returns it.
+    fn check(program: Vec<Instruction>, inputs: &[i64]) -> Vec<Instruction> {
+        let optimized = optimize(program.clone());
+
+        for &input in inputs {
+            let before = interpreter::run(program.clone(), vec![memval(input)]);
+            let after = interpreter::run(optimized.clone(), vec![memval(input)]);
+            let (cost_before, output_before) = before.expect("original program failed");
+            let (cost_after, output_after) = after.expect("optimized program failed");
+
+            assert_eq!(output_after, output_before);
+            assert!(cost_after <= cost_before);
+        }
+
+        optimized
+    }
+
+    #[test]
+    fn inc_dec_pairs() {
+        let optimized = check(vec![Get, Inc, Dec, Dec, Inc, Put, Halt], &[0, 5]);
+
+        assert_eq!(optimized, vec![Get, Put, Halt]);
+    }
+
+    #[test]
+    fn inc_dec_pairs_jumped_into() {
+        let program = vec![Get, Jzero(3), Inc, Dec, Put, Halt];
+        let optimized = check(program.clone(), &[0, 5]);
+
+        assert_eq!(optimized, program);
+    }
+
+    #[test]
+    fn jumps_to_next() {
+        let optimized = check(vec![Get, Jump(2), Jzero(3), Put, Halt], &[0, 5]);
+
+        assert_eq!(optimized, vec![Get, Put, Halt]);
+    }
+
+    #[test]
+    fn jump_threading() {
+        let program = vec![Get, Jzero(5), Put, Halt, Inc, Jump(7), Halt, Dec, Put, Halt];
+        let optimized = check(program, &[0, 5]);
+
+        assert!(optimized
+            .iter()
+            .all(|instruction| match target(instruction) {
+                Some(target) => !matches!(optimized[target as usize], Jump(_)),
+                None => true,
+            }));
+        assert!(!optimized.contains(&Jump(7)));
+    }
+
+    #[test]
+    fn jump_threading_conditional() {
+        let program = vec![Get, Jneg(3), Halt, Jneg(5), Halt, Put, Halt];
+        let optimized = check(program, &[-1, 5]);
+
+        assert_eq!(optimized, vec![Get, Jneg(3), Halt, Put, Halt]);
+    }
+
+    #[test]
+    fn jump_cycles() {
+        // an infinite loop can't be run, so it's only checked that optimizing it terminates
+        let program = vec![Jump(1), Jump(2), Jump(1)];
+
+        assert_eq!(optimize(program), vec![Jump(0)]);
+    }
+
+    #[test]
+    fn stores_of_loaded_values() {
+        let optimized = check(vec![Get, Store(1), Dec, Load(1), Store(1), Put, Halt], &[3]);
+
+        assert_eq!(optimized, vec![Get, Store(1), Dec, Load(1), Put, Halt]);
+    }
+
+    #[test]
+    fn stores_of_loaded_values_jumped_to() {
+        let program = vec![
+            Get,
+            Store(1),
+            Jzero(4),
+            Load(1),
+            Store(1),
+            Load(1),
+            Put,
+            Halt,
+        ];
+        let optimized = check(program.clone(), &[0, 3]);
+
+        assert_eq!(optimized, program);
+    }
+
+    #[test]
+    fn unreachable_code() {
+        let optimized = check(vec![Get, Jump(4), Put, Inc, Put, Halt, Put], &[2]);
+
+        assert_eq!(optimized, vec![Get, Put, Halt]);
+    }
+
+    #[test]
+    fn rules_combined() {
+        // removing the unreachable DEC leaves a jump to the next instruction,
+        // and removing the jump leaves an INC; DEC pair
+        let program = vec![Get, Inc, Jump(4), Dec, Dec, Put, Halt];
+        let optimized = check(program, &[0, 5]);
+
+        assert_eq!(optimized, vec![Get, Put, Halt]);
+    }
+}
@@ -1,5 +1,11 @@
-use crate::code_generator::intermediate::{Access, Constant, Context, Instruction, Label, UniqueVariable, Variable, VariableIndex, OperationType};
+use crate::code_generator::intermediate::{
+    Access, Constant, ConstantValue, Context, Instruction, Label, OperationType, UniqueVariable,
+    Variable, VariableIndex,
+};
 use ::virtual_machine::instruction::Instruction as VmInstruction;
+#[cfg(feature = "bignum")]
+use num_traits::Signed;
+use num_traits::Zero;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 
@@ -20,7 +26,7 @@ impl std::ops::AddAssign<u64> for MemoryLocation {
     }
 }
 
-type MemoryStorage = BTreeMap<VariableIndex, (MemoryLocation, Option<i64>)>;
+type MemoryStorage = BTreeMap<VariableIndex, (MemoryLocation, Option<Constant>)>;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct MemoryRange(MemoryLocation, MemoryLocation);
 
@@ -55,7 +61,7 @@ impl Memory {
         }
     }
 
-    fn add_variable(&mut self, index: VariableIndex, value: Option<i64>) -> MemoryLocation {
+    fn add_variable(&mut self, index: VariableIndex, value: Option<Constant>) -> MemoryLocation {
         let last = if let Some(MemoryRange(_, ref mut end)) = self.segments.variables {
             *end += 1;
             *end
@@ -78,14 +84,36 @@ impl Memory {
     fn get_location(&self, index: VariableIndex) -> MemoryLocation {
         self.storage[&index].0
     }
+
+    fn get_array_base(&self, index: VariableIndex) -> i64 {
+        self.storage[&index]
+            .1
+            .as_ref()
+            .and_then(Constant::as_i64)
+            .expect("unallocated array")
+    }
+}
+
+/// Returns whether `value` is negative and the binary digits of its magnitude,
+/// starting with the most significant one.
+fn binary_representation(value: &ConstantValue) -> (bool, Vec<bool>) {
+    #[cfg(feature = "bignum")]
+    let digits = value.abs().to_str_radix(2);
+    #[cfg(not(feature = "bignum"))]
+    let digits = format!("{:b}", value.unsigned_abs());
+
+    (
+        value.is_negative(),
+        digits.bytes().map(|d| d == b'1').collect(),
+    )
 }
 
 fn compare_variables(a: &UniqueVariable, b: &UniqueVariable) -> Ordering {
     match (a.variable(), b.variable()) {
         (v1 @ Variable::Array { .. }, v2 @ Variable::Array { .. }) => v1.size().cmp(&v2.size()),
-        (Variable::Unit { .. }, Variable::Unit { .. }) => Ordering::Equal,
-        (Variable::Array { .. }, Variable::Unit { .. }) => Ordering::Less,
-        (Variable::Unit { .. }, Variable::Array { .. }) => Ordering::Greater,
+        (Variable::Array { .. }, _) => Ordering::Less,
+        (_, Variable::Array { .. }) => Ordering::Greater,
+        _ => Ordering::Equal,
     }
 }
 
@@ -93,10 +121,30 @@ struct InstructionManager {
     target_instructions: Vec<VmInstruction>,
     label_positions: BTreeMap<Label, u64>,
     back_patches_list: BTreeMap<Label, Vec<usize>>,
+    /// A memory cell known to hold the value of the accumulator. Forgotten at labels,
+    /// since the accumulator can hold anything when jumped to.
+    accumulator: Option<MemoryLocation>,
 }
 
 #[allow(non_snake_case)]
 impl InstructionManager {
+    fn push(&mut self, instruction: VmInstruction) {
+        self.accumulator = match instruction {
+            VmInstruction::Load(operand) | VmInstruction::Store(operand) => {
+                Some(MemoryLocation(operand))
+            }
+            VmInstruction::Put
+            | VmInstruction::Jump(_)
+            | VmInstruction::Jpos(_)
+            | VmInstruction::Jneg(_)
+            | VmInstruction::Jzero(_)
+            | VmInstruction::Halt => self.accumulator,
+            // STOREI writes a cell that isn't known here, so nothing is assumed after it
+            _ => None,
+        };
+        self.target_instructions.push(instruction);
+    }
+
     fn fix_label(&mut self, instruction_ptr: usize, target_pointer: u64) {
         match self.target_instructions[instruction_ptr] {
             VmInstruction::Jump(ref mut target)
@@ -111,15 +159,16 @@ impl InstructionManager {
 
     fn translate_jump<F: FnOnce(u64) -> VmInstruction>(&mut self, label: &Label, create: F) {
         if let Some(pos) = self.label_positions.get(label) {
-            self.target_instructions.push(create(*pos));
+            self.push(create(*pos));
         } else {
             let pos = self.target_instructions.len();
             self.back_patches_list.entry(*label).or_default().push(pos);
-            self.target_instructions.push(create(u64::max_value()));
+            self.push(create(u64::max_value()));
         }
     }
 
     fn translate_label(&mut self, label: &Label) {
+        self.accumulator = None;
         let target = self.target_instructions.len() as u64;
         self.label_positions.insert(*label, target);
         if let Some(backlist) = self.back_patches_list.remove(&label) {
@@ -130,68 +179,88 @@ impl InstructionManager {
     }
 
     fn instr_Get(&mut self) {
-        self.target_instructions.push(VmInstruction::Get);
+        self.push(VmInstruction::Get);
     }
 
     fn instr_Put(&mut self) {
-        self.target_instructions.push(VmInstruction::Put);
+        self.push(VmInstruction::Put);
     }
 
+    /// Skipped if the accumulator already holds the value of the cell.
     fn instr_Load(&mut self, operand: MemoryLocation) {
-        self.target_instructions
-            .push(VmInstruction::Load(operand.0));
+        if self.accumulator == Some(operand) {
+            return;
+        }
+        self.push(VmInstruction::Load(operand.0));
     }
 
     fn instr_Loadi(&mut self, operand: MemoryLocation) {
-        self.target_instructions
-            .push(VmInstruction::Loadi(operand.0));
+        self.push(VmInstruction::Loadi(operand.0));
     }
 
     fn instr_Store(&mut self, operand: MemoryLocation) {
-        self.target_instructions
-            .push(VmInstruction::Store(operand.0));
+        self.push(VmInstruction::Store(operand.0));
     }
 
     fn instr_Storei(&mut self, operand: MemoryLocation) {
-        self.target_instructions
-            .push(VmInstruction::Storei(operand.0));
+        self.push(VmInstruction::Storei(operand.0));
     }
 
     fn instr_Add(&mut self, operand: MemoryLocation) {
-        self.target_instructions.push(VmInstruction::Add(operand.0));
+        self.push(VmInstruction::Add(operand.0));
     }
 
     fn instr_Sub(&mut self, operand: MemoryLocation) {
-        self.target_instructions.push(VmInstruction::Sub(operand.0));
+        self.push(VmInstruction::Sub(operand.0));
     }
 
     fn instr_Shift(&mut self, operand: MemoryLocation) {
-        self.target_instructions
-            .push(VmInstruction::Shift(operand.0));
+        self.push(VmInstruction::Shift(operand.0));
     }
 
     fn instr_Inc(&mut self) {
-        self.target_instructions.push(VmInstruction::Inc);
+        self.push(VmInstruction::Inc);
     }
 
     fn instr_Dec(&mut self) {
-        self.target_instructions.push(VmInstruction::Dec);
+        self.push(VmInstruction::Dec);
     }
 
     fn instr_Halt(&mut self) {
-        self.target_instructions.push(VmInstruction::Halt);
+        self.push(VmInstruction::Halt);
     }
 }
 
+/// Value written by programs translated with bounds checks
+/// right before they halt on an out-of-bounds array access.
+pub const OUT_OF_BOUNDS_SENTINEL: i64 = -999_999_999;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Check every array access with a computed index against the array's bounds.
+    /// An out-of-bounds access writes `OUT_OF_BOUNDS_SENTINEL` and halts the program.
+    /// Accesses to array parameters of procedures are only checked if the intermediate
+    /// code was generated with `intermediate::Options::bounds_checks`.
+    pub bounds_checks: bool,
+}
+
 pub struct Generator {
     context: Context,
     memory: Memory,
     instruction_manager: InstructionManager,
+    options: Options,
+    bounds_trap: Option<Label>,
+    call_sites: BTreeMap<VariableIndex, Vec<Label>>,
+    addresses: BTreeMap<VariableIndex, MemoryLocation>,
 }
 
 #[allow(dead_code)]
 impl Generator {
     pub fn new(context: Context) -> Self {
+        Self::with_options(context, Options::default())
+    }
+
+    pub fn with_options(context: Context, options: Options) -> Self {
         let cap = context.instructions().len() * 4;
         Generator {
             context,
@@ -200,7 +269,211 @@ impl Generator {
                 target_instructions: Vec::with_capacity(cap),
                 label_positions: BTreeMap::new(),
                 back_patches_list: BTreeMap::new(),
+                accumulator: None,
             },
+            options,
+            bounds_trap: None,
+            call_sites: BTreeMap::new(),
+            addresses: BTreeMap::new(),
+        }
+    }
+
+    /// Numbers the call sites of every procedure in the order they appear in.
+    fn register_calls(&mut self) {
+        for instruction in self.context.instructions() {
+            if let Instruction::Call {
+                return_address,
+                return_label,
+                ..
+            } = instruction
+            {
+                self.call_sites
+                    .entry(*return_address)
+                    .or_default()
+                    .push(*return_label);
+            }
+        }
+
+        let count = self.call_sites.values().map(Vec::len).max().unwrap_or(0);
+        for index in 0..count {
+            self.context.register_constant(Constant::from(index as i64));
+        }
+    }
+
+    /// Puts the address of every variable passed to a procedure in a cell of its own,
+    /// to be generated along with the constants.
+    fn allocate_addresses(&mut self) {
+        let variables: Vec<_> = self
+            .context
+            .instructions()
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Load {
+                    access: Access::Address(var),
+                } => Some(*var),
+                _ => None,
+            })
+            .collect();
+
+        for var in variables {
+            if self.addresses.contains_key(&var) {
+                continue;
+            }
+            let location = self.memory.get_location(var);
+            let cell = self.register_temp(&format!("addr{}", var.value()));
+            let address = Constant::from(location.0 as i64);
+            let cell_location = self.memory.add_variable(cell, Some(address));
+            self.addresses.insert(var, cell_location);
+        }
+    }
+
+    /// Jumps back to the call site whose index is stored in `return_address`.
+    ///
+    /// The VM can't jump to a computed address, so the index is counted
+    /// down, testing for every call site in turn.
+    fn translate_return(&mut self, return_address: VariableIndex) {
+        let call_sites = self
+            .call_sites
+            .get(&return_address)
+            .cloned()
+            .unwrap_or_default();
+        let (last, rest) = match call_sites.split_last() {
+            Some(split) => split,
+            // a procedure that is never called is never left either
+            None => return,
+        };
+
+        let return_loc = self.memory.get_location(return_address);
+        self.instruction_manager.instr_Load(return_loc);
+        for label in rest {
+            self.instruction_manager
+                .translate_jump(label, VmInstruction::Jzero);
+            self.instruction_manager.instr_Dec();
+        }
+        self.instruction_manager
+            .translate_jump(last, VmInstruction::Jump);
+    }
+
+    /// Returns the bounds of `arr`, or `None` for array parameters, whose bounds aren't known.
+    /// Indices into those are checked by the `Instruction::CheckBounds` in front of them.
+    ///
+    /// Indices of multi-dimensional arrays are linearized before they get here,
+    /// so only the array as a whole is guarded. Every dimension on its own is
+    /// checked by `Instruction::CheckBounds` as well.
+    fn get_array_bounds(&self, arr: VariableIndex) -> Option<(i64, i64)> {
+        match self.context.get_variable(&arr).variable() {
+            array @ Variable::Array { .. } => Some(array.linear_range()),
+            Variable::Reference { array: true, .. } => None,
+            _ => panic!("indexing a non-array variable"),
+        }
+    }
+
+    fn register_bounds_checks(&mut self) {
+        let arrays: Vec<_> = self
+            .context
+            .instructions()
+            .iter()
+            .flat_map(|instruction| match instruction {
+                Instruction::Load { access }
+                | Instruction::PreStore { access }
+                | Instruction::Store { access } => vec![access],
+                Instruction::Operation { left, right, .. } => vec![left, right],
+                _ => vec![],
+            })
+            .flat_map(Access::with_nested)
+            .filter_map(|access| match access {
+                Access::ArrayDynamic(arr, _) => self.get_array_bounds(*arr),
+                _ => None,
+            })
+            .collect();
+        let has_checks = self
+            .context
+            .instructions()
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::CheckBounds { .. }));
+
+        if arrays.is_empty() && !has_checks {
+            return;
+        }
+
+        for (start, end) in arrays {
+            self.context.register_constant(Constant::from(start));
+            self.context.register_constant(Constant::from(end));
+        }
+        self.context
+            .register_constant(Constant::from(OUT_OF_BOUNDS_SENTINEL));
+        self.bounds_trap = Some(self.context.new_label());
+    }
+
+    /// Returns where the value of an array index is, copying it into a temporary
+    /// if it isn't in memory already. Overwrites the accumulator.
+    fn translate_index_location(&mut self, index: &Access) -> MemoryLocation {
+        match index {
+            Access::Variable(ind) => self.memory.get_location(*ind),
+            Access::Constant(c) => self.get_location_of_constant(c),
+            _ => {
+                // a nested access is done with its temporary by the time it's loaded
+                self.translate_load_access(index);
+                let tmp = self.get_or_register_temp("array_index");
+                self.instruction_manager.instr_Store(tmp);
+                tmp
+            }
+        }
+    }
+
+    /// Jumps to the bounds trap unless the value at `ind_loc` is a valid index of `arr`.
+    /// Overwrites the accumulator.
+    fn translate_bounds_check(&mut self, arr: VariableIndex, ind_loc: MemoryLocation) {
+        let (start, end) = match (self.bounds_trap, self.get_array_bounds(arr)) {
+            (Some(_), Some(bounds)) => bounds,
+            _ => return,
+        };
+
+        let start_loc = self.get_constant_location(start);
+        let end_loc = self.get_constant_location(end);
+        self.translate_range_check(ind_loc, start_loc, end_loc);
+    }
+
+    /// Translates `Instruction::CheckBounds`, if bounds checks are enabled.
+    /// Overwrites the accumulator.
+    fn translate_check_bounds(&mut self, index: &Access, start: &Access, end: &Access) {
+        if self.bounds_trap.is_none() {
+            return;
+        }
+
+        let ind_loc = self.translate_index_location(index);
+        let start_loc = self.translate_index_location(start);
+        let end_loc = self.translate_index_location(end);
+        self.translate_range_check(ind_loc, start_loc, end_loc);
+    }
+
+    /// Jumps to the bounds trap unless the value at `ind_loc` is between
+    /// the values at `start_loc` and `end_loc`. Overwrites the accumulator.
+    fn translate_range_check(
+        &mut self,
+        ind_loc: MemoryLocation,
+        start_loc: MemoryLocation,
+        end_loc: MemoryLocation,
+    ) {
+        let trap = self.bounds_trap.expect("bounds checks are disabled");
+
+        self.instruction_manager.instr_Load(ind_loc);
+        self.instruction_manager.instr_Sub(start_loc);
+        self.instruction_manager
+            .translate_jump(&trap, VmInstruction::Jneg);
+        self.instruction_manager.instr_Load(ind_loc);
+        self.instruction_manager.instr_Sub(end_loc);
+        self.instruction_manager
+            .translate_jump(&trap, VmInstruction::Jpos);
+    }
+
+    fn translate_bounds_trap(&mut self) {
+        if let Some(trap) = self.bounds_trap {
+            self.instruction_manager.translate_label(&trap);
+            let sentinel = self.get_constant_location(OUT_OF_BOUNDS_SENTINEL);
+            self.instruction_manager.instr_Load(sentinel);
+            self.instruction_manager.instr_Put();
+            self.instruction_manager.instr_Halt();
         }
     }
 
@@ -215,7 +488,7 @@ impl Generator {
         let middle = variables
             .binary_search_by(|&a| match a.variable() {
                 Variable::Array { .. } => Ordering::Less,
-                Variable::Unit { .. } => Ordering::Greater,
+                Variable::Unit { .. } | Variable::Reference { .. } => Ordering::Greater,
             })
             .expect_err("incorrect ordering function");
 
@@ -230,11 +503,14 @@ impl Generator {
             let mut iter = variables.iter();
             let arrays = iter.by_ref().take(middle);
 
+            // the base is where the element with linear index 0 would be,
+            // so that adding a linear index to it gives the element's address
             let array_base_indexes = arrays.scan(1, |first, &arr| {
                 let start_index = *first;
                 *first += arr.variable().size();
-                if let Variable::Array { start, .. } = arr.variable() {
-                    Some((arr, start_index as i64 - *start))
+                if let Variable::Array { .. } = arr.variable() {
+                    let (start, _) = arr.variable().linear_range();
+                    Some((arr, Constant::from(start_index as i64 - start)))
                 } else {
                     panic!("incorrect variable order");
                 }
@@ -255,17 +531,21 @@ impl Generator {
     }
 
     fn get_constant_location(&self, value: i64) -> MemoryLocation {
-        let ind = self.context.get_constant_index(&Constant(value));
+        self.get_location_of_constant(&Constant::from(value))
+    }
+
+    fn get_location_of_constant(&self, constant: &Constant) -> MemoryLocation {
+        let ind = self.context.get_constant_index(constant);
         self.memory
             .storage
             .get(&ind)
-            .expect(format!("constant {} has not been generated", value).as_str())
+            .unwrap_or_else(|| panic!("constant {} has not been generated", constant.value()))
             .0
     }
 
-    fn generate_constant(&mut self, value: i64, location: MemoryLocation) {
-        let abs = value.abs() as u64;
-        if abs < 10 {
+    fn generate_constant(&mut self, constant: &Constant, location: MemoryLocation) {
+        let small = constant.as_i64().filter(|value| value.abs() < 10);
+        if let Some(value) = small {
             let (grow_instr, shrink_instr) = if value.is_positive() {
                 (VmInstruction::Inc, VmInstruction::Dec)
             } else {
@@ -273,46 +553,34 @@ impl Generator {
             };
 
             for _ in 0..value.abs() {
-                self.instruction_manager
-                    .target_instructions
-                    .push(grow_instr);
+                self.instruction_manager.push(grow_instr);
             }
             self.instruction_manager.instr_Store(location);
             for _ in 0..value.abs() {
-                self.instruction_manager
-                    .target_instructions
-                    .push(shrink_instr);
+                self.instruction_manager.push(shrink_instr);
             }
         } else {
-            let leading_zeros = abs.leading_zeros();
-            let mut abs = abs.reverse_bits();
+            let (negative, digits) = binary_representation(constant.value());
 
             let one_const = self.get_constant_location(1);
 
-            let grow_instr = if value.is_positive() {
-                VmInstruction::Inc
-            } else {
+            let grow_instr = if negative {
                 VmInstruction::Dec
+            } else {
+                VmInstruction::Inc
             };
 
-            while abs & 1 == 0 {
-                abs >>= 1;
-            }
+            let (last, rest) = digits.split_last().expect("no binary digits");
 
-            for _ in 0..(64 - leading_zeros - 1) {
-                if abs & 1 == 1 {
-                    self.instruction_manager
-                        .target_instructions
-                        .push(grow_instr);
+            for &digit in rest {
+                if digit {
+                    self.instruction_manager.push(grow_instr);
                 }
 
                 self.instruction_manager.instr_Shift(one_const);
-                abs >>= 1;
             }
-            if abs & 1 == 1 {
-                self.instruction_manager
-                    .target_instructions
-                    .push(grow_instr);
+            if *last {
+                self.instruction_manager.push(grow_instr);
             }
 
             self.instruction_manager.instr_Store(location);
@@ -327,18 +595,18 @@ impl Generator {
                 .storage
                 .get_mut(index)
                 .expect("constant not in memory");
-            value.1 = Some(constant.value());
+            value.1 = Some(constant.clone());
         }
 
         let mut to_generate: Vec<_> = self
             .memory
             .storage
-            .iter()
-            .filter_map(|(_, &(loc, val))| val.map(|val| (loc, val)))
+            .values()
+            .filter_map(|(loc, val)| val.clone().map(|val| (*loc, val)))
             .collect();
 
         to_generate.sort_unstable_by(|(_, val1), (_, val2)| {
-            let cmp = val1.abs().cmp(&val2.abs());
+            let cmp = val1.value().abs().cmp(&val2.value().abs());
             if cmp == Ordering::Equal {
                 val1.cmp(val2)
             } else {
@@ -353,7 +621,7 @@ impl Generator {
         self.instruction_manager.instr_Sub(MemoryLocation(0));
 
         for (loc, val) in to_generate {
-            self.generate_constant(val, loc);
+            self.generate_constant(&val, loc);
         }
     }
 
@@ -382,35 +650,36 @@ impl Generator {
 
     fn translate_optimized_multiplication(&mut self, left: &Access, right: &Access) -> bool {
         match (left, right) {
-            (Access::Constant(c), other)
-            | (other, Access::Constant(c)) => {
-                match c.value() {
-                    0 => {
+            (Access::Constant(c), other) | (other, Access::Constant(c)) => {
+                match c.as_i64() {
+                    Some(0) => {
                         self.instruction_manager.instr_Sub(MemoryLocation(0));
                         true
-                    },
-                    1 => {
+                    }
+                    Some(1) => {
                         self.translate_load_access(other);
                         true
-                    },
-                    -1 => {
+                    }
+                    Some(-1) => {
                         self.translate_load_access(other);
                         self.translate_neg_tmp();
                         true
                     }
-                    2 => {
+                    Some(2) => {
                         self.translate_load_access(other);
-                        self.instruction_manager.instr_Shift(self.get_constant_location(1));
+                        self.instruction_manager
+                            .instr_Shift(self.get_constant_location(1));
                         true
                     }
-                    -2 => {
+                    Some(-2) => {
                         self.translate_load_access(other);
-                        self.instruction_manager.instr_Shift(self.get_constant_location(1));
+                        self.instruction_manager
+                            .instr_Shift(self.get_constant_location(1));
                         self.translate_neg_tmp();
                         true
                     }
                     // n if (n.abs() as u64).is_power_of_two() => {}
-                    _ => false
+                    _ => false,
                 }
             }
             _ => false,
@@ -421,58 +690,56 @@ impl Generator {
         match (left, right) {
             (Access::Constant(c1), Access::Constant(c2)) => {
                 if div {
-                    let v1 = c1.value();
-                    let v2 = c2.value();
-
-                    match (v1.signum(), v2.signum()) {
-                        (0, _) | (_, 0) => {
-                            self.translate_load_zero();
-                            true
-                        },
-                        _ => false
+                    if c1.value().is_zero() || c2.value().is_zero() {
+                        self.translate_load_zero();
+                        true
+                    } else {
+                        false
                     }
                 } else {
                     false
                 }
             }
             (Access::Constant(c), _other) => {
-                match c.value() {
-                    0 => {
+                match c.as_i64() {
+                    Some(0) => {
                         self.instruction_manager.instr_Sub(MemoryLocation(0));
                         true
                     }
                     // n if (n.abs() as u64).is_power_of_two() => {}
-                    _ => false
+                    _ => false,
                 }
             }
             (other, Access::Constant(c)) => {
-                match c.value() {
-                    0 => {
+                match c.as_i64() {
+                    Some(0) => {
                         self.translate_load_zero();
                         true
-                    },
-                    1 => {
+                    }
+                    Some(1) => {
                         self.translate_load_access(other);
                         true
-                    },
-                    -1 => {
+                    }
+                    Some(-1) => {
                         self.translate_load_access(other);
                         self.translate_neg_tmp();
                         true
                     }
-                    2 => {
+                    Some(2) => {
                         if div {
                             self.translate_load_access(other);
-                            self.instruction_manager.instr_Shift(self.get_constant_location(-1));
+                            self.instruction_manager
+                                .instr_Shift(self.get_constant_location(-1));
                             true
                         } else {
                             false
                         }
                     }
-                    -2 => {
+                    Some(-2) => {
                         if div {
                             self.translate_load_access(other);
-                            self.instruction_manager.instr_Shift(self.get_constant_location(-1));
+                            self.instruction_manager
+                                .instr_Shift(self.get_constant_location(-1));
                             self.translate_neg_tmp();
                             true
                         } else {
@@ -480,14 +747,13 @@ impl Generator {
                         }
                     }
                     // n if (n.abs() as u64).is_power_of_two() => {}
-                    _ => false
+                    _ => false,
                 }
             }
             _ => false,
         }
     }
 
-
     fn translate_multiplication(&mut self, left: &Access, right: &Access) {
         /*
             if b == 0 { goto end }
@@ -876,7 +1142,7 @@ impl Generator {
     fn translate_load_access(&mut self, access: &Access) {
         match access {
             Access::Constant(c) => {
-                let loc = self.get_constant_location(c.value());
+                let loc = self.get_location_of_constant(c);
                 self.instruction_manager.instr_Load(loc);
             }
             Access::Variable(ind) => {
@@ -884,18 +1150,29 @@ impl Generator {
                 self.instruction_manager.instr_Load(loc);
             }
             Access::ArrayStatic(arr, c) => {
-                let real_arr_loc = self.memory.storage[arr].1.expect("unallocated array");
+                let real_arr_loc = self.memory.get_array_base(*arr);
+                let index = c.as_i64().expect("array index out of range");
                 self.instruction_manager
-                    .instr_Load(MemoryLocation((real_arr_loc + c.value()) as u64));
+                    .instr_Load(MemoryLocation((real_arr_loc + index) as u64));
             }
             Access::ArrayDynamic(arr, ind) => {
+                let ind_loc = self.translate_index_location(ind);
+                self.translate_bounds_check(*arr, ind_loc);
+
                 let arr_loc = self.memory.get_location(*arr);
-                let ind_loc = self.memory.get_location(*ind);
 
                 self.instruction_manager.instr_Load(arr_loc);
                 self.instruction_manager.instr_Add(ind_loc);
                 self.instruction_manager.instr_Loadi(MemoryLocation(0));
             }
+            Access::Indirect(reference) => {
+                let loc = self.memory.get_location(*reference);
+                self.instruction_manager.instr_Loadi(loc);
+            }
+            Access::Address(var) => {
+                let loc = self.addresses[var];
+                self.instruction_manager.instr_Load(loc);
+            }
         }
     }
 
@@ -907,16 +1184,19 @@ impl Generator {
                 self.instruction_manager.instr_Store(loc);
             }
             Access::ArrayStatic(arr, c) => {
-                let real_arr_loc = self.memory.storage[arr].1.expect("unallocated array");
+                let real_arr_loc = self.memory.get_array_base(*arr);
+                let index = c.as_i64().expect("array index out of range");
                 self.instruction_manager
-                    .instr_Store(MemoryLocation((real_arr_loc + c.value()) as u64));
+                    .instr_Store(MemoryLocation((real_arr_loc + index) as u64));
             }
             Access::ArrayDynamic(arr, ind) => {
                 let tmp1 = self.get_or_register_temp("store_tmp1");
                 self.instruction_manager.instr_Store(tmp1);
 
+                let ind_loc = self.translate_index_location(ind);
+                self.translate_bounds_check(*arr, ind_loc);
+
                 let arr_loc = self.memory.get_location(*arr);
-                let ind_loc = self.memory.get_location(*ind);
 
                 self.instruction_manager.instr_Load(arr_loc);
                 self.instruction_manager.instr_Add(ind_loc);
@@ -927,35 +1207,37 @@ impl Generator {
                 self.instruction_manager.instr_Load(tmp1);
                 self.instruction_manager.instr_Storei(tmp2);
             }
+            Access::Indirect(reference) => {
+                let loc = self.memory.get_location(*reference);
+                self.instruction_manager.instr_Storei(loc);
+            }
+            Access::Address(_) => panic!("can't store into an address"),
         }
     }
 
     fn translate_plus(&mut self, left: &Access, right: &Access) {
         let optimized = match (left, right) {
-            (Access::Constant(c), other)
-            | (other, Access::Constant(c)) => {
-                match c.value() {
-                    0 => {
-                        self.translate_load_access(other);
-                        true
-                    },
-                    n if n > 0 && n <= 10 => {
-                        self.translate_load_access(other);
-                        for _ in 0..n {
-                            self.instruction_manager.instr_Inc();
-                        }
-                        true
+            (Access::Constant(c), other) | (other, Access::Constant(c)) => match c.as_i64() {
+                Some(0) => {
+                    self.translate_load_access(other);
+                    true
+                }
+                Some(n) if n > 0 && n <= 10 => {
+                    self.translate_load_access(other);
+                    for _ in 0..n {
+                        self.instruction_manager.instr_Inc();
                     }
-                    n if n < 0 && n >= -10 => {
-                        self.translate_load_access(other);
-                        for _ in 0..n.abs() {
-                            self.instruction_manager.instr_Dec();
-                        }
-                        true
+                    true
+                }
+                Some(n) if n < 0 && n >= -10 => {
+                    self.translate_load_access(other);
+                    for _ in 0..n.abs() {
+                        self.instruction_manager.instr_Dec();
                     }
-                    _ => false
+                    true
                 }
-            }
+                _ => false,
+            },
             _ => false,
         };
 
@@ -966,39 +1248,35 @@ impl Generator {
 
     fn translate_minus(&mut self, left: &Access, right: &Access) {
         let optimized = match (left, right) {
-            (other, Access::Constant(c)) => {
-                match c.value() {
-                    0 => {
-                        self.translate_load_access(other);
-                        true
-                    },
-                    n if n > 0 && n <= 10 => {
-                        self.translate_load_access(other);
-                        for _ in 0..n {
-                            self.instruction_manager.instr_Dec();
-                        }
-                        true
-                    }
-                    n if n < 0 && n >= -10 => {
-                        self.translate_load_access(other);
-                        for _ in 0..n.abs() {
-                            self.instruction_manager.instr_Inc();
-                        }
-                        true
+            (other, Access::Constant(c)) => match c.as_i64() {
+                Some(0) => {
+                    self.translate_load_access(other);
+                    true
+                }
+                Some(n) if n > 0 && n <= 10 => {
+                    self.translate_load_access(other);
+                    for _ in 0..n {
+                        self.instruction_manager.instr_Dec();
                     }
-                    _ => false
+                    true
                 }
-            }
-            (Access::Constant(c), other) => {
-                match c.value() {
-                    0 => {
-                        self.translate_load_access(other);
-                        self.translate_neg_tmp();
-                        true
+                Some(n) if n < 0 && n >= -10 => {
+                    self.translate_load_access(other);
+                    for _ in 0..n.abs() {
+                        self.instruction_manager.instr_Inc();
                     }
-                    _ => false,
+                    true
                 }
-            }
+                _ => false,
+            },
+            (Access::Constant(c), other) => match c.as_i64() {
+                Some(0) => {
+                    self.translate_load_access(other);
+                    self.translate_neg_tmp();
+                    true
+                }
+                _ => false,
+            },
             _ => false,
         };
 
@@ -1007,7 +1285,12 @@ impl Generator {
         }
     }
 
-    fn translate_simple_bin_op(&mut self, left: &Access, right: &Access, op: fn(&mut InstructionManager, MemoryLocation)) {
+    fn translate_simple_bin_op(
+        &mut self,
+        left: &Access,
+        right: &Access,
+        op: fn(&mut InstructionManager, MemoryLocation),
+    ) {
         self.translate_load_access(right);
         let tmp = self.get_or_register_temp("bin_op");
         self.instruction_manager.instr_Store(tmp);
@@ -1017,19 +1300,19 @@ impl Generator {
     }
 
     pub fn translate(mut self) -> Vec<VmInstruction> {
-        let simple_constants = vec![
-            Constant(0),
-            Constant(1),
-            Constant(-1),
-            Constant(2),
-            Constant(-2),
-        ];
-
-        for c in &simple_constants {
-            self.context.register_constant(c.clone());
+        let simple_constants = vec![0, 1, -1, 2, -2];
+
+        for &c in &simple_constants {
+            self.context.register_constant(Constant::from(c));
+        }
+
+        if self.options.bounds_checks {
+            self.register_bounds_checks();
         }
+        self.register_calls();
 
         self.allocate_memory();
+        self.allocate_addresses();
         self.generate_constants();
 
         let ir_instructions = self.context.instructions().to_vec();
@@ -1044,31 +1327,26 @@ impl Generator {
                     match access {
                         Access::Constant(_) | Access::Variable(_) | Access::ArrayStatic(_, _) => (),
                         Access::ArrayDynamic(_, _) => (), // unimplemented!(),
+                        Access::Indirect(_) | Access::Address(_) => (),
                     }
                 }
                 Instruction::Store { access } => self.translate_store_access(access),
-                Instruction::Operation { left, op, right } => {
-                    match op {
-                        OperationType::Plus => {
-                            self.translate_plus(left, right)
-                        }
-                        OperationType::Minus => {
-                           self.translate_minus(left, right)
-                        }
-                        OperationType::Shift => {
-                            self.translate_simple_bin_op(left, right, InstructionManager::instr_Shift)
-                        }
-                        OperationType::Times => {
-                            self.translate_multiplication(left, right);
-                        }
-                        OperationType::Div => {
-                            self.translate_div_mod(left, right, true);
-                        }
-                        OperationType::Mod => {
-                            self.translate_div_mod(left, right, false);
-                        }
+                Instruction::Operation { left, op, right } => match op {
+                    OperationType::Plus => self.translate_plus(left, right),
+                    OperationType::Minus => self.translate_minus(left, right),
+                    OperationType::Shift => {
+                        self.translate_simple_bin_op(left, right, InstructionManager::instr_Shift)
                     }
-                }
+                    OperationType::Times => {
+                        self.translate_multiplication(left, right);
+                    }
+                    OperationType::Div => {
+                        self.translate_div_mod(left, right, true);
+                    }
+                    OperationType::Mod => {
+                        self.translate_div_mod(left, right, false);
+                    }
+                },
                 Instruction::Jump { label } => {
                     self.instruction_manager
                         .translate_jump(label, VmInstruction::Jump);
@@ -1085,12 +1363,34 @@ impl Generator {
                     self.instruction_manager
                         .translate_jump(label, VmInstruction::Jzero);
                 }
+                Instruction::CheckBounds { index, start, end } => {
+                    self.translate_check_bounds(index, start, end)
+                }
                 Instruction::Get => self.instruction_manager.instr_Get(),
                 Instruction::Put => self.instruction_manager.instr_Put(),
+                Instruction::Call {
+                    label,
+                    return_address,
+                    return_label,
+                } => {
+                    let index = self.call_sites[return_address]
+                        .iter()
+                        .position(|call_site| call_site == return_label)
+                        .expect("unregistered call site");
+                    let index_loc = self.get_constant_location(index as i64);
+                    let return_loc = self.memory.get_location(*return_address);
+
+                    self.instruction_manager.instr_Load(index_loc);
+                    self.instruction_manager.instr_Store(return_loc);
+                    self.instruction_manager
+                        .translate_jump(label, VmInstruction::Jump);
+                }
+                Instruction::Return { return_address } => self.translate_return(*return_address),
             }
         }
 
         self.instruction_manager.instr_Halt();
+        self.translate_bounds_trap();
 
         if cfg!(debug_assertions) {
             let vars = self
@@ -1,44 +1,205 @@
-use gembiler::code_generator::{intermediate, translator};
+use gembiler::code_generator::{intermediate, peephole, translator};
+use gembiler::verifier::{self, Severity};
+use parser::ast::{printer, Span};
 use std::env;
-use std::fs::File;
-use std::io::{Write as _};
-use std::fmt::{self, Write as _, Display, Formatter, Debug};
+use std::fmt::{self, Debug, Display, Formatter, Write as _};
+use std::fs::{self, File};
+use std::io::Write as _;
 use std::path::Path;
 use virtual_machine::instruction::InstructionListPrinter;
-use gembiler::verifier;
 
-fn compile<P1: AsRef<Path>, P2: AsRef<Path>>(path: P1, output_path: P2) -> Result<(), String> {
-    let program = parser::parse_file(path);
+struct Options {
+    parser: parser::Options,
+    verifier: verifier::Options,
+    intermediate: intermediate::Options,
+    translator: translator::Options,
+    warnings_as_errors: bool,
+    optimize: bool,
+}
 
-    program.and_then(|program| {
-        let program = verifier::verify(program).map_err(|errors| {
-            let mut buf = String::with_capacity(errors.len() * 40);
+fn render_diagnostic(
+    path: &Path,
+    source: &str,
+    severity: Severity,
+    message: &dyn Display,
+    span: Option<Span>,
+) -> String {
+    let mut buf = String::new();
+    writeln!(&mut buf, "{}: {}", severity, message).unwrap();
 
-            for e in errors {
-                writeln!(&mut buf, "{}", e).unwrap();
-            }
+    let span = match span {
+        Some(span) => span,
+        None => return buf,
+    };
+
+    let line_number = span.line.to_string();
+    let gutter = " ".repeat(line_number.len());
+    writeln!(&mut buf, "{}--> {}:{}", gutter, path.display(), span).unwrap();
+
+    if let Some(line) = source.lines().nth(span.line - 1) {
+        let padding: String = line
+            .chars()
+            .take(span.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let underline_len = source[span.start..span.end]
+            .lines()
+            .next()
+            .map_or(0, |s| s.chars().count())
+            .max(1);
 
-            buf
-        })?;
+        writeln!(&mut buf, "{} |", gutter).unwrap();
+        writeln!(&mut buf, "{} | {}", line_number, line).unwrap();
+        writeln!(
+            &mut buf,
+            "{} | {}{}",
+            gutter,
+            padding,
+            "^".repeat(underline_len)
+        )
+        .unwrap();
+    }
+
+    buf
+}
+
+fn compile<P1: AsRef<Path>, P2: AsRef<Path>>(
+    path: P1,
+    output_path: P2,
+    options: &Options,
+) -> Result<(), String> {
+    let path = path.as_ref();
+
+    let program = parser::parse_file_with_options(path, options.parser).map_err(|error| {
+        let path = error.path().unwrap_or(path);
+        let source = fs::read_to_string(path).unwrap_or_default();
+        render_diagnostic(path, &source, Severity::Error, &error, error.span())
+    })?;
+
+    // spans of included code refer to the file they were read from
+    let files = program.files.clone();
+    let sources: Vec<_> = files
+        .iter()
+        .map(|file| fs::read_to_string(file).unwrap_or_default())
+        .collect();
+
+    let (program, diagnostics) = match verifier::verify_with_options(program, options.verifier) {
+        Ok((program, diagnostics)) => (Some(program), diagnostics),
+        Err(diagnostics) => (None, diagnostics),
+    };
+
+    let mut rendered = Vec::with_capacity(diagnostics.len());
+    let mut failed = false;
+    for mut diagnostic in diagnostics {
+        if options.warnings_as_errors {
+            diagnostic.severity = Severity::Error;
+        }
+        failed |= diagnostic.is_error();
+        let file = diagnostic.span.file;
+        rendered.push(render_diagnostic(
+            &files[file],
+            &sources[file],
+            diagnostic.severity,
+            &diagnostic.kind,
+            Some(diagnostic.span),
+        ));
+    }
+    let rendered = rendered.join("\n");
 
-        let context = intermediate::generate(&program).unwrap();
-        let generator = translator::Generator::new(context);
-        let translated = generator.translate();
+    let program = match program {
+        Some(program) if !failed => program,
+        _ => return Err(rendered),
+    };
+    eprint!("{}", rendered);
 
-        let display = output_path.as_ref().display();
-        let mut file = match File::create(&output_path) {
-            Err(why) => panic!("couldn't create {}: {}", display, why),
-            Ok(file) => file,
+    let mut context = intermediate::generate_with_options(&program, options.intermediate).unwrap();
+    if options.optimize {
+        intermediate::optimizations::optimize(&mut context);
+    }
+    let generator = translator::Generator::with_options(context, options.translator);
+    let mut translated = generator.translate();
+    if options.optimize {
+        translated = peephole::optimize(translated);
+    }
+
+    let display = output_path.as_ref().display();
+    let mut file = match File::create(&output_path) {
+        Err(why) => panic!("couldn't create {}: {}", display, why),
+        Ok(file) => file,
+    };
+
+    file.write_fmt(format_args!(
+        "{}",
+        InstructionListPrinter(translated.as_slice())
+    ))
+    .expect("writing to file failed");
+
+    Ok(())
+}
+
+fn read_source(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|error| {
+        let error = parser::Error::Io {
+            path: path.to_owned(),
+            error,
         };
+        render_diagnostic(path, "", Severity::Error, &error, None)
+    })
+}
+
+/// Rewrites the file in canonical layout. Returns whether it was changed,
+/// without touching the file if `check` is set.
+fn format(path: &Path, check: bool) -> Result<bool, String> {
+    let source = read_source(path)?;
+
+    // formatting shouldn't reject programs that only fail with default options
+    #[allow(unused_mut)]
+    let mut options = parser::Options::default();
+    #[cfg(feature = "bignum")]
+    {
+        options.big_literals = true;
+    }
 
-        file.write_fmt(format_args!(
-            "{}",
-            InstructionListPrinter(translated.as_slice())
-        ))
-        .expect("writing to file failed");
+    let (program, comments) = parser::parse_ast_with_comments(&source, options)
+        .map_err(|error| render_diagnostic(path, &source, Severity::Error, &error, error.span()))?;
+    let formatted = printer::print_with_comments(&program, &comments);
 
+    if formatted == source {
+        return Ok(false);
+    }
+    if !check {
+        fs::write(path, formatted)
+            .map_err(|error| format!("error: couldn't write {}: {}\n", path.display(), error))?;
+    }
+
+    Ok(true)
+}
+
+fn format_files(paths: &[&String], check: bool) -> Result<(), DebugDisplayWrapper<String>> {
+    let mut failed = false;
+    let mut unformatted = false;
+
+    for path in paths {
+        match format(Path::new(path), check) {
+            Ok(true) if check => {
+                println!("{} is not formatted", path);
+                unformatted = true;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprint!("{}", e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        Err("formatting failed".to_owned().into())
+    } else if unformatted {
+        Err("some files are not formatted".to_owned().into())
+    } else {
         Ok(())
-    })
+    }
 }
 
 struct DebugDisplayWrapper<T: Display>(T);
@@ -55,20 +216,79 @@ impl<T: Display> From<T> for DebugDisplayWrapper<T> {
     }
 }
 
+fn usage(program_name: &str) -> String {
+    let mut usage = format!("Usage: {} [options] <input> <output>", program_name);
+    usage.push_str(&format!("\n       {} fmt [--check] <files>", program_name));
+    usage.push_str("\n  -Werror         treat warnings as errors");
+    usage.push_str("\n  --check-counter-indices\n                  check array indices that are counters of loops with constant bounds");
+    usage.push_str(
+        "\n  --bounds-checks halt with a sentinel output on out-of-bounds array accesses",
+    );
+    usage.push_str(
+        "\n  --no-optimize   translate the intermediate code as generated, without cleanup",
+    );
+    if cfg!(feature = "bignum") {
+        usage.push_str("\n  --big-literals  accept integer literals that don't fit in 64 bits");
+    }
+    usage.push_str("\n  --check         with fmt, only list the files that aren't formatted");
+    usage
+}
+
 fn main() -> Result<(), DebugDisplayWrapper<String>> {
     let args: Vec<_> = env::args().collect();
-    let len = args.len();
 
-    match len {
-        len if len < 3 => Err(format!("Usage: {} <input> <output>", args[0]).into()),
-        _ => match compile(args[1].as_str(), args[2].as_str()) {
+    if args.get(1).map(String::as_str) == Some("fmt") {
+        let (flags, paths): (Vec<_>, Vec<_>) =
+            args[2..].iter().partition(|arg| arg.starts_with('-'));
+        let mut check = false;
+        for flag in flags {
+            match flag.as_str() {
+                "--check" => check = true,
+                _ => return Err(format!("unknown option {}\n{}", flag, usage(&args[0])).into()),
+            }
+        }
+        if paths.is_empty() {
+            return Err(usage(&args[0]).into());
+        }
+        return format_files(&paths, check);
+    }
+
+    let (flags, paths): (Vec<_>, Vec<_>) = args[1..].iter().partition(|arg| arg.starts_with('-'));
+
+    let mut options = Options {
+        parser: parser::Options::default(),
+        verifier: verifier::Options::default(),
+        intermediate: intermediate::Options::default(),
+        translator: translator::Options::default(),
+        warnings_as_errors: false,
+        optimize: true,
+    };
+    for flag in flags {
+        match flag.as_str() {
+            "-Werror" => options.warnings_as_errors = true,
+            "--check-counter-indices" => options.verifier.check_counter_indices = true,
+            "--bounds-checks" => {
+                options.intermediate.bounds_checks = true;
+                options.translator.bounds_checks = true;
+            }
+            "--no-optimize" => options.optimize = false,
+            #[cfg(feature = "bignum")]
+            "--big-literals" => options.parser.big_literals = true,
+            _ => return Err(format!("unknown option {}\n{}", flag, usage(&args[0])).into()),
+        }
+    }
+
+    match paths.as_slice() {
+        [input, output] => match compile(input.as_str(), output.as_str(), &options) {
             Ok(_) => {
-                println!("Output written to {}", args[2]);
+                println!("Output written to {}", output);
                 Ok(())
-            },
+            }
             Err(e) => {
-                Err(format!("{}", e).into())
+                eprint!("{}", e);
+                Err("compilation failed".to_owned().into())
             }
         },
+        _ => Err(usage(&args[0]).into()),
     }
 }
@@ -0,0 +1,68 @@
+use super::{Error, Warning};
+use parser::ast::Span;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DiagnosticKind {
+    Error(Error),
+    Warning(Warning),
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticKind::Error(error) => error.fmt(f),
+            DiagnosticKind::Warning(warning) => warning.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn error(error: Error, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            kind: DiagnosticKind::Error(error),
+            span,
+        }
+    }
+
+    pub fn warning(warning: Warning, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            kind: DiagnosticKind::Warning(warning),
+            span,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.kind)
+    }
+}
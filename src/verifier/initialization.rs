@@ -0,0 +1,361 @@
+use super::{Diagnostic, Error, Warning};
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
+use parser::ast::visitor::{walk_procedure, Visitor};
+use parser::ast::*;
+use std::collections::BTreeMap;
+use std::mem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Initialization {
+    Never,
+    Sometimes,
+    Always,
+}
+
+impl Initialization {
+    fn join(self, other: Self) -> Self {
+        if self == other {
+            self
+        } else {
+            Initialization::Sometimes
+        }
+    }
+}
+
+type State = BTreeMap<String, Initialization>;
+
+/// How a procedure uses one of its parameters, as seen from a call.
+#[derive(Debug, Clone, Copy)]
+struct ParameterUse {
+    /// The argument may be read before the procedure assigns it.
+    read: bool,
+    /// Whether the argument is assigned once the procedure returns.
+    written: Initialization,
+}
+
+/// States in which the iteration of a loop was cut short.
+#[derive(Debug, Default)]
+struct LoopExits {
+    breaks: Vec<State>,
+    continues: Vec<State>,
+}
+
+/// Flow-sensitive check that every variable is assigned before it's read.
+///
+/// Array elements aren't tracked separately - an array counts as initialized
+/// once any of its elements may have been assigned.
+///
+/// Arguments of a call are checked like reads if the procedure may read
+/// the parameter before assigning it, and count as assigned by the call
+/// only if the procedure assigns the parameter.
+#[derive(Debug)]
+pub struct InitializationChecker {
+    state: State,
+    arrays: Vec<String>,
+    locals: Vec<String>,
+    /// Parameters of the procedure being checked, and whether they may be read before assigned.
+    parameters: BTreeMap<String, bool>,
+    procedures: BTreeMap<String, Vec<ParameterUse>>,
+    loops: Vec<LoopExits>,
+    /// False after a BREAK or CONTINUE, until control flow joins a path that may get there.
+    reachable: bool,
+    reporting: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl InitializationChecker {
+    pub fn new() -> Self {
+        InitializationChecker {
+            state: State::new(),
+            arrays: vec![],
+            locals: vec![],
+            parameters: BTreeMap::new(),
+            procedures: BTreeMap::new(),
+            loops: vec![],
+            reachable: true,
+            reporting: true,
+            diagnostics: vec![],
+        }
+    }
+
+    fn join_state(&mut self, other: State) {
+        for (name, initialization) in other {
+            let current = self.state.entry(name).or_insert(Initialization::Never);
+            *current = current.join(initialization);
+        }
+    }
+
+    /// Joins the state at the end of another path into the current one.
+    /// Paths that can't get here don't contribute.
+    fn join_path(&mut self, other: State, other_reachable: bool) {
+        if !other_reachable {
+            return;
+        }
+        if self.reachable {
+            self.join_state(other);
+        } else {
+            self.state = other;
+            self.reachable = true;
+        }
+    }
+
+    fn assign(&mut self, name: &str) {
+        if let Some(initialization) = self.state.get_mut(name) {
+            *initialization = Initialization::Always;
+        }
+    }
+
+    /// Records an assignment that happens on some of the paths only.
+    fn assign_sometimes(&mut self, name: &str) {
+        if let Some(initialization) = self.state.get_mut(name) {
+            *initialization = initialization.join(Initialization::Always);
+        }
+    }
+
+    fn check_read(&mut self, name: &str, span: Span) {
+        if !self.reporting || self.locals.iter().any(|local| local == name) {
+            return;
+        }
+
+        let is_array = self.arrays.iter().any(|array| array == name);
+        let is_error = match self.state.get(name) {
+            Some(Initialization::Never) => true,
+            Some(Initialization::Sometimes) if !is_array => false,
+            _ => return,
+        };
+
+        // a parameter read before it's assigned holds the value of the argument,
+        // which is checked at the calls instead
+        if let Some(read) = self.parameters.get_mut(name) {
+            *read = true;
+            return;
+        }
+
+        let name = name.to_owned();
+        let diagnostic = if is_error {
+            Diagnostic::error(Error::UninitializedVariable { name }, span)
+        } else {
+            Diagnostic::warning(Warning::PossiblyUninitializedVariable { name }, span)
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn check_index_read(&mut self, target: &Identifier) {
+        match target {
+            Identifier::ArrAccess { index, span, .. } => self.check_read(index, *span),
+            _ => {
+                let span = target.span();
+                for index in target.index_expressions() {
+                    for identifier in index.identifiers() {
+                        for name in identifier.all_names() {
+                            self.check_read(name, span);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Analyses a loop whose `body` may run any number of times
+    /// and returns the state at the start of an iteration.
+    ///
+    /// The body is visited once without reporting anything to find out
+    /// what a previous iteration may have assigned, and once more starting
+    /// from the state joined with that.
+    fn visit_loop<F: FnMut(&mut Self)>(&mut self, mut body: F) -> State {
+        let before = self.state.clone();
+        let reachable = self.reachable;
+        let reporting = mem::replace(&mut self.reporting, false);
+        body(self);
+        self.reporting = reporting;
+        self.join_path(before, reachable);
+        let start = self.state.clone();
+        body(self);
+        start
+    }
+
+    /// Visits the commands of a loop body, ending in the state the next
+    /// iteration continues from. Returns the states BREAK left the loop in.
+    fn visit_loop_commands(&mut self, commands: &Commands) -> Vec<State> {
+        self.loops.push(LoopExits::default());
+        self.visit_commands(commands);
+        let exits = self.loops.pop().expect("loops incorrectly nested");
+        for state in exits.continues {
+            self.join_path(state, true);
+        }
+        exits.breaks
+    }
+
+    fn join_breaks(&mut self, breaks: Vec<State>) {
+        for state in breaks {
+            self.join_path(state, true);
+        }
+    }
+}
+
+impl Visitor for InitializationChecker {
+    type Result = ();
+
+    fn visit_procedure(&mut self, procedure: &Procedure) -> Self::Result {
+        let state = mem::take(&mut self.state);
+        let arrays = mem::take(&mut self.arrays);
+        let reachable = mem::replace(&mut self.reachable, true);
+
+        // parameters refer to variables of the caller, which are checked at the calls
+        for parameter in &procedure.parameters {
+            if parameter.array {
+                self.arrays.push(parameter.name.clone());
+            }
+            self.state
+                .insert(parameter.name.clone(), Initialization::Never);
+            self.parameters.insert(parameter.name.clone(), false);
+        }
+        walk_procedure(self, procedure);
+
+        let uses = procedure
+            .parameters
+            .iter()
+            .map(|parameter| ParameterUse {
+                read: self.parameters[&parameter.name],
+                written: self.state[&parameter.name],
+            })
+            .collect();
+        self.procedures.insert(procedure.name.clone(), uses);
+        self.parameters.clear();
+
+        self.state = state;
+        self.arrays = arrays;
+        self.reachable = reachable;
+    }
+
+    fn visit_declaration(&mut self, declaration: &Declaration) -> Self::Result {
+        if let Declaration::Array { name, .. } = declaration {
+            self.arrays.push(name.clone());
+        }
+        self.state
+            .insert(declaration.name().to_owned(), Initialization::Never);
+    }
+
+    fn visit_if_else_command(
+        &mut self,
+        condition: &Condition,
+        positive: &Commands,
+        negative: &Commands,
+    ) -> Self::Result {
+        self.visit(condition);
+        let before = self.state.clone();
+        let reachable = self.reachable;
+        self.visit_commands(positive);
+        let after_positive = mem::replace(&mut self.state, before);
+        let positive_reachable = mem::replace(&mut self.reachable, reachable);
+        self.visit_commands(negative);
+        self.join_path(after_positive, positive_reachable);
+    }
+
+    fn visit_if_command(&mut self, condition: &Condition, positive: &Commands) -> Self::Result {
+        self.visit(condition);
+        let before = self.state.clone();
+        let reachable = self.reachable;
+        self.visit_commands(positive);
+        self.join_path(before, reachable);
+    }
+
+    fn visit_while_command(&mut self, condition: &Condition, commands: &Commands) -> Self::Result {
+        let reachable = self.reachable;
+        let mut breaks = vec![];
+        let start = self.visit_loop(|checker| {
+            checker.visit(condition);
+            breaks = checker.visit_loop_commands(commands);
+        });
+        // the loop is left when the condition fails at the start of an iteration
+        self.state = start;
+        self.reachable = reachable;
+        self.join_breaks(breaks);
+    }
+
+    fn visit_do_command(&mut self, commands: &Commands, condition: &Condition) -> Self::Result {
+        let mut breaks = vec![];
+        self.visit_loop(|checker| {
+            breaks = checker.visit_loop_commands(commands);
+            checker.visit(condition);
+        });
+        self.join_breaks(breaks);
+    }
+
+    fn visit_for_command(
+        &mut self,
+        counter: &str,
+        _ascending: bool,
+        from: &Value,
+        to: &Value,
+        step: Option<&Value>,
+        commands: &Commands,
+    ) -> Self::Result {
+        self.visit(from);
+        self.visit(to);
+        self.visit_collection(step);
+
+        self.locals.push(counter.to_owned());
+        let reachable = self.reachable;
+        let mut breaks = vec![];
+        self.state = self.visit_loop(|checker| breaks = checker.visit_loop_commands(commands));
+        self.reachable = reachable;
+        self.join_breaks(breaks);
+        self.locals.pop();
+    }
+
+    fn visit_break_command(&mut self) -> Self::Result {
+        if let (true, Some(exits)) = (self.reachable, self.loops.last_mut()) {
+            exits.breaks.push(self.state.clone());
+        }
+        self.reachable = false;
+    }
+
+    fn visit_continue_command(&mut self) -> Self::Result {
+        if let (true, Some(exits)) = (self.reachable, self.loops.last_mut()) {
+            exits.continues.push(self.state.clone());
+        }
+        self.reachable = false;
+    }
+
+    fn visit_read_command(&mut self, targets: &[Identifier]) -> Self::Result {
+        // targets are read in order, so an index may be read just before
+        for target in targets {
+            self.check_index_read(target);
+            self.assign(target.name());
+        }
+    }
+
+    fn visit_assign_command(&mut self, target: &Identifier, expr: &Expression) -> Self::Result {
+        self.visit(expr);
+        self.check_index_read(target);
+        self.assign(target.name());
+    }
+
+    fn visit_call_command(&mut self, name: &str, arguments: &[Argument]) -> Self::Result {
+        // arguments are passed by reference, so the procedure may read and assign them
+        let uses = self.procedures[name].clone();
+        for (argument, usage) in arguments.iter().zip(uses) {
+            if usage.read {
+                self.check_read(&argument.name, argument.span);
+            }
+            match usage.written {
+                Initialization::Always => self.assign(&argument.name),
+                Initialization::Sometimes => self.assign_sometimes(&argument.name),
+                Initialization::Never => {}
+            }
+        }
+    }
+
+    fn visit_num_value(&mut self, _: i64) -> Self::Result {}
+
+    #[cfg(feature = "bignum")]
+    fn visit_big_num_value(&mut self, _: &BigInt) -> Self::Result {}
+
+    fn visit_identifier(&mut self, identifier: &Identifier) -> Self::Result {
+        for name in identifier.all_names() {
+            self.check_read(name, identifier.span());
+        }
+    }
+}
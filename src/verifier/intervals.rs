@@ -0,0 +1,167 @@
+use super::{Diagnostic, Error};
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
+use parser::ast::visitor::{walk_procedure, Visitor};
+use parser::ast::*;
+use std::mem;
+
+/// Interval analysis of array indices that are counters of loops with constant bounds.
+///
+/// Such a counter takes every value between its bounds that its step lands on,
+/// so an index is either proven to be in range, or some iteration reads or
+/// writes outside the array. With a step that isn't constant, the counter is
+/// only known to stay between its bounds.
+#[derive(Debug)]
+pub struct CounterIndexChecker {
+    arrays: Vec<(String, Vec<(i64, i64)>)>,
+    counters: Vec<(String, Option<(i64, i64)>)>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CounterIndexChecker {
+    pub fn new() -> Self {
+        CounterIndexChecker {
+            arrays: vec![],
+            counters: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    fn get_interval(&self, counter: &str) -> Option<(i64, i64)> {
+        self.counters
+            .iter()
+            .rev()
+            .find(|(name, _)| name == counter)
+            .and_then(|(_, interval)| *interval)
+    }
+}
+
+impl Visitor for CounterIndexChecker {
+    type Result = ();
+
+    fn visit_procedure(&mut self, procedure: &Procedure) -> Self::Result {
+        // bounds of array parameters aren't known, so their indices aren't checked
+        let arrays = mem::take(&mut self.arrays);
+        walk_procedure(self, procedure);
+        self.arrays = arrays;
+    }
+
+    fn visit_declaration(&mut self, declaration: &Declaration) -> Self::Result {
+        if let Declaration::Array {
+            name, dimensions, ..
+        } = declaration
+        {
+            self.arrays.push((name.clone(), dimensions.clone()));
+        }
+    }
+
+    fn visit_for_command(
+        &mut self,
+        counter: &str,
+        ascending: bool,
+        from: &Value,
+        to: &Value,
+        step: Option<&Value>,
+        commands: &Commands,
+    ) -> Self::Result {
+        // with a constant step the counter stops at the last value it can reach
+        // without passing `to`, otherwise it's only known to stay between the bounds
+        let step = match step {
+            None => Some(1),
+            Some(Value::Num { value, .. }) => value.checked_abs().filter(|&step| step != 0),
+            Some(_) => None,
+        };
+        let interval = match (from, to) {
+            (&Value::Num { value: from, .. }, &Value::Num { value: to, .. }) => {
+                let (low, high) = if ascending { (from, to) } else { (to, from) };
+                // how far from `from` the counter gets
+                let reach = match step {
+                    Some(step) if low <= high => {
+                        high.checked_sub(low).map(|distance| distance / step * step)
+                    }
+                    _ => None,
+                };
+                match reach {
+                    Some(reach) if ascending => Some((from, from + reach)),
+                    Some(reach) => Some((from - reach, from)),
+                    None => Some((low, high)),
+                }
+            }
+            _ => None,
+        };
+        // a loop that never runs can't access anything
+        let interval = interval.filter(|(low, high)| low <= high);
+
+        self.counters.push((counter.to_owned(), interval));
+        self.visit_commands(commands);
+        self.counters.pop();
+    }
+
+    fn visit_num_value(&mut self, _: i64) -> Self::Result {}
+
+    #[cfg(feature = "bignum")]
+    fn visit_big_num_value(&mut self, _: &BigInt) -> Self::Result {}
+
+    fn visit_identifier(&mut self, identifier: &Identifier) -> Self::Result {
+        for index in identifier.index_expressions() {
+            for nested in index.identifiers() {
+                self.visit_identifier(nested);
+            }
+        }
+
+        let (name, indices, span) = match identifier {
+            Identifier::ArrAccess { name, index, span } => (name, vec![Some(index.as_str())], span),
+            Identifier::ArrMultiAccess {
+                name,
+                indices,
+                span,
+            } => {
+                let indices = indices
+                    .iter()
+                    .map(|index| match index {
+                        Expression::Simple {
+                            value: Value::Identifier(Identifier::VarAccess { name, .. }),
+                            ..
+                        } => Some(name.as_str()),
+                        // constant indices are checked by the semantic verifier
+                        _ => None,
+                    })
+                    .collect();
+                (name, indices, span)
+            }
+            _ => return,
+        };
+
+        // an access with the wrong number of indices is reported by the semantic verifier
+        let dimensions = match self.arrays.iter().find(|(array, _)| array == name) {
+            Some((_, dimensions)) if dimensions.len() == indices.len() => dimensions.clone(),
+            _ => return,
+        };
+
+        for (index, (start, end)) in indices.into_iter().zip(dimensions) {
+            let (low, high) = match index.and_then(|index| self.get_interval(index)) {
+                // invalid ranges are reported by the semantic verifier
+                Some(interval) if start <= end => interval,
+                _ => continue,
+            };
+
+            let out_of_bounds = if low < start {
+                Some(low)
+            } else if high > end {
+                Some(high)
+            } else {
+                None
+            };
+
+            if let Some(index) = out_of_bounds {
+                let error = Error::ArrayIndexOutOfBounds {
+                    name: name.clone(),
+                    index,
+                    start,
+                    end,
+                };
+                self.diagnostics.push(Diagnostic::error(error, *span));
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use super::{Diagnostic, Warning};
+#[cfg(feature = "bignum")]
+use num_bigint::{BigInt, Sign};
+use parser::ast::visitor::{walk_procedure, Visitor};
+use parser::ast::*;
+use std::collections::BTreeSet;
+use std::mem;
+
+#[derive(Debug)]
+struct CounterLoop {
+    counter: String,
+    indexed_arrays: BTreeSet<String>,
+    constant_accesses: Vec<(String, i64, Span)>,
+}
+
+/// Looks for code that is valid, but most likely doesn't do what was intended.
+#[derive(Debug)]
+pub struct Linter {
+    declarations: Vec<Declaration>,
+    used: BTreeSet<String>,
+    loop_depth: usize,
+    counter_loops: Vec<CounterLoop>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    pub fn new() -> Self {
+        Linter {
+            declarations: vec![],
+            used: BTreeSet::new(),
+            loop_depth: 0,
+            counter_loops: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    fn warn(&mut self, warning: Warning, span: Span) {
+        self.diagnostics.push(Diagnostic::warning(warning, span));
+    }
+
+    fn report_unused(&mut self) {
+        let unused: Vec<_> = self
+            .declarations
+            .iter()
+            .filter(|declaration| !self.used.contains(declaration.name()))
+            .map(|declaration| (declaration.name().to_owned(), declaration.span()))
+            .collect();
+
+        for (name, span) in unused {
+            self.warn(Warning::UnusedDeclaration { name }, span);
+        }
+    }
+
+    fn visit_loop_body(&mut self, commands: &Commands) {
+        self.loop_depth += 1;
+        self.visit_commands(commands);
+        self.loop_depth -= 1;
+    }
+}
+
+impl Visitor for Linter {
+    type Result = ();
+
+    fn visit_program(&mut self, program: &Program) -> Self::Result {
+        self.visit_collection(&program.procedures);
+        if let Some(declarations) = &program.declarations {
+            self.visit_declarations(declarations);
+        }
+        self.visit_commands(&program.commands);
+        self.report_unused();
+    }
+
+    fn visit_procedure(&mut self, procedure: &Procedure) -> Self::Result {
+        let declarations = mem::take(&mut self.declarations);
+        let used = mem::take(&mut self.used);
+
+        walk_procedure(self, procedure);
+        self.report_unused();
+
+        self.declarations = declarations;
+        self.used = used;
+    }
+
+    fn visit_declaration(&mut self, declaration: &Declaration) -> Self::Result {
+        self.declarations.push(declaration.clone());
+    }
+
+    fn visit_while_command(&mut self, condition: &Condition, commands: &Commands) -> Self::Result {
+        self.visit(condition);
+        self.visit_loop_body(commands);
+    }
+
+    fn visit_do_command(&mut self, commands: &Commands, condition: &Condition) -> Self::Result {
+        self.visit_loop_body(commands);
+        self.visit(condition);
+    }
+
+    fn visit_for_command(
+        &mut self,
+        counter: &str,
+        ascending: bool,
+        from: &Value,
+        to: &Value,
+        step: Option<&Value>,
+        commands: &Commands,
+    ) -> Self::Result {
+        self.visit(from);
+        self.visit(to);
+        self.visit_collection(step);
+
+        if let (Value::Num { value: start, .. }, Value::Num { value: end, .. }) = (from, to) {
+            let never_runs = if ascending { start > end } else { start < end };
+            if never_runs {
+                let counter = counter.to_owned();
+                self.warn(Warning::EmptyForLoop { counter }, from.span().to(to.span()));
+            }
+        }
+
+        // the direction comes from TO or DOWNTO, so a negative step reads as going the other way
+        if let Some(step) = step {
+            let is_negative = match step {
+                Value::Num { value, .. } => *value < 0,
+                #[cfg(feature = "bignum")]
+                Value::BigNum { value, .. } => value.sign() == Sign::Minus,
+                Value::Identifier(_) => false,
+            };
+            if is_negative {
+                let counter = counter.to_owned();
+                self.warn(Warning::NegativeForStep { counter }, step.span());
+            }
+        }
+
+        self.counter_loops.push(CounterLoop {
+            counter: counter.to_owned(),
+            indexed_arrays: BTreeSet::new(),
+            constant_accesses: vec![],
+        });
+        self.visit_loop_body(commands);
+        let counter_loop = self.counter_loops.pop().unwrap();
+
+        for (name, index, span) in counter_loop.constant_accesses {
+            if counter_loop.indexed_arrays.contains(&name) {
+                let warning = Warning::ConstantIndexInCounterLoop {
+                    name,
+                    index,
+                    counter: counter_loop.counter.clone(),
+                };
+                self.warn(warning, span);
+            } else if let Some(outer) = self.counter_loops.last_mut() {
+                // the array may still be indexed by the counter of an enclosing loop
+                outer.constant_accesses.push((name, index, span));
+            }
+        }
+    }
+
+    fn visit_write_command(&mut self, values: &[Value]) -> Self::Result {
+        for value in values {
+            let is_constant = !matches!(value, Value::Identifier(_));
+            if is_constant && self.loop_depth > 0 {
+                self.warn(Warning::ConstantWriteInLoop, value.span());
+            }
+
+            self.visit(value);
+        }
+    }
+
+    fn visit_call_command(&mut self, _name: &str, arguments: &[Argument]) -> Self::Result {
+        for argument in arguments {
+            self.used.insert(argument.name.clone());
+        }
+    }
+
+    fn visit_num_value(&mut self, _: i64) -> Self::Result {}
+
+    #[cfg(feature = "bignum")]
+    fn visit_big_num_value(&mut self, _: &BigInt) -> Self::Result {}
+
+    fn visit_identifier(&mut self, identifier: &Identifier) -> Self::Result {
+        for name in identifier.all_names() {
+            self.used.insert(name.to_owned());
+        }
+
+        match identifier {
+            Identifier::VarAccess { .. } => (),
+            Identifier::ArrAccess { name, index, .. } => {
+                let counter_loop = self
+                    .counter_loops
+                    .iter_mut()
+                    .rev()
+                    .find(|counter_loop| &counter_loop.counter == index);
+                if let Some(counter_loop) = counter_loop {
+                    counter_loop.indexed_arrays.insert(name.clone());
+                }
+            }
+            Identifier::ArrConstAccess { name, index, span } => {
+                if let Some(counter_loop) = self.counter_loops.last_mut() {
+                    counter_loop
+                        .constant_accesses
+                        .push((name.clone(), *index, *span));
+                }
+            }
+            Identifier::ArrExprAccess { .. } | Identifier::ArrMultiAccess { .. } => {
+                let name = identifier.name();
+                for index in identifier.index_expressions() {
+                    let index = match index {
+                        Expression::Simple {
+                            value: Value::Identifier(Identifier::VarAccess { name, .. }),
+                            ..
+                        } => name,
+                        index => {
+                            for nested in index.identifiers() {
+                                self.visit_identifier(nested);
+                            }
+                            continue;
+                        }
+                    };
+                    let counter_loop = self
+                        .counter_loops
+                        .iter_mut()
+                        .rev()
+                        .find(|counter_loop| &counter_loop.counter == index);
+                    if let Some(counter_loop) = counter_loop {
+                        counter_loop.indexed_arrays.insert(name.to_owned());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,780 @@
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
+use parser::ast::visitor::{
+    walk_command, walk_procedure, ResultCombineErr, Visitable, Visitor, VisitorResult,
+    VisitorResultVec,
+};
+use parser::ast::*;
+use std::fmt::{self, Display, Formatter};
+
+mod diagnostic;
+mod initialization;
+mod intervals;
+mod lints;
+
+pub use diagnostic::{Diagnostic, DiagnosticKind, Severity};
+use initialization::InitializationChecker;
+use intervals::CounterIndexChecker;
+use lints::Linter;
+
+#[derive(Debug)]
+pub struct SemanticVerifier {
+    globals: Vec<Declaration>,
+    locals: Vec<String>,
+    parameters: Vec<Parameter>,
+    procedures: Vec<(String, Vec<Parameter>)>,
+    procedure: Option<String>,
+    loop_depth: usize,
+}
+
+impl SemanticVerifier {
+    pub fn new() -> SemanticVerifier {
+        SemanticVerifier {
+            globals: vec![],
+            locals: vec![],
+            parameters: vec![],
+            procedures: vec![],
+            procedure: None,
+            loop_depth: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    InvalidArrayRange {
+        name: String,
+        start: i64,
+        end: i64,
+    },
+    UndeclaredVariable {
+        name: String,
+    },
+    ForCounterModification {
+        name: String,
+    },
+    InvalidVariableUsage {
+        name: String,
+    },
+    UninitializedVariable {
+        name: String,
+    },
+    DuplicateDeclaration {
+        name: String,
+    },
+    CounterShadowing {
+        name: String,
+    },
+    ArrayIndexOutOfBounds {
+        name: String,
+        index: i64,
+        start: i64,
+        end: i64,
+    },
+    UndeclaredProcedure {
+        name: String,
+    },
+    RecursiveCall {
+        name: String,
+    },
+    ArgumentCountMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    UnresolvedInclude {
+        path: String,
+    },
+    InvalidForStep {
+        name: String,
+    },
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    ArrayDimensionMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            InvalidArrayRange { name, start, end } => {
+                write!(f, "invalid array range: {}({}:{})", name, start, end)
+            }
+            UndeclaredVariable { name } => write!(f, "undeclared variable {}", name),
+            ForCounterModification { name } => {
+                write!(f, "illegal modification of for loop counter {}", name)
+            }
+            InvalidVariableUsage { name } => write!(f, "invalid variable usage: {}", name),
+            UninitializedVariable { name } => write!(f, "use of uninitialized variable {}", name),
+            DuplicateDeclaration { name } => write!(f, "duplicate declaration of {}", name),
+            CounterShadowing { name } => {
+                write!(f, "for loop counter {} shadows an existing variable", name)
+            }
+            ArrayIndexOutOfBounds {
+                name,
+                index,
+                start,
+                end,
+            } => write!(
+                f,
+                "index {} is out of bounds for array {}({}:{})",
+                index, name, start, end
+            ),
+            UndeclaredProcedure { name } => write!(f, "undeclared procedure {}", name),
+            RecursiveCall { name } => write!(f, "procedure {} calls itself", name),
+            ArgumentCountMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "procedure {} takes {} arguments, but {} were given",
+                name, expected, found
+            ),
+            UnresolvedInclude { path } => write!(f, "unresolved include of {}", path),
+            InvalidForStep { name } => write!(
+                f,
+                "step of for loop {} is 0, so its counter never moves",
+                name
+            ),
+            BreakOutsideLoop => write!(f, "BREAK outside of a loop"),
+            ContinueOutsideLoop => write!(f, "CONTINUE outside of a loop"),
+            ArrayDimensionMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "array {} has {} dimensions, but is used with {}",
+                name, expected, found
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Warning {
+    PossiblyUninitializedVariable {
+        name: String,
+    },
+    UnusedDeclaration {
+        name: String,
+    },
+    EmptyForLoop {
+        counter: String,
+    },
+    NegativeForStep {
+        counter: String,
+    },
+    ConstantIndexInCounterLoop {
+        name: String,
+        index: i64,
+        counter: String,
+    },
+    ConstantWriteInLoop,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use Warning::*;
+        match self {
+            PossiblyUninitializedVariable { name } => {
+                write!(f, "variable {} may be uninitialized", name)
+            }
+            UnusedDeclaration { name } => write!(f, "{} is declared but never used", name),
+            EmptyForLoop { counter } => write!(f, "body of for loop over {} never runs", counter),
+            NegativeForStep { counter } => write!(
+                f,
+                "step of for loop over {} is negative, the counter moves by its absolute value",
+                counter
+            ),
+            ConstantIndexInCounterLoop {
+                name,
+                index,
+                counter,
+            } => write!(
+                f,
+                "constant index {}({}) in a loop that indexes {} with its counter {}",
+                name, index, name, counter
+            ),
+            ConstantWriteInLoop => write!(
+                f,
+                "the same constant is written on every iteration of a loop"
+            ),
+        }
+    }
+}
+
+pub fn verify(program: Program) -> Result<Program, Vec<Error>> {
+    verify_with_diagnostics(program)
+        .map(|(program, _)| program)
+        .map_err(|diagnostics| {
+            diagnostics
+                .into_iter()
+                .filter_map(|diagnostic| match diagnostic.kind {
+                    DiagnosticKind::Error(error) => Some(error),
+                    DiagnosticKind::Warning(_) => None,
+                })
+                .collect()
+        })
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Report array indices that are counters of loops with constant bounds
+    /// and go out of the array's range in some iteration.
+    pub check_counter_indices: bool,
+}
+
+/// Verifies the program, returning it along with any warnings if there were no errors.
+///
+/// On failure all diagnostics are returned, including the warnings.
+pub fn verify_with_diagnostics(
+    program: Program,
+) -> Result<(Program, Vec<Diagnostic>), Vec<Diagnostic>> {
+    verify_with_options(program, Options::default())
+}
+
+pub fn verify_with_options(
+    program: Program,
+    options: Options,
+) -> Result<(Program, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let mut verifier = SemanticVerifier::new();
+    let result = program.accept(&mut verifier);
+    result.into_result().map_err(|v| v.into_vec())?;
+
+    // only well-formed programs are checked further, so that nothing is reported twice
+    let mut checker = InitializationChecker::new();
+    program.accept(&mut checker);
+    let mut linter = Linter::new();
+    program.accept(&mut linter);
+
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.append(&mut linter.diagnostics);
+
+    if options.check_counter_indices {
+        let mut index_checker = CounterIndexChecker::new();
+        program.accept(&mut index_checker);
+        diagnostics.append(&mut index_checker.diagnostics);
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        Err(diagnostics)
+    } else {
+        Ok((program, diagnostics))
+    }
+}
+
+fn with_span(
+    result: ResultCombineErr<(), VisitorResultVec<Error>>,
+    span: Span,
+) -> ResultCombineErr<(), VisitorResultVec<Diagnostic>> {
+    result
+        .into_result()
+        .map_err(|errors| {
+            errors
+                .into_vec()
+                .into_iter()
+                .map(|error| Diagnostic::error(error, span))
+                .collect::<Vec<_>>()
+                .into()
+        })
+        .into()
+}
+
+impl SemanticVerifier {
+    fn get_global(&self, name: &str) -> Option<&Declaration> {
+        self.globals.iter().find(|&global| global.name() == name)
+    }
+
+    fn get_parameter(&self, name: &str) -> Option<&Parameter> {
+        self.parameters
+            .iter()
+            .find(|&parameter| parameter.name == name)
+    }
+
+    fn get_local(&self, name: &str) -> Option<&str> {
+        self.locals
+            .iter()
+            .find(|&local| local == name)
+            .map(|s| s.as_str())
+    }
+
+    fn is_declared(&self, name: &str) -> bool {
+        self.get_global(name).is_some()
+            || self.get_parameter(name).is_some()
+            || self.get_local(name).is_some()
+    }
+
+    fn check_modification(&self, name: &str) -> Result<(), Error> {
+        self.get_local(name).map_or(Ok(()), |_| {
+            Err(Error::ForCounterModification {
+                name: name.to_owned(),
+            })
+        })
+    }
+
+    fn check_var_usage(&self, name: &str) -> Result<(), Error> {
+        self.get_global(name)
+            .map(|g| match g {
+                Declaration::Var { .. } => Ok(()),
+                Declaration::Array { .. } => Err(Error::InvalidVariableUsage {
+                    name: name.to_owned(),
+                }),
+            })
+            .unwrap_or_else(|| match self.get_parameter(name) {
+                Some(Parameter { array: true, .. }) => Err(Error::InvalidVariableUsage {
+                    name: name.to_owned(),
+                }),
+                _ => Ok(()),
+            })
+    }
+
+    fn check_array_usage(&self, name: &str) -> Result<(), Error> {
+        self.get_global(name)
+            .map(|g| match g {
+                Declaration::Var { .. } => Err(Error::InvalidVariableUsage {
+                    name: name.to_owned(),
+                }
+                .into()),
+                Declaration::Array { .. } => Ok(()),
+            })
+            .unwrap_or_else(|| {
+                let scalar = self
+                    .get_parameter(name)
+                    .map_or(self.get_local(name).is_some(), |parameter| !parameter.array);
+                if scalar {
+                    Err(Error::InvalidVariableUsage {
+                        name: name.to_owned(),
+                    })
+                } else {
+                    Ok(())
+                }
+            })
+    }
+
+    /// Array parameters can only be passed one-dimensional arrays, so they have one dimension.
+    fn check_array_dimensions(&self, name: &str, found: usize) -> Result<(), Error> {
+        let expected = match self.get_global(name) {
+            Some(Declaration::Array { dimensions, .. }) => dimensions.len(),
+            _ => 1,
+        };
+        if expected == found {
+            Ok(())
+        } else {
+            Err(Error::ArrayDimensionMismatch {
+                name: name.to_owned(),
+                expected,
+                found,
+            })
+        }
+    }
+
+    /// Ranges with `start > end` are reported along with the declaration,
+    /// so indices into them aren't checked.
+    fn check_index_bounds(name: &str, index: i64, (start, end): (i64, i64)) -> Result<(), Error> {
+        if start > end || (index >= start && index <= end) {
+            Ok(())
+        } else {
+            Err(Error::ArrayIndexOutOfBounds {
+                name: name.to_owned(),
+                index,
+                start,
+                end,
+            })
+        }
+    }
+
+    fn check_index_usage(
+        &self,
+        index: &Expression,
+    ) -> ResultCombineErr<(), VisitorResultVec<Error>> {
+        let checks = index
+            .identifiers()
+            .into_iter()
+            .map(|identifier| self.check_identifier_usage(identifier));
+        ResultCombineErr::combine_collection(checks)
+    }
+
+    fn check_identifier_usage(
+        &self,
+        identifier: &Identifier,
+    ) -> ResultCombineErr<(), VisitorResultVec<Error>> {
+        match identifier {
+            Identifier::VarAccess { name, .. } => {
+                self.check_var_usage(name).map_err(Into::into).into()
+            }
+            Identifier::ArrAccess { name, index, .. } => {
+                let main: ResultCombineErr<_, _> = self
+                    .check_array_usage(name)
+                    .and_then(|_| self.check_array_dimensions(name, 1))
+                    .map_err(Into::into)
+                    .into();
+                main.combine(
+                    self.check_var_usage(index)
+                        .map_err(|_| {
+                            Error::InvalidVariableUsage {
+                                name: name.to_owned(),
+                            }
+                            .into()
+                        })
+                        .into(),
+                )
+            }
+            Identifier::ArrConstAccess { name, index, .. } => self
+                .get_global(name)
+                .map(|g| match g {
+                    Declaration::Var { .. } => Err(Error::InvalidVariableUsage {
+                        name: name.to_owned(),
+                    }
+                    .into()),
+                    Declaration::Array { dimensions, .. } => self
+                        .check_array_dimensions(name, 1)
+                        .and_then(|_| Self::check_index_bounds(name, *index, dimensions[0]))
+                        .map_err(Into::into),
+                })
+                .unwrap_or_else(|| self.check_array_usage(name).map_err(Into::into))
+                .into(),
+            Identifier::ArrExprAccess { name, index, .. } => {
+                let main: ResultCombineErr<_, _> = self
+                    .check_array_usage(name)
+                    .and_then(|_| self.check_array_dimensions(name, 1))
+                    .map_err(Into::into)
+                    .into();
+                main.combine(self.check_index_usage(index))
+            }
+            Identifier::ArrMultiAccess { name, indices, .. } => {
+                let main = self
+                    .check_array_usage(name)
+                    .and_then(|_| self.check_array_dimensions(name, indices.len()));
+                if let Err(error) = main {
+                    return Err(error.into()).into();
+                }
+
+                let dimensions = match self.get_global(name) {
+                    Some(Declaration::Array { dimensions, .. }) => dimensions,
+                    _ => unreachable!("only declared arrays have several dimensions"),
+                };
+                let checks = indices
+                    .iter()
+                    .zip(dimensions)
+                    .map(|(index, &range)| match index {
+                        Expression::Simple {
+                            value: Value::Num { value, .. },
+                            ..
+                        } => Self::check_index_bounds(name, *value, range)
+                            .map_err(Into::into)
+                            .into(),
+                        index => self.check_index_usage(index),
+                    });
+                ResultCombineErr::combine_collection(checks)
+            }
+        }
+    }
+}
+
+impl SemanticVerifier {
+    /// Procedures can only call procedures defined before them, so recursion is impossible.
+    fn check_call(&self, name: &str, arguments: &[Argument], span: Span) -> Vec<Diagnostic> {
+        let parameters = match self
+            .procedures
+            .iter()
+            .find(|(procedure, _)| procedure == name)
+        {
+            Some((_, parameters)) => parameters,
+            None => {
+                let name = name.to_owned();
+                let error = if self.procedure.as_ref() == Some(&name) {
+                    Error::RecursiveCall { name }
+                } else {
+                    Error::UndeclaredProcedure { name }
+                };
+                return vec![Diagnostic::error(error, span)];
+            }
+        };
+
+        if parameters.len() != arguments.len() {
+            let error = Error::ArgumentCountMismatch {
+                name: name.to_owned(),
+                expected: parameters.len(),
+                found: arguments.len(),
+            };
+            return vec![Diagnostic::error(error, span)];
+        }
+
+        arguments
+            .iter()
+            .zip(parameters)
+            .filter_map(|(argument, parameter)| {
+                let name = argument.name.clone();
+                let error = if !self.is_declared(&name) {
+                    Error::UndeclaredVariable { name }
+                } else if self.check_modification(&name).is_err() {
+                    // the procedure may assign to its parameter
+                    Error::ForCounterModification { name }
+                } else {
+                    let usage = if parameter.array {
+                        self.check_array_usage(&name)
+                            .and_then(|_| self.check_array_dimensions(&name, 1))
+                    } else {
+                        self.check_var_usage(&name)
+                    };
+                    usage.err()?
+                };
+                Some(Diagnostic::error(error, argument.span))
+            })
+            .collect()
+    }
+}
+
+impl<'a> Visitor for SemanticVerifier {
+    type Result = ResultCombineErr<(), VisitorResultVec<Diagnostic>>;
+
+    fn visit_procedure(&mut self, procedure: &Procedure) -> Self::Result {
+        let mut diagnostics = vec![];
+        let name = procedure.name.clone();
+        if self
+            .procedures
+            .iter()
+            .any(|(previous, _)| previous == &name)
+        {
+            let error = Error::DuplicateDeclaration { name: name.clone() };
+            diagnostics.push(Diagnostic::error(error, procedure.span));
+        }
+
+        // parameters and declarations share a scope
+        let declarations = procedure.declarations.iter().flatten();
+        let names = procedure
+            .parameters
+            .iter()
+            .map(|parameter| (parameter.name.as_str(), parameter.span))
+            .chain(declarations.map(|declaration| (declaration.name(), declaration.span())));
+        for (i, (name, span)) in names.enumerate() {
+            let mut previous = procedure.parameters.iter().take(i);
+            if previous.any(|parameter| parameter.name == name) {
+                let error = Error::DuplicateDeclaration {
+                    name: name.to_owned(),
+                };
+                diagnostics.push(Diagnostic::error(error, span));
+            }
+        }
+        let res = Self::Result::combine_collection(
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| Err(diagnostic.into()).into()),
+        );
+
+        self.parameters = procedure.parameters.clone();
+        self.globals = vec![];
+        self.procedure = Some(name.clone());
+        let res = res.combine(walk_procedure(self, procedure));
+        self.parameters = vec![];
+        self.globals = vec![];
+        self.procedure = None;
+
+        self.procedures.push((name, procedure.parameters.clone()));
+        res
+    }
+
+    fn visit_declarations(&mut self, declarations: &Declarations) -> Self::Result {
+        let results = declarations
+            .iter()
+            .map(|declaration| self.visit(declaration));
+        let res = Self::Result::combine_collection(results);
+
+        let duplicates = declarations
+            .iter()
+            .enumerate()
+            .filter_map(|(i, declaration)| {
+                let name = declaration.name();
+                if declarations[..i]
+                    .iter()
+                    .any(|previous| previous.name() == name)
+                {
+                    let error = Error::DuplicateDeclaration {
+                        name: name.to_owned(),
+                    };
+                    Some(Err(Diagnostic::error(error, declaration.span()).into()).into())
+                } else {
+                    None
+                }
+            });
+        let res = res.combine(Self::Result::combine_collection(duplicates));
+
+        self.globals = declarations.clone();
+
+        res
+    }
+
+    fn visit_declaration(&mut self, declaration: &Declaration) -> Self::Result {
+        match declaration {
+            Declaration::Var { .. } => Self::Result::identity(),
+            Declaration::Array {
+                name,
+                dimensions,
+                span,
+            } => {
+                let ranges = dimensions.iter().map(|&(start, end)| {
+                    if start > end {
+                        let error = Error::InvalidArrayRange {
+                            name: name.clone(),
+                            start,
+                            end,
+                        };
+                        Err(Diagnostic::error(error, *span).into()).into()
+                    } else {
+                        Self::Result::identity()
+                    }
+                });
+                Self::Result::combine_collection(ranges)
+            }
+        }
+    }
+
+    fn visit_command(&mut self, command: &Command) -> Self::Result {
+        let checks = match command {
+            Command::For {
+                counter,
+                step,
+                span,
+                ..
+            } => {
+                let shadowing = if self.is_declared(counter) {
+                    let error = Error::CounterShadowing {
+                        name: counter.clone(),
+                    };
+                    Err(Diagnostic::error(error, *span).into()).into()
+                } else {
+                    Self::Result::identity()
+                };
+                // the direction comes from TO or DOWNTO, the sign of the step is ignored
+                let step = match step {
+                    Some(Value::Num { value: 0, span }) => {
+                        let error = Error::InvalidForStep {
+                            name: counter.clone(),
+                        };
+                        Err(Diagnostic::error(error, *span).into()).into()
+                    }
+                    _ => Self::Result::identity(),
+                };
+                shadowing.combine(step)
+            }
+            Command::Call {
+                name,
+                arguments,
+                span,
+            } => {
+                let diagnostics = self.check_call(name, arguments, *span);
+                Self::Result::combine_collection(
+                    diagnostics
+                        .into_iter()
+                        .map(|diagnostic| Err(diagnostic.into()).into()),
+                )
+            }
+            Command::Include { path, span } => {
+                let error = Error::UnresolvedInclude { path: path.clone() };
+                Err(Diagnostic::error(error, *span).into()).into()
+            }
+            Command::Break { span } if self.loop_depth == 0 => {
+                Err(Diagnostic::error(Error::BreakOutsideLoop, *span).into()).into()
+            }
+            Command::Continue { span } if self.loop_depth == 0 => {
+                Err(Diagnostic::error(Error::ContinueOutsideLoop, *span).into()).into()
+            }
+            _ => Self::Result::identity(),
+        };
+
+        let is_loop = matches!(
+            command,
+            Command::While { .. } | Command::Do { .. } | Command::For { .. }
+        );
+        if is_loop {
+            self.loop_depth += 1;
+        }
+        let result = checks.combine(walk_command(self, command));
+        if is_loop {
+            self.loop_depth -= 1;
+        }
+        result
+    }
+
+    fn visit_for_command(
+        &mut self,
+        counter: &str,
+        _ascending: bool,
+        from: &Value,
+        to: &Value,
+        step: Option<&Value>,
+        commands: &Commands,
+    ) -> Self::Result {
+        let result = self
+            .visit(from)
+            .combine(self.visit(to))
+            .combine(self.visit_collection(step));
+        self.locals.push(counter.to_string());
+        let result = result.combine(self.visit_commands(commands));
+        self.locals.pop();
+        result
+    }
+
+    fn visit_read_command(&mut self, targets: &[Identifier]) -> Self::Result {
+        let results: Vec<_> = targets
+            .iter()
+            .map(|target| {
+                let modification = self
+                    .check_modification(target.name())
+                    .map_err(Into::into)
+                    .into();
+                self.visit(target)
+                    .combine(with_span(modification, target.span()))
+            })
+            .collect();
+        Self::Result::combine_collection(results)
+    }
+
+    fn visit_assign_command(&mut self, target: &Identifier, expr: &Expression) -> Self::Result {
+        let modification = self
+            .check_modification(target.name())
+            .map_err(Into::into)
+            .into();
+        self.visit(target)
+            .combine(with_span(modification, target.span()))
+            .combine(self.visit(expr))
+    }
+
+    fn visit_num_value(&mut self, _: i64) -> Self::Result {
+        // nothing to be done - allow anything
+        Self::Result::identity()
+    }
+
+    #[cfg(feature = "bignum")]
+    fn visit_big_num_value(&mut self, _: &BigInt) -> Self::Result {
+        Self::Result::identity()
+    }
+
+    fn visit_identifier(&mut self, identifier: &Identifier) -> Self::Result {
+        let results = identifier.all_names().into_iter().map(|name| {
+            Some(())
+                .filter(|_| self.is_declared(name))
+                .ok_or(
+                    Error::UndeclaredVariable {
+                        name: name.to_owned(),
+                    }
+                    .into(),
+                )
+                .into()
+        });
+
+        let undeclared = ResultCombineErr::combine_collection(results);
+
+        let usage = self.check_identifier_usage(identifier);
+
+        with_span(undeclared.combine(usage), identifier.span())
+    }
+}
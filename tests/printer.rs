@@ -0,0 +1,51 @@
+use parser::ast::printer::{print, print_with_comments};
+use std::fs;
+use std::path::Path;
+use test_data::TEST_DATA;
+
+fn check_round_trip(text: &str) {
+    let program = parser::parse_ast(text).expect("invalid test program");
+    let printed = print(&program);
+    let reparsed = parser::parse_ast(&printed).expect("printed program doesn't parse");
+
+    assert_eq!(reparsed, program, "printed program:\n{}", printed);
+    assert_eq!(print(&reparsed), printed);
+}
+
+fn check_formatting_is_stable(text: &str) {
+    let (program, comments) = parser::parse_ast_with_comments(text, Default::default()).unwrap();
+    let formatted = print_with_comments(&program, &comments);
+    let (reparsed, recomments) =
+        parser::parse_ast_with_comments(&formatted, Default::default()).unwrap();
+
+    assert_eq!(reparsed, program);
+    assert_eq!(recomments.len(), comments.len());
+    assert_eq!(print_with_comments(&reparsed, &recomments), formatted);
+}
+
+#[test]
+fn round_trip_test_data() {
+    for (name, data) in TEST_DATA.iter() {
+        println!("{}", name);
+        check_round_trip(data.text);
+    }
+}
+
+#[test]
+fn round_trip_files() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data/files");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "imp") {
+            continue;
+        }
+
+        let text = fs::read_to_string(&path).unwrap();
+        // some of the files are meant to fail parsing
+        if parser::parse_ast(&text).is_ok() {
+            println!("{}", path.display());
+            check_round_trip(&text);
+            check_formatting_is_stable(&text);
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use gembiler::code_generator::intermediate;
-use gembiler::code_generator::translator::Generator;
+use gembiler::code_generator::translator::{self, Generator};
+use gembiler::code_generator::{intermediate, peephole};
 use test_data::TEST_DATA;
+use virtual_machine::instruction::Instruction as VmInstruction;
 use virtual_machine::interpreter;
 use virtual_machine::interpreter::{memval, MemoryValue};
 
@@ -75,17 +76,39 @@ where
 }
 
 fn check_success(code: &str, input: Vec<MemoryValue>, expected: &[MemoryValue]) {
-    let ast = parser::parse_ast(code);
+    check_success_with_options(code, parser::Options::default(), input, expected)
+}
+
+fn check_success_with_options(
+    code: &str,
+    options: parser::Options,
+    input: Vec<MemoryValue>,
+    expected: &[MemoryValue],
+) {
+    let ast = parser::parse_ast_with_options(code, options);
     assert!(ast.is_ok());
     let program = ast.unwrap();
 
-    let ir = intermediate::generate(&program);
-    assert!(ir.is_ok());
+    // optimizations must not change what the program does
+    for &optimize in &[false, true] {
+        let ir = intermediate::generate(&program);
+        assert!(ir.is_ok());
+        let mut ir = ir.unwrap();
+        if optimize {
+            intermediate::optimizations::optimize(&mut ir);
+        }
+
+        let mut translated = Generator::new(ir).translate();
+        if optimize {
+            translated = peephole::optimize(translated);
+        }
+        check_output(translated, input.clone(), expected);
+    }
+}
 
+fn check_output(translated: Vec<VmInstruction>, input: Vec<MemoryValue>, expected: &[MemoryValue]) {
     println!("{:#?}", DebugMultilineCollectionPrinter(&input));
 
-    let generator = Generator::new(ir.unwrap());
-    let translated = generator.translate();
     // println!("{:#?}", translated);
     // let (run_result, logs) = virtual_machine::interpreter::run_debug(translated, input, false);
     let run_result = virtual_machine::interpreter::run(translated, input);
@@ -311,3 +334,753 @@ fn bonus2() {
 
     check_success(code, input, expected.as_slice());
 }
+
+#[test]
+fn do_while() {
+    let code = r#"
+        DECLARE
+            n
+        BEGIN
+            READ n;
+            DO
+                WRITE n;
+                n ASSIGN n MINUS 1;
+            WHILE n GE 0 ENDDO
+        END
+    "#;
+
+    let input = memval_vec(&[3]);
+    let expected = memval_vec(&[3, 2, 1]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn do_while_runs_at_least_once() {
+    let code = r#"
+        DECLARE
+            n
+        BEGIN
+            READ n;
+            DO
+                WRITE n;
+                n ASSIGN n PLUS 1;
+            WHILE n LE 0 ENDDO
+            WRITE n;
+        END
+    "#;
+
+    let input = memval_vec(&[5]);
+    let expected = memval_vec(&[5, 6]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn nested_do_while() {
+    let code = r#"
+        DECLARE
+            i, j
+        BEGIN
+            i ASSIGN 0;
+            DO
+                j ASSIGN i;
+                DO
+                    WRITE j;
+                    j ASSIGN j MINUS 1;
+                WHILE j GEQ 0 ENDDO
+                i ASSIGN i PLUS 1;
+            WHILE i LE 3 ENDDO
+        END
+    "#;
+
+    let input = memval_vec(&[]);
+    let expected = memval_vec(&[0, 1, 0, 2, 1, 0]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn nested_expressions() {
+    let code = r#"
+        DECLARE
+            a, b, c, t(0:2)
+        BEGIN
+            READ a;
+            READ b;
+            READ c;
+            t(0) ASSIGN a PLUS b TIMES c;
+            t(1) ASSIGN (a PLUS b) TIMES c;
+            t(2) ASSIGN a MINUS b MINUS c;
+            WRITE t(0);
+            WRITE t(1);
+            WRITE t(2);
+            a ASSIGN a MINUS (b MINUS c);
+            WRITE a;
+            a ASSIGN (t(0) DIV c PLUS t(1) MOD 7) TIMES (c MINUS t(2) DIV 2);
+            WRITE a;
+            b ASSIGN (a MINUS 100) DIV 10;
+            WRITE b;
+        END
+    "#;
+
+    let input = memval_vec(&[2, 3, 4]);
+    let expected = memval_vec(&[14, 20, -5, 3, 63, -4]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn deeply_nested_expressions() {
+    let code = r#"
+        DECLARE
+            a, b
+        BEGIN
+            READ a;
+            FOR i FROM 1 TO 3 DO
+                b ASSIGN (1 PLUS (i TIMES (a PLUS (i MINUS 1)))) MOD ((a PLUS i) TIMES 2 MINUS 1);
+                WRITE b;
+            ENDFOR
+        END
+    "#;
+
+    // a = 5: (1 + i * (4 + i)) mod (2 * (5 + i) - 1)
+    let input = memval_vec(&[5]);
+    let expected = memval_vec(&[6, 0, 7]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn logical_conditions() {
+    let code = r#"
+        DECLARE
+            a, b
+        BEGIN
+            FOR i FROM 0 TO 1 DO
+                FOR j FROM 0 TO 1 DO
+                    a ASSIGN i;
+                    b ASSIGN j;
+                    IF a EQ 1 AND b EQ 1 THEN WRITE 1; ELSE WRITE 0; ENDIF
+                    IF a EQ 1 OR b EQ 1 THEN WRITE 1; ELSE WRITE 0; ENDIF
+                    IF NOT a EQ 1 THEN WRITE 1; ENDIF
+                    IF NOT (a EQ 1 OR b GE 0) OR a NEQ b AND NOT b LEQ 0 THEN
+                        WRITE 1;
+                    ELSE
+                        WRITE 0;
+                    ENDIF
+                ENDFOR
+            ENDFOR
+        END
+    "#;
+
+    let input = memval_vec(&[]);
+    #[rustfmt::skip]
+    let expected = memval_vec(&[
+        0, 0, 1, 1,
+        0, 1, 1, 1,
+        0, 1, 0,
+        1, 1, 0,
+    ]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn logical_loop_conditions() {
+    let code = r#"
+        DECLARE
+            n, m
+        BEGIN
+            READ n;
+            m ASSIGN 0;
+            WHILE n GE 0 AND NOT m EQ 3 DO
+                n ASSIGN n MINUS 1;
+                m ASSIGN m PLUS 1;
+            ENDWHILE
+            WRITE n;
+            DO
+                n ASSIGN n PLUS 1;
+            WHILE n LE 0 OR (n LE 10 AND n NEQ 4) ENDDO
+            WRITE n;
+        END
+    "#;
+
+    let input = memval_vec(&[2]);
+    let expected = memval_vec(&[0, 4]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn multiple_targets() {
+    let code = r#"
+        DECLARE
+            n, a, t(1:3)
+        BEGIN
+            READ n, t(n), a;
+            WRITE a, t(n), n, 7;
+        END
+    "#;
+
+    let input = memval_vec(&[2, 10, 20]);
+    let expected = memval_vec(&[20, 10, 2, 7]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn for_loop_steps() {
+    let code = r#"
+        DECLARE
+            n, s
+        BEGIN
+            READ n;
+            READ s;
+            FOR i FROM 0 TO n STEP 3 DO
+                WRITE i;
+            ENDFOR
+            FOR i FROM n DOWNTO 0 STEP s DO
+                WRITE i;
+                s ASSIGN 100;
+            ENDFOR
+            FOR i FROM 1 TO 2 STEP 1 DO
+                WRITE i;
+            ENDFOR
+            FOR i FROM 5 DOWNTO 0 STEP -2 DO
+                WRITE i;
+            ENDFOR
+            s ASSIGN -4;
+            FOR i FROM 1 TO n STEP s DO
+                WRITE i;
+            ENDFOR
+        END
+    "#;
+
+    // negative steps move the counter the way TO or DOWNTO say
+    let input = memval_vec(&[7, 2]);
+    let expected = memval_vec(&[0, 3, 6, 7, 5, 3, 1, 1, 2, 5, 3, 1, 1, 5]);
+
+    check_success(code, input, expected.as_slice());
+
+    // a loop with a step of 0 is skipped
+    let input = memval_vec(&[7, 0]);
+    let expected = memval_vec(&[0, 3, 6, 1, 2, 5, 3, 1, 1, 5]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn unit_for_step_uses_inc_and_dec() {
+    let translate = |code: &str| {
+        let program = parser::parse_ast(code).expect("invalid test program");
+        let ir = intermediate::generate(&program).expect("IR generation failed");
+        Generator::new(ir).translate()
+    };
+
+    let implicit = translate("DECLARE n BEGIN READ n; FOR i FROM n TO 9 DO WRITE i; ENDFOR END");
+    let explicit =
+        translate("DECLARE n BEGIN READ n; FOR i FROM n TO 9 STEP 1 DO WRITE i; ENDFOR END");
+    assert_eq!(explicit, implicit);
+    assert!(explicit.contains(&VmInstruction::Inc));
+
+    let descending =
+        translate("DECLARE n BEGIN READ n; FOR i FROM n DOWNTO 0 STEP 1 DO WRITE i; ENDFOR END");
+    assert!(descending.contains(&VmInstruction::Dec));
+
+    let negative =
+        translate("DECLARE n BEGIN READ n; FOR i FROM n DOWNTO 0 STEP -1 DO WRITE i; ENDFOR END");
+    assert_eq!(negative, descending);
+}
+
+#[test]
+fn break_and_continue() {
+    let code = r#"
+        DECLARE
+            n, m, s
+        BEGIN
+            READ n;
+            m ASSIGN 0;
+            WHILE m LE n DO
+                m ASSIGN m PLUS 1;
+                IF m EQ 2 THEN
+                    CONTINUE;
+                ENDIF
+                IF m EQ 4 THEN
+                    BREAK;
+                ENDIF
+                WRITE m;
+            ENDWHILE
+            s ASSIGN 0;
+            FOR i FROM 1 TO n STEP 2 DO
+                IF i EQ 3 THEN
+                    CONTINUE;
+                ENDIF
+                FOR j FROM 1 TO n DO
+                    IF j GE i THEN
+                        BREAK;
+                    ENDIF
+                    s ASSIGN s PLUS 1;
+                ENDFOR
+                WRITE i;
+            ENDFOR
+            WRITE s;
+            DO
+                n ASSIGN n MINUS 1;
+                IF n GE 5 THEN
+                    CONTINUE;
+                ENDIF
+                WRITE n;
+                BREAK;
+            WHILE n GE 0 ENDDO
+        END
+    "#;
+
+    let input = memval_vec(&[7]);
+    let expected = memval_vec(&[1, 3, 1, 5, 7, 13, 5]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn multi_dimensional_arrays() {
+    let code = r#"
+        PROCEDURE diagonal(k) IS
+        DECLARE
+            m(0:1, 0:1)
+        BEGIN
+            m(k, k) ASSIGN k PLUS 5;
+            WRITE m(k, k);
+        END
+
+        DECLARE
+            a(0:2, -1:2), b(1:3, 0:2), n, s, c
+        BEGIN
+            READ n;
+            FOR i FROM 0 TO 2 DO
+                FOR j FROM -1 TO 2 DO
+                    a(i, j) ASSIGN i TIMES 10 PLUS j;
+                ENDFOR
+            ENDFOR
+            FOR i FROM 1 TO 3 DO
+                FOR j FROM 0 TO 2 DO
+                    b(i, j) ASSIGN a(j, n);
+                ENDFOR
+            ENDFOR
+            WRITE a(2, -1), a(1, n), b(3, 1);
+            s ASSIGN 0;
+            FOR i FROM 1 TO 3 DO
+                FOR j FROM 0 TO 2 DO
+                    s ASSIGN s PLUS b(i, j);
+                ENDFOR
+            ENDFOR
+            WRITE s;
+            READ a(n, 0), b(n, n);
+            WRITE a(2, 0), b(2, 2);
+            IF a(n, 0) GE b(n, n) THEN
+                WRITE 1;
+            ELSE
+                WRITE 0;
+            ENDIF
+            c ASSIGN 1;
+            diagonal(c);
+        END
+    "#;
+
+    let input = memval_vec(&[2, 5, 8]);
+    let expected = memval_vec(&[19, 12, 12, 108, 5, 8, 0, 6]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+#[test]
+fn power_of_two_dimensions_use_shift() {
+    let translate = |code: &str| {
+        let program = parser::parse_ast(code).expect("invalid test program");
+        let ir = intermediate::generate(&program).expect("IR generation failed");
+        Generator::new(ir).translate()
+    };
+    let is_shift = |instruction: &VmInstruction| matches!(instruction, VmInstruction::Shift(_));
+
+    let shifted = translate("DECLARE t(0:2, 1:8), n BEGIN READ n; t(n, n) ASSIGN 1; END");
+    assert!(shifted.iter().any(is_shift));
+
+    // a size of 7 takes the whole multiplication loop
+    let multiplied = translate("DECLARE t(0:2, 1:7), n BEGIN READ n; t(n, n) ASSIGN 1; END");
+    assert!(shifted.len() < multiplied.len());
+
+    // constant indices are folded into a single address
+    let constant = translate("DECLARE t(0:2, 1:7) BEGIN t(2, 3) ASSIGN 1; END");
+    assert!(!constant.iter().any(is_shift));
+}
+
+#[test]
+fn stored_values_are_not_reloaded() {
+    let code = "DECLARE a, b BEGIN READ a; b ASSIGN a; WRITE b; END";
+    let program = parser::parse_ast(code).expect("invalid test program");
+    let ir = intermediate::generate(&program).expect("IR generation failed");
+    let translated = Generator::new(ir).translate();
+
+    // a and b are still in the accumulator when they are read
+    assert!(!translated
+        .iter()
+        .any(|instruction| matches!(instruction, VmInstruction::Load(_))));
+
+    let (_cost, output) = interpreter::run(translated, memval_vec(&[7])).expect("program failed");
+    assert_eq!(output, memval_vec(&[7]));
+}
+
+#[test]
+fn constant_operations_are_folded() {
+    let code = "DECLARE a, b BEGIN a ASSIGN 5; b ASSIGN a TIMES 8; WRITE b; END";
+    let translate = |optimize: bool| {
+        let program = parser::parse_ast(code).expect("invalid test program");
+        let mut ir = intermediate::generate(&program).expect("IR generation failed");
+        if optimize {
+            intermediate::optimizations::optimize(&mut ir);
+        }
+        Generator::new(ir).translate()
+    };
+
+    let (optimized, unoptimized) = (translate(true), translate(false));
+    assert!(!optimized
+        .iter()
+        .any(|instruction| matches!(instruction, VmInstruction::Jzero(_))));
+    assert!(optimized.len() < unoptimized.len());
+
+    let (_cost, output) = interpreter::run(optimized, vec![]).expect("program failed");
+    assert_eq!(output, memval_vec(&[40]));
+}
+
+#[test]
+fn expression_indices() {
+    let code = r#"
+        PROCEDURE shift(T t, n) IS
+        BEGIN
+            FOR i FROM n DOWNTO 1 DO
+                t(i) ASSIGN t(i MINUS 1);
+            ENDFOR
+            t(0) ASSIGN 0;
+        END
+
+        DECLARE
+            t(0:5), p(1:5), m(0:2, 0:2), n, k
+        BEGIN
+            READ n;
+            FOR i FROM 0 TO n DO
+                t(i) ASSIGN i TIMES i;
+            ENDFOR
+            FOR i FROM 1 TO 5 DO
+                p(i) ASSIGN 6 MINUS i;
+            ENDFOR
+            WRITE t(n MINUS 1), t(p(n)), t(p(p(n)) MINUS 1);
+            t(p(5)) ASSIGN t(n) PLUS t(p(n TIMES 1));
+            WRITE t(1);
+            k ASSIGN 1;
+            m(k PLUS 1, p(4)) ASSIGN 7;
+            WRITE m(2, 2);
+            READ t(p(3)), t(n PLUS 1);
+            WRITE t(3), t(5);
+            IF t(p(n)) LE t(n MINUS 1) THEN
+                WRITE 1;
+            ENDIF
+            shift(t, n);
+            WRITE t(n), t(1 PLUS 1);
+        END
+    "#;
+
+    let input = memval_vec(&[4, 11, 12]);
+    let expected = memval_vec(&[9, 4, 9, 20, 7, 11, 12, 1, 11, 20]);
+
+    check_success(code, input, expected.as_slice());
+}
+
+const PROCEDURES: &str = r#"
+    PROCEDURE swap(a, b) IS
+    DECLARE
+        tmp
+    BEGIN
+        tmp ASSIGN a;
+        a ASSIGN b;
+        b ASSIGN tmp;
+    END
+
+    PROCEDURE double(a) IS
+    BEGIN
+        a ASSIGN a PLUS a;
+    END
+
+    PROCEDURE quadruple(a) IS
+    BEGIN
+        double(a);
+        double(a);
+    END
+
+    PROCEDURE set(T t, k, v) IS
+    BEGIN
+        t(k) ASSIGN v;
+        k ASSIGN k PLUS 1;
+    END
+
+    PROCEDURE sum(T t, n, s) IS
+    BEGIN
+        s ASSIGN t(0);
+        FOR i FROM 1 TO n DO
+            s ASSIGN s PLUS t(i);
+        ENDFOR
+    END
+
+    PROCEDURE total(T t, n, s) IS
+    BEGIN
+        sum(t, n, s);
+    END
+
+    PROCEDURE spread(T t, v) IS
+    BEGIN
+        v ASSIGN t(1) MINUS t(-1);
+    END
+
+    DECLARE
+        x, y, k, m, s, t(0:3), u(-2:2)
+    BEGIN
+        READ x;
+        READ y;
+        swap(x, y);
+        WRITE x;
+        WRITE y;
+        quadruple(x);
+        WRITE x;
+        k ASSIGN 0;
+        WHILE k LE 4 DO
+            set(t, k, x);
+            double(x);
+        ENDWHILE
+        m ASSIGN 3;
+        total(t, m, s);
+        WRITE s;
+        FOR i FROM -2 TO 2 DO
+            u(i) ASSIGN i TIMES 10;
+        ENDFOR
+        spread(u, s);
+        WRITE s;
+        WRITE k;
+        WRITE x;
+    END
+"#;
+
+#[test]
+fn procedures() {
+    let input = memval_vec(&[3, 5]);
+    let expected = memval_vec(&[5, 3, 20, 300, 20, 4, 320]);
+
+    check_success(PROCEDURES, input, expected.as_slice());
+}
+
+#[test]
+fn procedures_with_bounds_checks() {
+    let output = run_with_bounds_checks(PROCEDURES, memval_vec(&[3, 5]));
+
+    assert_eq!(output, memval_vec(&[5, 3, 20, 300, 20, 4, 320]));
+}
+
+#[cfg(feature = "bignum")]
+#[test]
+fn big_literals() {
+    let code = r#"
+        DECLARE
+            a, b
+        BEGIN
+            a ASSIGN 123456789012345678901234567890;
+            b ASSIGN a MINUS 1;
+            WRITE a;
+            WRITE b;
+            WRITE -98765432109876543210;
+        END
+    "#;
+
+    let options = parser::Options { big_literals: true };
+    let input = memval_vec(&[]);
+    let expected = big_memval_vec::<_, ()>(vec![
+        "123456789012345678901234567890",
+        "123456789012345678901234567889",
+        "-98765432109876543210",
+    ]);
+
+    check_success_with_options(code, options, input, expected.as_slice());
+}
+
+fn run_with_bounds_checks(code: &str, input: Vec<MemoryValue>) -> Vec<MemoryValue> {
+    let program = parser::parse_ast(code).expect("invalid test program");
+    let run = |optimize: bool| {
+        let options = intermediate::Options {
+            bounds_checks: true,
+        };
+        let mut ir =
+            intermediate::generate_with_options(&program, options).expect("IR generation failed");
+        if optimize {
+            intermediate::optimizations::optimize(&mut ir);
+        }
+
+        let options = translator::Options {
+            bounds_checks: true,
+        };
+        let mut translated = Generator::with_options(ir, options).translate();
+        if optimize {
+            translated = peephole::optimize(translated);
+        }
+        let (_cost, output) = interpreter::run(translated, input.clone()).expect("program failed");
+        output
+    };
+
+    // accesses that can go out of bounds must survive the optimizations
+    let output = run(false);
+    assert_eq!(run(true), output);
+
+    output
+}
+
+const BOUNDS_CHECKED: &str = r#"
+    DECLARE
+        t(3:5), n, m
+    BEGIN
+        FOR i FROM 3 TO 5 DO
+            t(i) ASSIGN i;
+        ENDFOR
+        READ n;
+        READ m;
+        t(n) ASSIGN 7;
+        WRITE t(m);
+        WRITE t(n);
+    END
+"#;
+
+#[test]
+fn bounds_checks_multi_dimensional() {
+    let code = r#"
+        DECLARE
+            t(0:1, 0:2), n
+        BEGIN
+            READ n;
+            t(n, n) ASSIGN 4;
+            WRITE t(n, n);
+        END
+    "#;
+    let sentinel = translator::OUT_OF_BOUNDS_SENTINEL;
+
+    let output = run_with_bounds_checks(code, memval_vec(&[1]));
+    assert_eq!(output, memval_vec(&[4]));
+
+    let output = run_with_bounds_checks(code, memval_vec(&[2]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+}
+
+#[test]
+fn bounds_checks_every_dimension() {
+    // t(1, 3) is out of bounds, though its linear index is that of t(2, 0)
+    let code = r#"
+        DECLARE
+            t(0:2, 0:2), n
+        BEGIN
+            READ n;
+            t(2, 0) ASSIGN 0;
+            t(1, n) ASSIGN 7;
+            WRITE t(2, 0);
+        END
+    "#;
+    let sentinel = translator::OUT_OF_BOUNDS_SENTINEL;
+
+    let output = run_with_bounds_checks(code, memval_vec(&[2]));
+    assert_eq!(output, memval_vec(&[0]));
+
+    let output = run_with_bounds_checks(code, memval_vec(&[3]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+}
+
+#[test]
+fn bounds_checks_array_parameters() {
+    // the bounds are passed on to procedures along with the array
+    let code = r#"
+        PROCEDURE p(T a, i) IS
+        BEGIN
+            a(i) ASSIGN 7;
+        END
+        PROCEDURE q(T b, j) IS
+        BEGIN
+            p(b, j);
+            WRITE b(j);
+        END
+        DECLARE
+            t(3:5), u(0:2), n
+        BEGIN
+            READ n;
+            u(0) ASSIGN 0;
+            q(t, n);
+            WRITE u(0);
+        END
+    "#;
+    let sentinel = translator::OUT_OF_BOUNDS_SENTINEL;
+
+    let output = run_with_bounds_checks(code, memval_vec(&[4]));
+    assert_eq!(output, memval_vec(&[7, 0]));
+
+    let output = run_with_bounds_checks(code, memval_vec(&[6]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+
+    let output = run_with_bounds_checks(code, memval_vec(&[2]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+}
+
+#[test]
+fn bounds_checks_nested_indices() {
+    let code = r#"
+        DECLARE
+            t(0:2), u(0:2), n
+        BEGIN
+            FOR i FROM 0 TO 2 DO
+                t(i) ASSIGN i;
+                u(i) ASSIGN i PLUS 1;
+            ENDFOR
+            READ n;
+            WRITE t(u(n));
+        END
+    "#;
+    let sentinel = translator::OUT_OF_BOUNDS_SENTINEL;
+
+    let output = run_with_bounds_checks(code, memval_vec(&[1]));
+    assert_eq!(output, memval_vec(&[2]));
+
+    let output = run_with_bounds_checks(code, memval_vec(&[2]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+
+    let output = run_with_bounds_checks(code, memval_vec(&[3]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+}
+
+#[test]
+fn bounds_checks_in_bounds() {
+    let output = run_with_bounds_checks(BOUNDS_CHECKED, memval_vec(&[5, 3]));
+
+    assert_eq!(output, memval_vec(&[3, 7]));
+}
+
+#[test]
+fn bounds_checks_store_out_of_bounds() {
+    let sentinel = translator::OUT_OF_BOUNDS_SENTINEL;
+
+    let output = run_with_bounds_checks(BOUNDS_CHECKED, memval_vec(&[6, 3]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+
+    let output = run_with_bounds_checks(BOUNDS_CHECKED, memval_vec(&[2, 3]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+}
+
+#[test]
+fn bounds_checks_load_out_of_bounds() {
+    let sentinel = translator::OUT_OF_BOUNDS_SENTINEL;
+
+    let output = run_with_bounds_checks(BOUNDS_CHECKED, memval_vec(&[4, -1]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+
+    let output = run_with_bounds_checks(BOUNDS_CHECKED, memval_vec(&[4, 100]));
+    assert_eq!(output, memval_vec(&[sentinel]));
+}
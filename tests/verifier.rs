@@ -1,13 +1,37 @@
-use ::gembiler::verifier::{verify, Error};
+use ::gembiler::verifier::{
+    verify, verify_with_diagnostics, verify_with_options, DiagnosticKind, Error, Options, Warning,
+};
 use ::parser::ast::*;
 
+fn parse(text: &str) -> Program {
+    parser::parse_ast(text).expect("invalid test program")
+}
+
+fn warnings(program: Program) -> Vec<Warning> {
+    let (_, diagnostics) = verify_with_diagnostics(program).expect("verification failed");
+    diagnostics
+        .into_iter()
+        .map(|diagnostic| match diagnostic.kind {
+            DiagnosticKind::Warning(warning) => warning,
+            DiagnosticKind::Error(error) => panic!("unexpected error: {}", error),
+        })
+        .collect()
+}
+
 #[test]
 fn no_declarations_ok() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: None,
         commands: vec![Command::Write {
-            value: Value::Num(1),
+            values: vec![Value::Num {
+                value: 1,
+                span: Span::default(),
+            }],
+            span: Span::default(),
         }],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -18,12 +42,17 @@ fn no_declarations_ok() {
 #[test]
 fn no_declarations_err_undeclared() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: None,
         commands: vec![Command::Read {
-            target: Identifier::VarAccess {
+            targets: vec![Identifier::VarAccess {
                 name: String::from("a"),
-            },
+                span: Span::default(),
+            }],
+            span: Span::default(),
         }],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -37,19 +66,26 @@ fn no_declarations_err_undeclared() {
 #[test]
 fn no_declarations_err_undeclared_all() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: None,
         commands: vec![
             Command::Read {
-                target: Identifier::VarAccess {
+                targets: vec![Identifier::VarAccess {
                     name: String::from("a"),
-                },
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             },
             Command::Read {
-                target: Identifier::VarAccess {
+                targets: vec![Identifier::VarAccess {
                     name: String::from("b"),
-                },
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             },
         ],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -68,18 +104,31 @@ fn no_declarations_err_undeclared_all() {
 #[test]
 fn no_declarations_for_ok() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: None,
         commands: vec![Command::For {
             counter: "i".to_string(),
             ascending: false,
-            from: Value::Num(1),
-            to: Value::Num(10),
+            from: Value::Num {
+                value: 1,
+                span: Span::default(),
+            },
+            to: Value::Num {
+                value: 10,
+                span: Span::default(),
+            },
+            step: None,
             commands: vec![Command::Write {
-                value: Value::Identifier(Identifier::VarAccess {
+                values: vec![Value::Identifier(Identifier::VarAccess {
                     name: String::from("i"),
-                }),
+                    span: Span::default(),
+                })],
+                span: Span::default(),
             }],
+            span: Span::default(),
         }],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -90,23 +139,40 @@ fn no_declarations_for_ok() {
 #[test]
 fn no_declarations_for_err() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: None,
         commands: vec![
             Command::For {
                 counter: "i".to_string(),
                 ascending: false,
-                from: Value::Num(1),
-                to: Value::Num(10),
+                from: Value::Num {
+                    value: 1,
+                    span: Span::default(),
+                },
+                to: Value::Num {
+                    value: 10,
+                    span: Span::default(),
+                },
+                step: None,
                 commands: vec![Command::Write {
-                    value: Value::Num(1),
+                    values: vec![Value::Num {
+                        value: 1,
+                        span: Span::default(),
+                    }],
+                    span: Span::default(),
                 }],
+                span: Span::default(),
             },
             Command::Write {
-                value: Value::Identifier(Identifier::VarAccess {
+                values: vec![Value::Identifier(Identifier::VarAccess {
                     name: String::from("i"),
-                }),
+                    span: Span::default(),
+                })],
+                span: Span::default(),
             },
         ],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -121,31 +187,54 @@ fn no_declarations_for_err() {
 #[test]
 fn no_declarations_nested_for_ok() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: None,
         commands: vec![Command::For {
             counter: "i".to_string(),
             ascending: false,
-            from: Value::Num(1),
-            to: Value::Num(10),
+            from: Value::Num {
+                value: 1,
+                span: Span::default(),
+            },
+            to: Value::Num {
+                value: 10,
+                span: Span::default(),
+            },
+            step: None,
             commands: vec![Command::For {
                 counter: "j".to_string(),
                 ascending: false,
-                from: Value::Num(101),
-                to: Value::Num(110),
+                from: Value::Num {
+                    value: 101,
+                    span: Span::default(),
+                },
+                to: Value::Num {
+                    value: 110,
+                    span: Span::default(),
+                },
+                step: None,
                 commands: vec![
                     Command::Write {
-                        value: Value::Identifier(Identifier::VarAccess {
+                        values: vec![Value::Identifier(Identifier::VarAccess {
                             name: String::from("i"),
-                        }),
+                            span: Span::default(),
+                        })],
+                        span: Span::default(),
                     },
                     Command::Write {
-                        value: Value::Identifier(Identifier::VarAccess {
+                        values: vec![Value::Identifier(Identifier::VarAccess {
                             name: String::from("j"),
-                        }),
+                            span: Span::default(),
+                        })],
+                        span: Span::default(),
                     },
                 ],
+                span: Span::default(),
             }],
+            span: Span::default(),
         }],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -156,26 +245,35 @@ fn no_declarations_nested_for_ok() {
 #[test]
 fn simple_declarations_ok() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: Some(vec![
             Declaration::Var {
                 name: String::from("a"),
+                span: Span::default(),
             },
             Declaration::Var {
                 name: String::from("b"),
+                span: Span::default(),
             },
         ]),
         commands: vec![
             Command::Read {
-                target: Identifier::VarAccess {
+                targets: vec![Identifier::VarAccess {
                     name: String::from("a"),
-                },
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             },
             Command::Read {
-                target: Identifier::VarAccess {
+                targets: vec![Identifier::VarAccess {
                     name: String::from("b"),
-                },
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             },
         ],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -186,26 +284,36 @@ fn simple_declarations_ok() {
 #[test]
 fn simple_declarations_err() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: Some(vec![Declaration::Var {
             name: String::from("a"),
+            span: Span::default(),
         }]),
         commands: vec![
             Command::Read {
-                target: Identifier::VarAccess {
+                targets: vec![Identifier::VarAccess {
                     name: String::from("a"),
-                },
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             },
             Command::Write {
-                value: Value::Identifier(Identifier::VarAccess {
+                values: vec![Value::Identifier(Identifier::VarAccess {
                     name: String::from("a"),
-                }),
+                    span: Span::default(),
+                })],
+                span: Span::default(),
             },
             Command::Read {
-                target: Identifier::VarAccess {
+                targets: vec![Identifier::VarAccess {
                     name: String::from("b"),
-                },
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             },
         ],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -219,35 +327,45 @@ fn simple_declarations_err() {
 #[test]
 fn arr_declarations_ok() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: Some(vec![
             Declaration::Var {
                 name: String::from("a"),
+                span: Span::default(),
             },
             Declaration::Array {
                 name: String::from("arr"),
-                start: 0,
-                end: 10,
+                dimensions: vec![(0, 10)],
+                span: Span::default(),
             },
         ]),
         commands: vec![
             Command::Read {
-                target: Identifier::VarAccess {
+                targets: vec![Identifier::VarAccess {
                     name: String::from("a"),
-                },
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             },
             Command::Read {
-                target: Identifier::ArrAccess {
+                targets: vec![Identifier::ArrAccess {
                     name: String::from("arr"),
                     index: String::from("a"),
-                },
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             },
             Command::Write {
-                value: Value::Identifier(Identifier::ArrAccess {
+                values: vec![Value::Identifier(Identifier::ArrAccess {
                     name: String::from("arr"),
                     index: String::from("a"),
-                }),
+                    span: Span::default(),
+                })],
+                span: Span::default(),
             },
         ],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -258,21 +376,28 @@ fn arr_declarations_ok() {
 #[test]
 fn arr_declarations_err() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: None,
         commands: vec![
             Command::Read {
-                target: Identifier::ArrConstAccess {
+                targets: vec![Identifier::ArrConstAccess {
                     name: String::from("arr"),
                     index: 0,
-                },
+                    span: Span::default(),
+                }],
+                span: Span::default(),
             },
             Command::Write {
-                value: Value::Identifier(Identifier::ArrAccess {
+                values: vec![Value::Identifier(Identifier::ArrAccess {
                     name: String::from("arr"),
                     index: String::from("a"),
-                }),
+                    span: Span::default(),
+                })],
+                span: Span::default(),
             },
         ],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -294,25 +419,40 @@ fn arr_declarations_err() {
 #[test]
 fn no_declarations_for_modification_err() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: None,
         commands: vec![
             Command::For {
                 counter: "i".to_string(),
                 ascending: false,
-                from: Value::Num(1),
-                to: Value::Num(10),
+                from: Value::Num {
+                    value: 1,
+                    span: Span::default(),
+                },
+                to: Value::Num {
+                    value: 10,
+                    span: Span::default(),
+                },
+                step: None,
                 commands: vec![Command::Read {
-                    target: Identifier::VarAccess {
+                    targets: vec![Identifier::VarAccess {
                         name: String::from("i"),
-                    },
+                        span: Span::default(),
+                    }],
+                    span: Span::default(),
                 }],
+                span: Span::default(),
             },
             Command::Write {
-                value: Value::Identifier(Identifier::VarAccess {
+                values: vec![Value::Identifier(Identifier::VarAccess {
                     name: String::from("i"),
-                }),
+                    span: Span::default(),
+                })],
+                span: Span::default(),
             },
         ],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -332,31 +472,54 @@ fn no_declarations_for_modification_err() {
 #[test]
 fn no_declarations_nested_for_modification_err() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: None,
         commands: vec![Command::For {
             counter: "i".to_string(),
             ascending: false,
-            from: Value::Num(1),
-            to: Value::Num(10),
+            from: Value::Num {
+                value: 1,
+                span: Span::default(),
+            },
+            to: Value::Num {
+                value: 10,
+                span: Span::default(),
+            },
+            step: None,
             commands: vec![Command::For {
                 counter: "j".to_string(),
                 ascending: false,
-                from: Value::Num(101),
-                to: Value::Num(110),
+                from: Value::Num {
+                    value: 101,
+                    span: Span::default(),
+                },
+                to: Value::Num {
+                    value: 110,
+                    span: Span::default(),
+                },
+                step: None,
                 commands: vec![
                     Command::Read {
-                        target: Identifier::VarAccess {
+                        targets: vec![Identifier::VarAccess {
                             name: String::from("i"),
-                        },
+                            span: Span::default(),
+                        }],
+                        span: Span::default(),
                     },
                     Command::Read {
-                        target: Identifier::VarAccess {
+                        targets: vec![Identifier::VarAccess {
                             name: String::from("j"),
-                        },
+                            span: Span::default(),
+                        }],
+                        span: Span::default(),
                     },
                 ],
+                span: Span::default(),
             }],
+            span: Span::default(),
         }],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -376,44 +539,67 @@ fn no_declarations_nested_for_modification_err() {
 #[test]
 fn for_complex_err() {
     let program = Program {
+        files: vec![],
+        procedures: vec![],
         declarations: Some(vec![Declaration::Array {
             name: String::from("arr"),
-            start: 0,
-            end: 10,
+            dimensions: vec![(0, 10)],
+            span: Span::default(),
         }]),
         commands: vec![
             Command::For {
                 counter: "i".to_string(),
                 ascending: false,
-                from: Value::Num(1),
-                to: Value::Num(10),
+                from: Value::Num {
+                    value: 1,
+                    span: Span::default(),
+                },
+                to: Value::Num {
+                    value: 10,
+                    span: Span::default(),
+                },
+                step: None,
                 commands: vec![
                     Command::Read {
-                        target: Identifier::VarAccess {
+                        targets: vec![Identifier::VarAccess {
                             name: String::from("i"),
-                        },
+                            span: Span::default(),
+                        }],
+                        span: Span::default(),
                     },
                     Command::Assign {
                         target: Identifier::VarAccess {
                             name: String::from("i"),
+                            span: Span::default(),
                         },
-                        expr: Expression::Compound {
-                            left: Value::Identifier(Identifier::ArrAccess {
+                        expr: Expression::compound(
+                            Value::Identifier(Identifier::ArrAccess {
                                 name: String::from("arr"),
                                 index: String::from("a"),
-                            }),
-                            op: ExprOp::Plus,
-                            right: Value::Num(1),
-                        },
+                                span: Span::default(),
+                            })
+                            .into(),
+                            ExprOp::Plus,
+                            Value::Num {
+                                value: 1,
+                                span: Span::default(),
+                            }
+                            .into(),
+                        ),
+                        span: Span::default(),
                     },
                 ],
+                span: Span::default(),
             },
             Command::Write {
-                value: Value::Identifier(Identifier::VarAccess {
+                values: vec![Value::Identifier(Identifier::VarAccess {
                     name: String::from("i"),
-                }),
+                    span: Span::default(),
+                })],
+                span: Span::default(),
             },
         ],
+        span: Span::default(),
     };
 
     let result = verify(program);
@@ -435,3 +621,1262 @@ fn for_complex_err() {
 
     assert_eq!(result, Err(expected_errors));
 }
+
+#[test]
+fn uninitialized_err() {
+    let program = parse(
+        r#"
+        DECLARE a, b
+        BEGIN
+            READ b;
+            b ASSIGN a;
+            WRITE a;
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![
+        Error::UninitializedVariable {
+            name: String::from("a"),
+        },
+        Error::UninitializedVariable {
+            name: String::from("a"),
+        },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn initialized_in_both_branches_ok() {
+    let program = parse(
+        r#"
+        DECLARE a, b
+        BEGIN
+            READ b;
+            IF b GE 0 THEN
+                a ASSIGN 1;
+            ELSE
+                READ a;
+            ENDIF
+            WRITE a;
+        END
+    "#,
+    );
+
+    assert_eq!(warnings(program), vec![]);
+}
+
+#[test]
+fn initialized_in_one_branch_warning() {
+    let program = parse(
+        r#"
+        DECLARE a, b
+        BEGIN
+            READ b;
+            IF b GE 0 THEN
+                a ASSIGN 1;
+            ENDIF
+            WRITE a;
+        END
+    "#,
+    );
+
+    let expected_warnings = vec![Warning::PossiblyUninitializedVariable {
+        name: String::from("a"),
+    }];
+
+    assert_eq!(warnings(program), expected_warnings);
+}
+
+#[test]
+fn initialized_in_loops() {
+    let program = parse(
+        r#"
+        DECLARE a, b, c, d
+        BEGIN
+            READ a;
+            WHILE a GE 0 DO
+                b ASSIGN a;
+                a ASSIGN a MINUS 1;
+            ENDWHILE
+            DO
+                c ASSIGN a;
+                a ASSIGN a PLUS 1;
+            WHILE a LE 0 ENDDO
+            FOR i FROM a TO 10 DO
+                d ASSIGN i;
+            ENDFOR
+            WRITE b;
+            WRITE c;
+            WRITE d;
+        END
+    "#,
+    );
+
+    let expected_warnings = vec![
+        Warning::PossiblyUninitializedVariable {
+            name: String::from("b"),
+        },
+        Warning::PossiblyUninitializedVariable {
+            name: String::from("d"),
+        },
+    ];
+
+    assert_eq!(warnings(program), expected_warnings);
+}
+
+#[test]
+fn initialized_in_previous_iteration_warning() {
+    let program = parse(
+        r#"
+        DECLARE a, b
+        BEGIN
+            READ a;
+            WHILE a GE 0 DO
+                WRITE b;
+                b ASSIGN a;
+                a ASSIGN a MINUS 1;
+            ENDWHILE
+        END
+    "#,
+    );
+
+    let expected_warnings = vec![Warning::PossiblyUninitializedVariable {
+        name: String::from("b"),
+    }];
+
+    assert_eq!(warnings(program), expected_warnings);
+}
+
+#[test]
+fn nested_expression_err() {
+    let program = parse(
+        r#"
+        DECLARE a, b, t(0:3)
+        BEGIN
+            READ a;
+            a ASSIGN (a PLUS 1) TIMES (t(a) MINUS (c DIV (b MOD t)));
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![
+        Error::UndeclaredVariable {
+            name: String::from("c"),
+        },
+        Error::InvalidVariableUsage {
+            name: String::from("t"),
+        },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn nested_expression_uninitialized_err() {
+    let program = parse(
+        r#"
+        DECLARE a, b, c
+        BEGIN
+            READ a;
+            c ASSIGN a TIMES (2 PLUS a DIV (b MINUS a));
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![Error::UninitializedVariable {
+        name: String::from("b"),
+    }];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn logical_condition_err() {
+    let program = parse(
+        r#"
+        DECLARE a, t(0:3)
+        BEGIN
+            READ a;
+            WHILE a GE 0 AND NOT (t EQ 1 OR b LE a) DO
+                a ASSIGN a MINUS 1;
+            ENDWHILE
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![
+        Error::InvalidVariableUsage {
+            name: String::from("t"),
+        },
+        Error::UndeclaredVariable {
+            name: String::from("b"),
+        },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn uninitialized_array_err() {
+    let program = parse(
+        r#"
+        DECLARE a, arr(0:10)
+        BEGIN
+            a ASSIGN arr(1);
+            READ a;
+            WRITE arr(a);
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![
+        Error::UninitializedVariable {
+            name: String::from("arr"),
+        },
+        Error::UninitializedVariable {
+            name: String::from("arr"),
+        },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn partially_initialized_array_ok() {
+    let program = parse(
+        r#"
+        DECLARE arr(0:10)
+        BEGIN
+            FOR i FROM 0 TO 5 DO
+                arr(i) ASSIGN i;
+            ENDFOR
+            WRITE arr(10);
+        END
+    "#,
+    );
+
+    assert_eq!(warnings(program), vec![]);
+}
+
+#[test]
+fn unused_declaration_warning() {
+    let program = parse(
+        r#"
+        DECLARE a, b, arr(0:10)
+        BEGIN
+            READ a;
+            WRITE a;
+        END
+    "#,
+    );
+
+    let expected_warnings = vec![
+        Warning::UnusedDeclaration {
+            name: String::from("b"),
+        },
+        Warning::UnusedDeclaration {
+            name: String::from("arr"),
+        },
+    ];
+
+    assert_eq!(warnings(program), expected_warnings);
+}
+
+#[test]
+fn empty_for_loop_warning() {
+    let program = parse(
+        r#"
+        BEGIN
+            FOR i FROM 10 TO 1 DO
+                WRITE i;
+            ENDFOR
+            FOR j FROM 1 DOWNTO 10 DO
+                WRITE j;
+            ENDFOR
+            FOR k FROM 1 TO 1 DO
+                WRITE k;
+            ENDFOR
+        END
+    "#,
+    );
+
+    let expected_warnings = vec![
+        Warning::EmptyForLoop {
+            counter: String::from("i"),
+        },
+        Warning::EmptyForLoop {
+            counter: String::from("j"),
+        },
+    ];
+
+    assert_eq!(warnings(program), expected_warnings);
+}
+
+#[test]
+fn negative_for_step_warning() {
+    let program = parse(
+        r#"
+        DECLARE
+            n
+        BEGIN
+            READ n;
+            FOR i FROM 5 DOWNTO 0 STEP -2 DO
+                WRITE i;
+            ENDFOR
+            FOR j FROM 1 TO n STEP -4 DO
+                WRITE j;
+            ENDFOR
+            FOR k FROM 1 TO n STEP 2 DO
+                WRITE k;
+            ENDFOR
+        END
+    "#,
+    );
+
+    let expected_warnings = vec![
+        Warning::NegativeForStep {
+            counter: String::from("i"),
+        },
+        Warning::NegativeForStep {
+            counter: String::from("j"),
+        },
+    ];
+
+    assert_eq!(warnings(program), expected_warnings);
+}
+
+#[test]
+fn constant_write_in_loop_warning() {
+    let program = parse(
+        r#"
+        DECLARE a
+        BEGIN
+            WRITE 1;
+            READ a;
+            WHILE a GE 0 DO
+                WRITE 2;
+                a ASSIGN a MINUS 1;
+            ENDWHILE
+        END
+    "#,
+    );
+
+    assert_eq!(warnings(program), vec![Warning::ConstantWriteInLoop]);
+}
+
+#[test]
+fn constant_index_in_counter_loop_warning() {
+    let program = parse(
+        r#"
+        DECLARE arr(0:10), other(0:10)
+        BEGIN
+            FOR i FROM 0 TO 10 DO
+                arr(i) ASSIGN i;
+                other(2) ASSIGN i;
+                FOR j FROM 0 TO 10 DO
+                    arr(5) ASSIGN j;
+                ENDFOR
+            ENDFOR
+            WRITE other(2);
+        END
+    "#,
+    );
+
+    let expected_warnings = vec![Warning::ConstantIndexInCounterLoop {
+        name: String::from("arr"),
+        index: 5,
+        counter: String::from("i"),
+    }];
+
+    assert_eq!(warnings(program), expected_warnings);
+}
+
+#[test]
+fn diagnostic_spans() {
+    let text = r#"
+        DECLARE a, b
+        BEGIN
+            READ a;
+            WRITE b;
+        END
+    "#;
+
+    let diagnostics = verify_with_diagnostics(parse(text)).unwrap_err();
+    let located: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.is_error(),
+                &text[diagnostic.span.start..diagnostic.span.end],
+            )
+        })
+        .collect();
+
+    assert_eq!(located, vec![(true, "b")]);
+    assert_eq!(
+        (diagnostics[0].span.line, diagnostics[0].span.column),
+        (5, 19)
+    );
+}
+
+#[test]
+fn warnings_dont_fail_verification() {
+    let program = parse(
+        r#"
+        DECLARE a, b
+        BEGIN
+            READ a;
+            WRITE a;
+        END
+    "#,
+    );
+
+    assert!(verify(program).is_ok());
+}
+
+#[test]
+fn duplicate_declaration_err() {
+    let program = parse(
+        r#"
+        DECLARE a, b, a(1:3), b
+        BEGIN
+            READ a;
+            WRITE a;
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![
+        Error::DuplicateDeclaration {
+            name: String::from("a"),
+        },
+        Error::DuplicateDeclaration {
+            name: String::from("b"),
+        },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn counter_shadowing_global_err() {
+    let program = parse(
+        r#"
+        DECLARE i
+        BEGIN
+            i ASSIGN 1;
+            FOR i FROM 1 TO 10 DO
+                WRITE i;
+            ENDFOR
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![Error::CounterShadowing {
+        name: String::from("i"),
+    }];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn counter_shadowing_counter_err() {
+    let program = parse(
+        r#"
+        BEGIN
+            FOR i FROM 1 TO 10 DO
+                FOR j FROM 1 TO i DO
+                    FOR i FROM j TO 10 DO
+                        WRITE i;
+                    ENDFOR
+                ENDFOR
+            ENDFOR
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![Error::CounterShadowing {
+        name: String::from("i"),
+    }];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn sequential_counters_ok() {
+    let program = parse(
+        r#"
+        BEGIN
+            FOR i FROM 1 TO 10 DO
+                WRITE i;
+            ENDFOR
+            FOR i FROM 10 DOWNTO 1 DO
+                WRITE i;
+            ENDFOR
+        END
+    "#,
+    );
+
+    assert!(verify(program).is_ok());
+}
+
+#[test]
+fn constant_index_out_of_bounds_err() {
+    let program = parse(
+        r#"
+        DECLARE arr(5:10)
+        BEGIN
+            arr(4) ASSIGN 1;
+            arr(10) ASSIGN 1;
+            WRITE arr(11);
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![
+        Error::ArrayIndexOutOfBounds {
+            name: String::from("arr"),
+            index: 4,
+            start: 5,
+            end: 10,
+        },
+        Error::ArrayIndexOutOfBounds {
+            name: String::from("arr"),
+            index: 11,
+            start: 5,
+            end: 10,
+        },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+const COUNTER_INDICES: &str = r#"
+    DECLARE arr(5:10), n
+    BEGIN
+        FOR i FROM 5 TO 10 DO
+            arr(i) ASSIGN i;
+        ENDFOR
+        FOR i FROM 10 DOWNTO 4 DO
+            WRITE arr(i);
+        ENDFOR
+        FOR i FROM 6 TO 11 DO
+            WRITE arr(i);
+        ENDFOR
+        FOR i FROM 20 TO 1 DO
+            WRITE arr(i);
+        ENDFOR
+        READ n;
+        FOR i FROM 0 TO n DO
+            WRITE arr(i);
+        ENDFOR
+    END
+"#;
+
+#[test]
+fn counter_index_out_of_bounds_err() {
+    let options = Options {
+        check_counter_indices: true,
+    };
+    let result = verify_with_options(parse(COUNTER_INDICES), options);
+
+    let errors: Vec<_> = result
+        .unwrap_err()
+        .into_iter()
+        .filter_map(|diagnostic| match diagnostic.kind {
+            DiagnosticKind::Error(error) => Some(error),
+            DiagnosticKind::Warning(_) => None,
+        })
+        .collect();
+    let expected_errors = vec![
+        Error::ArrayIndexOutOfBounds {
+            name: String::from("arr"),
+            index: 4,
+            start: 5,
+            end: 10,
+        },
+        Error::ArrayIndexOutOfBounds {
+            name: String::from("arr"),
+            index: 11,
+            start: 5,
+            end: 10,
+        },
+    ];
+
+    assert_eq!(errors, expected_errors);
+}
+
+#[test]
+fn counter_index_unchecked_by_default() {
+    assert!(verify(parse(COUNTER_INDICES)).is_ok());
+}
+
+#[test]
+fn counter_index_steps() {
+    let options = Options {
+        check_counter_indices: true,
+    };
+    let errors = |code: &str| match verify_with_options(parse(code), options) {
+        Ok(_) => vec![],
+        Err(diagnostics) => diagnostics
+            .into_iter()
+            .filter_map(|diagnostic| match diagnostic.kind {
+                DiagnosticKind::Error(error) => Some(error),
+                DiagnosticKind::Warning(_) => None,
+            })
+            .collect(),
+    };
+
+    // the counters stop at 9 and 1
+    let reaching = r#"
+        DECLARE t(0:9)
+        BEGIN
+            FOR i FROM 0 TO 10 STEP 3 DO
+                t(i) ASSIGN i;
+            ENDFOR
+            FOR i FROM 9 DOWNTO 0 STEP -4 DO
+                t(i) ASSIGN i;
+            ENDFOR
+        END
+    "#;
+    assert_eq!(errors(reaching), vec![]);
+
+    let passing = r#"
+        DECLARE t(0:9), s
+        BEGIN
+            FOR i FROM 0 TO 10 STEP 5 DO
+                t(i) ASSIGN i;
+            ENDFOR
+            s ASSIGN 3;
+            FOR i FROM 0 TO 10 STEP s DO
+                t(i) ASSIGN i;
+            ENDFOR
+        END
+    "#;
+    let out_of_bounds = Error::ArrayIndexOutOfBounds {
+        name: String::from("t"),
+        index: 10,
+        start: 0,
+        end: 9,
+    };
+    assert_eq!(errors(passing), vec![out_of_bounds.clone(), out_of_bounds]);
+}
+
+#[test]
+fn invalid_array_range_indices_err() {
+    let program = parse(
+        r#"
+        DECLARE a, b(11:10), c(0:2, 3:1)
+        BEGIN
+            b(10) ASSIGN 1;
+            a ASSIGN b(11);
+            c(1, 7) ASSIGN 1;
+            c(5, 7) ASSIGN 1;
+        END
+    "#,
+    );
+
+    // the range is reported once, not along with every access
+    let result = verify(program);
+    let name = |name: &str| String::from(name);
+    let expected_errors = vec![
+        Error::InvalidArrayRange {
+            name: name("b"),
+            start: 11,
+            end: 10,
+        },
+        Error::InvalidArrayRange {
+            name: name("c"),
+            start: 3,
+            end: 1,
+        },
+        Error::ArrayIndexOutOfBounds {
+            name: name("c"),
+            index: 5,
+            start: 0,
+            end: 2,
+        },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn procedures_ok() {
+    let program = parse(
+        r#"
+        PROCEDURE fill(T t, n, v) IS
+        DECLARE
+            i
+        BEGIN
+            i ASSIGN 0;
+            WHILE i LEQ n DO
+                t(i) ASSIGN v;
+                i ASSIGN i PLUS 1;
+            ENDWHILE
+        END
+        PROCEDURE first(T t, v) IS
+        BEGIN
+            fill(t, v, v);
+            v ASSIGN t(0);
+        END
+        DECLARE a, t(0:9)
+        BEGIN
+            READ a;
+            first(t, a);
+            WRITE a;
+        END
+    "#,
+    );
+
+    assert_eq!(warnings(program), vec![]);
+}
+
+#[test]
+fn procedure_scope_err() {
+    let program = parse(
+        r#"
+        PROCEDURE p(a, T t, a) IS
+        DECLARE
+            t, b
+        BEGIN
+            t(b) ASSIGN a(0);
+            WRITE t;
+            WRITE c;
+        END
+        DECLARE c
+        BEGIN
+            READ c;
+            WRITE b;
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let name = |name: &str| String::from(name);
+    let expected_errors = vec![
+        Error::DuplicateDeclaration { name: name("a") },
+        Error::DuplicateDeclaration { name: name("t") },
+        Error::InvalidVariableUsage { name: name("t") },
+        Error::InvalidVariableUsage { name: name("a") },
+        Error::UndeclaredVariable { name: name("c") },
+        Error::UndeclaredVariable { name: name("b") },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn procedure_call_err() {
+    let program = parse(
+        r#"
+        PROCEDURE p(a, T t) IS
+        BEGIN
+            p(a, t);
+            q(a);
+        END
+        PROCEDURE q(a) IS
+        BEGIN
+            a ASSIGN 1;
+        END
+        DECLARE a, t(0:3)
+        BEGIN
+            r();
+            q(a, a);
+            p(t, a);
+            p(b, t);
+            FOR i FROM 0 TO 3 DO
+                q(i);
+            ENDFOR
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let name = |name: &str| String::from(name);
+    let expected_errors = vec![
+        Error::RecursiveCall { name: name("p") },
+        Error::UndeclaredProcedure { name: name("q") },
+        Error::UndeclaredProcedure { name: name("r") },
+        Error::ArgumentCountMismatch {
+            name: name("q"),
+            expected: 1,
+            found: 2,
+        },
+        Error::InvalidVariableUsage { name: name("t") },
+        Error::InvalidVariableUsage { name: name("a") },
+        Error::UndeclaredVariable { name: name("b") },
+        Error::ForCounterModification { name: name("i") },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn procedure_initialization() {
+    let program = parse(
+        r#"
+        PROCEDURE get(a, b) IS
+        DECLARE
+            c, d
+        BEGIN
+            WRITE b;
+            READ a;
+            WRITE c;
+        END
+        DECLARE x, y
+        BEGIN
+            get(x, y);
+            WRITE x;
+        END
+    "#,
+    );
+
+    let result = verify_with_diagnostics(program).map(|_| ());
+    let diagnostics = result.expect_err("verification succeeded");
+    let kinds: Vec<_> = diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.kind)
+        .collect();
+    let name = |name: &str| String::from(name);
+
+    assert_eq!(
+        kinds,
+        vec![
+            DiagnosticKind::Warning(Warning::UnusedDeclaration { name: name("d") }),
+            DiagnosticKind::Error(Error::UninitializedVariable { name: name("c") }),
+            DiagnosticKind::Error(Error::UninitializedVariable { name: name("y") }),
+        ]
+    );
+}
+
+#[test]
+fn procedure_argument_initialization() {
+    let program = parse(
+        r#"
+        PROCEDURE p(x) IS
+        BEGIN
+            WRITE x;
+        END
+        PROCEDURE set(x, y) IS
+        BEGIN
+            IF y GE 0 THEN
+                x ASSIGN y;
+            ENDIF
+        END
+        PROCEDURE q(x, y) IS
+        BEGIN
+            p(x);
+            y ASSIGN 1;
+        END
+        PROCEDURE r(x) IS
+        BEGIN
+            x ASSIGN 1;
+            p(x);
+        END
+        DECLARE a, b, c, d, e
+        BEGIN
+            p(a);
+            READ b;
+            set(c, b);
+            WRITE c;
+            q(d, e);
+            WRITE e;
+            r(a);
+            WRITE a;
+        END
+    "#,
+    );
+
+    let result = verify_with_diagnostics(program).map(|_| ());
+    let diagnostics = result.expect_err("verification succeeded");
+    let kinds: Vec<_> = diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.kind)
+        .collect();
+    let name = |name: &str| String::from(name);
+
+    // only parameters read before they're assigned need initialized arguments,
+    // and only the ones always assigned are initialized by the call
+    assert_eq!(
+        kinds,
+        vec![
+            DiagnosticKind::Error(Error::UninitializedVariable { name: name("a") }),
+            DiagnosticKind::Warning(Warning::PossiblyUninitializedVariable { name: name("c") }),
+            DiagnosticKind::Error(Error::UninitializedVariable { name: name("d") }),
+        ]
+    );
+}
+
+#[test]
+fn unresolved_include_err() {
+    let program = parse(
+        r#"
+        BEGIN
+            INCLUDE "lib.imp";
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![Error::UnresolvedInclude {
+        path: String::from("lib.imp"),
+    }];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn included_diagnostic_spans() {
+    let directory = std::env::temp_dir().join(format!("gembiler-verifier-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(
+        directory.join("main.imp"),
+        "DECLARE a BEGIN INCLUDE \"lib.imp\"; END",
+    )
+    .unwrap();
+    std::fs::write(
+        directory.join("lib.imp"),
+        "DECLARE a, b BEGIN\n    WRITE b; END",
+    )
+    .unwrap();
+    let program = parser::parse_file(directory.join("main.imp")).unwrap();
+
+    let diagnostics = verify_with_diagnostics(program).unwrap_err();
+    let spans: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.span.file,
+                diagnostic.span.line,
+                diagnostic.span.column,
+            )
+        })
+        .collect();
+
+    // `a` is unused in the main file, `b` is uninitialized in the included one
+    assert_eq!(spans, vec![(0, 1, 9), (1, 2, 11)]);
+}
+
+#[test]
+fn multiple_targets_ok() {
+    let program = parse(
+        r#"
+        DECLARE a, t(0:3)
+        BEGIN
+            READ a, t(a);
+            WRITE t(a), a;
+        END
+    "#,
+    );
+
+    assert!(verify(program).is_ok());
+
+    // the index is read after the array element
+    let program = parse("DECLARE a, t(0:3) BEGIN READ t(a), a; END");
+    let expected_errors = vec![Error::UninitializedVariable {
+        name: String::from("a"),
+    }];
+
+    assert_eq!(verify(program), Err(expected_errors));
+}
+
+#[test]
+fn multiple_targets_err() {
+    let program = parse(
+        r#"
+        DECLARE a, t(0:3)
+        BEGIN
+            READ a, t(a);
+            FOR i FROM 0 TO 3 DO
+                READ a, i, b;
+                WRITE a, c;
+            ENDFOR
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let name = |name: &str| String::from(name);
+    let expected_errors = vec![
+        Error::ForCounterModification { name: name("i") },
+        Error::UndeclaredVariable { name: name("b") },
+        Error::UndeclaredVariable { name: name("c") },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn for_step_err() {
+    let program = parse(
+        r#"
+        DECLARE n, s
+        BEGIN
+            READ n;
+            FOR i FROM 0 TO n STEP 0 DO
+                WRITE i;
+            ENDFOR
+            FOR i FROM n DOWNTO 0 STEP -2 DO
+                WRITE i;
+            ENDFOR
+            FOR i FROM n DOWNTO 0 STEP -0 DO
+                WRITE i;
+            ENDFOR
+            FOR i FROM 0 TO n STEP i DO
+                WRITE i;
+            ENDFOR
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let name = |name: &str| String::from(name);
+    let expected_errors = vec![
+        Error::InvalidForStep { name: name("i") },
+        Error::InvalidForStep { name: name("i") },
+        Error::UndeclaredVariable { name: name("i") },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+
+    let program = parse(
+        r#"
+        DECLARE n, s
+        BEGIN
+            READ n;
+            FOR i FROM 0 TO n STEP s DO
+                WRITE i;
+            ENDFOR
+        END
+    "#,
+    );
+    let expected_errors = vec![Error::UninitializedVariable { name: name("s") }];
+
+    assert_eq!(verify(program), Err(expected_errors));
+}
+
+#[test]
+fn loop_control_outside_loop_err() {
+    let program = parse(
+        r#"
+        PROCEDURE p(a) IS
+        BEGIN
+            IF a EQ 0 THEN
+                CONTINUE;
+            ENDIF
+        END
+        DECLARE a
+        BEGIN
+            READ a;
+            WHILE a GE 0 DO
+                a ASSIGN a MINUS 1;
+                IF a EQ 3 THEN
+                    BREAK;
+                ENDIF
+                CONTINUE;
+            ENDWHILE
+            FOR i FROM 0 TO a DO
+                DO
+                    BREAK;
+                WHILE a GE 0 ENDDO
+                CONTINUE;
+            ENDFOR
+            BREAK;
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![Error::ContinueOutsideLoop, Error::BreakOutsideLoop];
+
+    assert_eq!(result, Err(expected_errors));
+}
+
+#[test]
+fn initialized_before_break() {
+    let program = parse(
+        r#"
+        DECLARE a, b, c, n
+        BEGIN
+            READ n;
+            DO
+                IF n EQ 0 THEN
+                    BREAK;
+                ENDIF
+                a ASSIGN n;
+                IF n EQ 1 THEN
+                    b ASSIGN 1;
+                ELSE
+                    CONTINUE;
+                ENDIF
+                WRITE b;
+                c ASSIGN b;
+            WHILE c LE 10 ENDDO
+            WRITE a;
+        END
+    "#,
+    );
+
+    let name = |name: &str| String::from(name);
+    let expected_warnings = vec![
+        Warning::PossiblyUninitializedVariable { name: name("c") },
+        Warning::PossiblyUninitializedVariable { name: name("a") },
+    ];
+
+    assert_eq!(warnings(program), expected_warnings);
+}
+
+#[test]
+fn multi_dimensional_arrays_err() {
+    let program = parse(
+        r#"
+        PROCEDURE p(T a) IS
+        BEGIN
+            a(0, 0) ASSIGN 1;
+        END
+        DECLARE t(0:3, 5:2), u(0:2, -1:1), v(0:3), n
+        BEGIN
+            READ n;
+            u(3, n) ASSIGN 1;
+            u(n) ASSIGN 1;
+            v(n, n) ASSIGN 1;
+            WRITE u(1, -2);
+            WRITE u(2);
+            u(n, 0) ASSIGN u(n, n);
+            p(u);
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let name = |name: &str| String::from(name);
+    let mismatch = |name: &str, expected, found| Error::ArrayDimensionMismatch {
+        name: String::from(name),
+        expected,
+        found,
+    };
+    let expected_errors = vec![
+        mismatch("a", 1, 2),
+        Error::InvalidArrayRange {
+            name: name("t"),
+            start: 5,
+            end: 2,
+        },
+        Error::ArrayIndexOutOfBounds {
+            name: name("u"),
+            index: 3,
+            start: 0,
+            end: 2,
+        },
+        mismatch("u", 2, 1),
+        mismatch("v", 1, 2),
+        Error::ArrayIndexOutOfBounds {
+            name: name("u"),
+            index: -2,
+            start: -1,
+            end: 1,
+        },
+        mismatch("u", 2, 1),
+        mismatch("u", 2, 1),
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+
+    let program = parse(
+        r#"
+        DECLARE u(0:2, -1:1)
+        BEGIN
+            FOR i FROM 0 TO 2 DO
+                FOR j FROM -1 TO 2 DO
+                    u(i, j) ASSIGN 1;
+                ENDFOR
+            ENDFOR
+        END
+    "#,
+    );
+
+    let options = Options {
+        check_counter_indices: true,
+    };
+    let result = verify_with_options(program, options);
+
+    let errors: Vec<_> = result
+        .unwrap_err()
+        .into_iter()
+        .map(|diagnostic| diagnostic.kind)
+        .collect();
+    let expected_errors = vec![DiagnosticKind::Error(Error::ArrayIndexOutOfBounds {
+        name: name("u"),
+        index: 2,
+        start: -1,
+        end: 1,
+    })];
+
+    assert_eq!(errors, expected_errors);
+}
+
+#[test]
+fn expression_indices_err() {
+    let program = parse(
+        r#"
+        DECLARE t(0:3), u(0:2, 0:2), n
+        BEGIN
+            READ n;
+            WRITE t(n PLUS x);
+            WRITE t(t PLUS 1);
+            WRITE t(u(n));
+            WRITE t(u(n, 3));
+            WRITE n(n PLUS 1);
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let name = |name: &str| String::from(name);
+    let expected_errors = vec![
+        Error::UndeclaredVariable { name: name("x") },
+        Error::InvalidVariableUsage { name: name("t") },
+        Error::ArrayDimensionMismatch {
+            name: name("u"),
+            expected: 2,
+            found: 1,
+        },
+        Error::ArrayIndexOutOfBounds {
+            name: name("u"),
+            index: 3,
+            start: 0,
+            end: 2,
+        },
+        Error::InvalidVariableUsage { name: name("n") },
+    ];
+
+    assert_eq!(result, Err(expected_errors));
+
+    let program = parse(
+        r#"
+        DECLARE t(0:3), n, m
+        BEGIN
+            READ n;
+            t(0) ASSIGN n;
+            t(t(n) PLUS m) ASSIGN 1;
+        END
+    "#,
+    );
+
+    let result = verify(program);
+    let expected_errors = vec![Error::UninitializedVariable { name: name("m") }];
+
+    assert_eq!(result, Err(expected_errors));
+}